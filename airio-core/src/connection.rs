@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use crate::Multiaddr;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Endpoint {
@@ -30,11 +30,11 @@ impl Endpoint {
 #[derive(PartialEq, Eq, Debug, Clone, Hash)]
 pub enum ConnectedPoint {
     Dialer {
-        addr: SocketAddr,
+        addr: Multiaddr,
     },
     Listener {
-        local_addr: SocketAddr,
-        remote_addr: SocketAddr,
+        local_addr: Multiaddr,
+        remote_addr: Multiaddr,
     },
 }
 
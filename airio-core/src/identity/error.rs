@@ -0,0 +1,44 @@
+/// 密钥解码失败
+#[derive(Debug, thiserror::Error)]
+pub enum DecodingError {
+    #[error("Invalid key length for {key_type}: {len}")]
+    InvalidLength { key_type: &'static str, len: usize },
+    #[error("Invalid {0} key")]
+    InvalidKey(&'static str),
+    #[error("Unsupported key type: {0}")]
+    UnsupportedKeyType(u64),
+    #[error("Invalid protobuf encoding")]
+    InvalidProtobuf,
+    #[error("Invalid PKCS#8 or SPKI encoding: {0}")]
+    Pkcs8(String),
+}
+
+/// `PeerId` 解析失败
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Invalid base58 encoding")]
+    Base58,
+    #[error("Invalid multihash")]
+    InvalidMultihash,
+    #[error("Unsupported multihash code: {0:#x}")]
+    UnsupportedCode(u64),
+}
+
+/// 密钥存储读写失败
+#[derive(Debug, thiserror::Error)]
+pub enum KeyStoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Key file is accessible by other users (mode {0:o})")]
+    InsecurePermissions(u32),
+    #[error("Malformed key file or continuity statement")]
+    Malformed,
+    #[error("Unsupported key file version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Key file is encrypted but no passphrase was given")]
+    PassphraseRequired,
+    #[error("Failed to decrypt key file: wrong passphrase or corrupted file")]
+    Decryption,
+    #[error("Invalid key: {0}")]
+    Key(#[from] DecodingError),
+}
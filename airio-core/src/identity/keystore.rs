@@ -0,0 +1,665 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use zeroize::Zeroizing;
+
+use super::{KeyStoreError, Keypair, PublicKey};
+
+const MAGIC: &[u8; 8] = b"AIRIOKEY";
+const VERSION: u8 = 1;
+
+const PLAINTEXT: u8 = 0;
+/// scrypt 派生密钥，XChaCha20-Poly1305 加密，文件头作为附加数据
+const ENCRYPTED: u8 = 1;
+
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// 读取时接受的 scrypt 参数上限，避免被篡改的文件耗尽内存或 CPU
+const MAX_SCRYPT_LOG_N: u8 = 17;
+const MAX_SCRYPT_R: u32 = 8;
+const MAX_SCRYPT_P: u32 = 1;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// 签名连续性声明时的前缀，避免签名被挪作他用
+const CONTINUITY_DOMAIN: &[u8] = b"airio-key-continuity:";
+
+/// 节点密钥的文件存储，使重启后的节点保持同一个 `PeerId`。
+///
+/// 文件在 Unix 上以 `0600` 权限创建，读取时拒绝组或其他用户可访问的文件。
+/// 设置口令后，内容使用 scrypt 派生的密钥以 XChaCha20-Poly1305 加密；
+/// 未加密的旧文件仍可读取，下次写入时加密。
+///
+/// [`KeyStore::rotate`] 轮换密钥时保留旧密钥，在宽限期内可由它签发
+/// [`ContinuityStatement`]，向对端证明新旧 `PeerId` 属于同一节点。
+#[derive(Clone)]
+pub struct KeyStore {
+    path: PathBuf,
+    passphrase: Option<Zeroizing<String>>,
+    grace_period: Duration,
+}
+
+impl KeyStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: None,
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// 加密密钥文件的口令
+    pub fn passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = Some(Zeroizing::new(passphrase.into()));
+        self
+    }
+
+    /// 轮换后旧密钥的保留时间，默认 7 天
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 读取当前密钥
+    pub fn load(&self) -> Result<Keypair, KeyStoreError> {
+        Ok(self.read()?.current)
+    }
+
+    /// 读取当前密钥，文件不存在时生成 ed25519 密钥并保存
+    pub fn load_or_generate(&self) -> Result<Keypair, KeyStoreError> {
+        match self.load() {
+            Err(KeyStoreError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                let keypair = Keypair::generate_ed25519();
+                self.save(&keypair)?;
+                tracing::info!(
+                    "Generated node key {} at {}",
+                    keypair.to_peer_id(),
+                    self.path.display()
+                );
+                Ok(keypair)
+            }
+            result => result,
+        }
+    }
+
+    /// 以 `keypair` 覆盖存储，已保留的旧密钥一并丢弃
+    pub fn save(&self, keypair: &Keypair) -> Result<(), KeyStoreError> {
+        self.write(&Stored {
+            current: keypair.clone(),
+            previous: None,
+        })
+    }
+
+    /// 宽限期内的旧密钥
+    pub fn previous(&self) -> Result<Option<Keypair>, KeyStoreError> {
+        let stored = self.read()?;
+        Ok(stored
+            .previous
+            .filter(|retired| self.valid_until(retired) > unix_time(SystemTime::now()))
+            .map(|retired| retired.keypair))
+    }
+
+    /// 以 `next` 替换当前密钥，当前密钥保留到宽限期结束，并返回其对 `next` 的连续性声明
+    pub fn rotate(&self, next: &Keypair) -> Result<ContinuityStatement, KeyStoreError> {
+        let stored = self.read()?;
+        let now = unix_time(SystemTime::now());
+        let retired = Retired {
+            keypair: stored.current,
+            retired_at: now,
+        };
+        let statement = ContinuityStatement::sign(
+            &retired.keypair,
+            next.public(),
+            now,
+            self.valid_until(&retired),
+        );
+        self.write(&Stored {
+            current: next.clone(),
+            previous: Some(retired),
+        })?;
+        tracing::info!(
+            "Rotated node key {} to {}",
+            statement.previous.to_peer_id(),
+            statement.current.to_peer_id()
+        );
+        Ok(statement)
+    }
+
+    /// 由宽限期内的旧密钥重新签发连续性声明，有效期截止到宽限期结束
+    pub fn continuity_statement(&self) -> Result<Option<ContinuityStatement>, KeyStoreError> {
+        let stored = self.read()?;
+        let now = unix_time(SystemTime::now());
+        Ok(stored
+            .previous
+            .filter(|retired| self.valid_until(retired) > now)
+            .map(|retired| {
+                ContinuityStatement::sign(
+                    &retired.keypair,
+                    stored.current.public(),
+                    now,
+                    self.valid_until(&retired),
+                )
+            }))
+    }
+
+    fn valid_until(&self, retired: &Retired) -> u64 {
+        retired
+            .retired_at
+            .saturating_add(self.grace_period.as_secs())
+    }
+
+    fn read(&self) -> Result<Stored, KeyStoreError> {
+        let mut file = File::open(&self.path)?;
+        check_permissions(&file.metadata()?)?;
+        let mut contents = Zeroizing::new(Vec::new());
+        file.read_to_end(&mut contents)?;
+
+        let mut reader = Reader(&contents);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(KeyStoreError::Malformed);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(KeyStoreError::UnsupportedVersion(version));
+        }
+        match reader.u8()? {
+            PLAINTEXT => Stored::decode(reader.0),
+            ENCRYPTED => {
+                let log_n = reader.u8()?;
+                let r = reader.u32()?;
+                let p = reader.u32()?;
+                let salt = reader.take(SALT_LENGTH)?;
+                let nonce = reader.take(NONCE_LENGTH)?;
+                if log_n > MAX_SCRYPT_LOG_N || r > MAX_SCRYPT_R || p > MAX_SCRYPT_P {
+                    return Err(KeyStoreError::Malformed);
+                }
+                let params = scrypt::Params::new(log_n, r, p, KEY_LENGTH)
+                    .map_err(|_| KeyStoreError::Malformed)?;
+                let passphrase = self
+                    .passphrase
+                    .as_ref()
+                    .ok_or(KeyStoreError::PassphraseRequired)?;
+                let header = &contents[..contents.len() - reader.0.len()];
+                let body = cipher(passphrase, salt, &params)
+                    .decrypt(
+                        XNonce::from_slice(nonce),
+                        Payload {
+                            msg: reader.0,
+                            aad: header,
+                        },
+                    )
+                    .map_err(|_| KeyStoreError::Decryption)?;
+                Stored::decode(&Zeroizing::new(body))
+            }
+            _ => Err(KeyStoreError::Malformed),
+        }
+    }
+
+    fn write(&self, stored: &Stored) -> Result<(), KeyStoreError> {
+        let body = stored.encode();
+        let mut contents = Zeroizing::new(Vec::with_capacity(body.len() + 64));
+        contents.extend_from_slice(MAGIC);
+        contents.push(VERSION);
+        match &self.passphrase {
+            None => {
+                contents.push(PLAINTEXT);
+                contents.extend_from_slice(&body);
+            }
+            Some(passphrase) => {
+                let salt: [u8; SALT_LENGTH] = rand::random();
+                let nonce: [u8; NONCE_LENGTH] = rand::random();
+                let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, KEY_LENGTH)
+                    .expect("valid scrypt parameters");
+                contents.push(ENCRYPTED);
+                contents.push(SCRYPT_LOG_N);
+                contents.extend_from_slice(&SCRYPT_R.to_be_bytes());
+                contents.extend_from_slice(&SCRYPT_P.to_be_bytes());
+                contents.extend_from_slice(&salt);
+                contents.extend_from_slice(&nonce);
+                let ciphertext = cipher(passphrase, &salt, &params)
+                    .encrypt(
+                        XNonce::from_slice(&nonce),
+                        Payload {
+                            msg: &body,
+                            aad: &contents,
+                        },
+                    )
+                    .expect("key store fits in a single AEAD message");
+                contents.extend_from_slice(&ciphertext);
+            }
+        }
+        write_private(&self.path, &contents)?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStore")
+            .field("path", &self.path)
+            .field("encrypted", &self.passphrase.is_some())
+            .field("grace_period", &self.grace_period)
+            .finish()
+    }
+}
+
+/// 文件中的密钥：当前密钥，以及可能存在的旧密钥
+struct Stored {
+    current: Keypair,
+    previous: Option<Retired>,
+}
+
+struct Retired {
+    keypair: Keypair,
+    /// 轮换时的 Unix 时间（秒）
+    retired_at: u64,
+}
+
+impl Stored {
+    /// `[当前密钥]` 后接可选的 `[u64 retired_at][旧密钥]`，密钥为带长度的 protobuf 编码
+    fn encode(&self) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        put_field(
+            &mut out,
+            &Zeroizing::new(self.current.to_protobuf_encoding()),
+        );
+        if let Some(retired) = &self.previous {
+            out.extend_from_slice(&retired.retired_at.to_be_bytes());
+            put_field(
+                &mut out,
+                &Zeroizing::new(retired.keypair.to_protobuf_encoding()),
+            );
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, KeyStoreError> {
+        let mut reader = Reader(bytes);
+        let current = Keypair::from_protobuf_encoding(reader.field()?)?;
+        let previous = if reader.0.is_empty() {
+            None
+        } else {
+            let retired_at = reader.u64()?;
+            let keypair = Keypair::from_protobuf_encoding(reader.field()?)?;
+            Some(Retired {
+                keypair,
+                retired_at,
+            })
+        };
+        reader.finish()?;
+        Ok(Stored { current, previous })
+    }
+}
+
+/// 旧密钥对新密钥的签名声明，证明两个 `PeerId` 属于同一节点
+#[derive(Clone, Debug)]
+pub struct ContinuityStatement {
+    previous: PublicKey,
+    current: PublicKey,
+    issued_at: u64,
+    valid_until: u64,
+    signature: Vec<u8>,
+}
+
+impl ContinuityStatement {
+    fn sign(previous: &Keypair, current: PublicKey, issued_at: u64, valid_until: u64) -> Self {
+        let previous_key = previous.public();
+        let signature = previous.sign(&Self::message(
+            &previous_key,
+            &current,
+            issued_at,
+            valid_until,
+        ));
+        Self {
+            previous: previous_key,
+            current,
+            issued_at,
+            valid_until,
+            signature,
+        }
+    }
+
+    fn message(
+        previous: &PublicKey,
+        current: &PublicKey,
+        issued_at: u64,
+        valid_until: u64,
+    ) -> Vec<u8> {
+        let mut message = CONTINUITY_DOMAIN.to_vec();
+        put_field(&mut message, &previous.to_protobuf_encoding());
+        put_field(&mut message, &current.to_protobuf_encoding());
+        message.extend_from_slice(&issued_at.to_be_bytes());
+        message.extend_from_slice(&valid_until.to_be_bytes());
+        message
+    }
+
+    /// 签发声明的旧密钥
+    pub fn previous(&self) -> &PublicKey {
+        &self.previous
+    }
+
+    /// 被声明的新密钥
+    pub fn current(&self) -> &PublicKey {
+        &self.current
+    }
+
+    pub fn issued_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.issued_at)
+    }
+
+    pub fn valid_until(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.valid_until)
+    }
+
+    /// 签名有效且尚未过期
+    pub fn verify(&self) -> bool {
+        unix_time(SystemTime::now()) < self.valid_until
+            && self.previous.verify(
+                &Self::message(
+                    &self.previous,
+                    &self.current,
+                    self.issued_at,
+                    self.valid_until,
+                ),
+                &self.signature,
+            )
+    }
+
+    /// `[旧公钥][新公钥][u64 issued_at][u64 valid_until][签名]`，
+    /// 公钥为 protobuf 编码，各字段带两字节大端长度
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_field(&mut out, &self.previous.to_protobuf_encoding());
+        put_field(&mut out, &self.current.to_protobuf_encoding());
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&self.valid_until.to_be_bytes());
+        put_field(&mut out, &self.signature);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyStoreError> {
+        let mut reader = Reader(bytes);
+        let previous = PublicKey::from_protobuf_encoding(reader.field()?)?;
+        let current = PublicKey::from_protobuf_encoding(reader.field()?)?;
+        let issued_at = reader.u64()?;
+        let valid_until = reader.u64()?;
+        let signature = reader.field()?.to_vec();
+        reader.finish()?;
+        Ok(Self {
+            previous,
+            current,
+            issued_at,
+            valid_until,
+            signature,
+        })
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8], params: &scrypt::Params) -> XChaCha20Poly1305 {
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    scrypt::scrypt(passphrase.as_bytes(), salt, params, key.as_mut())
+        .expect("output length matches parameters");
+    XChaCha20Poly1305::new(key.as_ref().into())
+}
+
+fn put_field(out: &mut Vec<u8>, field: &[u8]) {
+    out.extend_from_slice(&(field.len() as u16).to_be_bytes());
+    out.extend_from_slice(field);
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], KeyStoreError> {
+        let (head, rest) = self
+            .0
+            .split_at_checked(len)
+            .ok_or(KeyStoreError::Malformed)?;
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, KeyStoreError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, KeyStoreError> {
+        Ok(u32::from_be_bytes(
+            self.take(4)?.try_into().expect("length checked"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, KeyStoreError> {
+        Ok(u64::from_be_bytes(
+            self.take(8)?.try_into().expect("length checked"),
+        ))
+    }
+
+    fn field(&mut self) -> Result<&'a [u8], KeyStoreError> {
+        let len = u16::from_be_bytes(self.take(2)?.try_into().expect("length checked"));
+        self.take(len as usize)
+    }
+
+    fn finish(self) -> Result<(), KeyStoreError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(KeyStoreError::Malformed)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn check_permissions(metadata: &fs::Metadata) -> Result<(), KeyStoreError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(KeyStoreError::InsecurePermissions(mode));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_: &fs::Metadata) -> Result<(), KeyStoreError> {
+    Ok(())
+}
+
+/// 先写入仅属主可读写的临时文件，再原子地替换目标文件
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(parent)?;
+    }
+
+    // 临时文件名带随机后缀，多个进程同时写入时互不干扰
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+    let temp = PathBuf::from(temp);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let result = options.open(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试结束时删除的临时目录
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path =
+                std::env::temp_dir().join(format!("airio-keystore-{:016x}", rand::random::<u64>()));
+            fs::create_dir(&path).unwrap();
+            Self(path)
+        }
+
+        fn join(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn load_or_generate_keeps_peer_id() {
+        let dir = TempDir::new();
+        let store = KeyStore::new(dir.join("keys/node.key"));
+        let generated = store.load_or_generate().unwrap();
+        let loaded = KeyStore::new(store.path()).load_or_generate().unwrap();
+        assert_eq!(loaded.to_peer_id(), generated.to_peer_id());
+
+        let leftovers = fs::read_dir(dir.join("keys")).unwrap().count();
+        assert_eq!(leftovers, 1, "temporary file left behind");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_insecure_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let store = KeyStore::new(dir.join("node.key"));
+        store.save(&Keypair::generate_ed25519()).unwrap();
+        let mode = fs::metadata(store.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::set_permissions(store.path(), fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(
+            store.load(),
+            Err(KeyStoreError::InsecurePermissions(0o644))
+        ));
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let dir = TempDir::new();
+        let path = dir.join("node.key");
+        let keypair = Keypair::generate_ed25519();
+        KeyStore::new(&path)
+            .passphrase("correct horse")
+            .save(&keypair)
+            .unwrap();
+
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(contents[MAGIC.len() + 1], ENCRYPTED);
+        let loaded = KeyStore::new(&path)
+            .passphrase("correct horse")
+            .load()
+            .unwrap();
+        assert_eq!(loaded.to_peer_id(), keypair.to_peer_id());
+
+        assert!(matches!(
+            KeyStore::new(&path).passphrase("battery staple").load(),
+            Err(KeyStoreError::Decryption)
+        ));
+        assert!(matches!(
+            KeyStore::new(&path).load(),
+            Err(KeyStoreError::PassphraseRequired)
+        ));
+
+        let mut tampered = contents;
+        tampered[MAGIC.len() + 2] = MAX_SCRYPT_LOG_N + 1;
+        fs::write(&path, tampered).unwrap();
+        assert!(matches!(
+            KeyStore::new(&path).passphrase("correct horse").load(),
+            Err(KeyStoreError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn rotate_issues_continuity_statement() {
+        let dir = TempDir::new();
+        let store = KeyStore::new(dir.join("node.key"));
+        let old = store.load_or_generate().unwrap();
+        let new = Keypair::generate_ed25519();
+        let statement = store.rotate(&new).unwrap();
+
+        assert_eq!(statement.previous(), &old.public());
+        assert_eq!(statement.current(), &new.public());
+        assert!(statement.verify());
+        assert_eq!(store.load().unwrap().to_peer_id(), new.to_peer_id());
+        assert_eq!(
+            store.previous().unwrap().unwrap().to_peer_id(),
+            old.to_peer_id()
+        );
+
+        let decoded = ContinuityStatement::from_bytes(&statement.to_bytes()).unwrap();
+        assert!(decoded.verify());
+        assert_eq!(decoded.previous(), statement.previous());
+        assert_eq!(decoded.current(), statement.current());
+        assert_eq!(decoded.valid_until(), statement.valid_until());
+
+        let mut tampered = statement.to_bytes();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(!ContinuityStatement::from_bytes(&tampered).unwrap().verify());
+        assert!(matches!(
+            ContinuityStatement::from_bytes(&tampered[..tampered.len() - 1]),
+            Err(KeyStoreError::Malformed)
+        ));
+
+        let reissued = store.continuity_statement().unwrap().unwrap();
+        assert!(reissued.verify());
+        assert_eq!(reissued.current(), &new.public());
+    }
+
+    #[test]
+    fn previous_key_expires_after_grace_period() {
+        let dir = TempDir::new();
+        let store = KeyStore::new(dir.join("node.key")).grace_period(Duration::ZERO);
+        store.load_or_generate().unwrap();
+        let statement = store.rotate(&Keypair::generate_ed25519()).unwrap();
+
+        assert!(!statement.verify());
+        assert!(store.previous().unwrap().is_none());
+        assert!(store.continuity_statement().unwrap().is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{cmp, fmt, str::FromStr};
+
+use sha2::Digest;
+
+use super::{DecodingError, KeyType, ParseError, PublicKey, proto};
+
+/// multihash 的 identity 编码，摘要即原始内容
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+const SHA2_256_LENGTH: usize = 32;
+
+/// 可内联的公钥编码最大长度，更长的公钥改为存放其 SHA-256
+const MAX_INLINE_KEY_LENGTH: usize = 42;
+
+/// 两字节头部（算法编号与长度）加上最长的摘要
+const MAX_MULTIHASH_LENGTH: usize = 2 + MAX_INLINE_KEY_LENGTH;
+
+/// 节点标识，为公钥 protobuf 编码（[`PublicKey::to_protobuf_encoding`]）的 multihash。
+///
+/// 不超过 42 字节的公钥（ed25519、secp256k1）直接内联，可由 `PeerId` 还原公钥；
+/// 更长的公钥（ECDSA、RSA）存放其 SHA-256。文本形式为 multihash 的 base58 编码，
+/// 与 libp2p 相同。
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct PeerId {
+    len: u8,
+    bytes: [u8; MAX_MULTIHASH_LENGTH],
+}
+
+impl PeerId {
+    pub fn from_public_key(key: &PublicKey) -> Self {
+        let encoded = key.to_protobuf_encoding();
+        if encoded.len() <= MAX_INLINE_KEY_LENGTH {
+            Self::from_multihash(IDENTITY, &encoded)
+        } else {
+            Self::from_multihash(SHA2_256, &sha2::Sha256::digest(&encoded))
+        }
+    }
+
+    /// 两个编号都小于 0x80，头部的 varint 各占一个字节
+    fn from_multihash(code: u64, digest: &[u8]) -> Self {
+        let mut bytes = [0u8; MAX_MULTIHASH_LENGTH];
+        bytes[0] = code as u8;
+        bytes[1] = digest.len() as u8;
+        bytes[2..2 + digest.len()].copy_from_slice(digest);
+        PeerId {
+            len: (2 + digest.len()) as u8,
+            bytes,
+        }
+    }
+
+    /// 由 multihash 的二进制形式构造
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        let (code, rest) = proto::read_varint(bytes).map_err(|_| ParseError::InvalidMultihash)?;
+        let (len, digest) = proto::read_varint(rest).map_err(|_| ParseError::InvalidMultihash)?;
+        if digest.len() as u64 != len {
+            return Err(ParseError::InvalidMultihash);
+        }
+        match code {
+            IDENTITY if digest.len() <= MAX_INLINE_KEY_LENGTH => {}
+            SHA2_256 if digest.len() == SHA2_256_LENGTH => {}
+            IDENTITY | SHA2_256 => return Err(ParseError::InvalidMultihash),
+            code => return Err(ParseError::UnsupportedCode(code)),
+        }
+        Ok(Self::from_multihash(code, digest))
+    }
+
+    /// 由旧版本 identify 使用的 32 字节标识（ed25519 公钥本身）得到对应的 `PeerId`
+    pub fn from_legacy_ed25519(bytes: [u8; 32]) -> Result<Self, DecodingError> {
+        PublicKey::try_from_raw(KeyType::Ed25519, &bytes).map(|key| key.to_peer_id())
+    }
+
+    /// 旧版本 QUIC 使用的 32 字节标识，即证书中 SubjectPublicKeyInfo 的 SHA-256。
+    ///
+    /// 旧标识只保存摘要，无法还原为 `PeerId`，只能由内联了公钥的 `PeerId` 计算后与之比较；
+    /// 只保存了摘要的 `PeerId` 返回 `None`。
+    pub fn to_legacy_quic_bytes(&self) -> Option<[u8; 32]> {
+        let key = self.public_key()?;
+        Some(sha2::Sha256::digest(key.to_spki_der()).into())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.as_bytes()).into_string()
+    }
+
+    /// 内联的公钥，公钥过长而只保存了摘要时返回 `None`
+    pub fn public_key(&self) -> Option<PublicKey> {
+        match self.code() {
+            IDENTITY => PublicKey::from_protobuf_encoding(self.digest()).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    fn code(&self) -> u64 {
+        u64::from(self.bytes[0])
+    }
+
+    fn digest(&self) -> &[u8] {
+        &self.as_bytes()[2..]
+    }
+}
+
+impl Ord for PeerId {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl PartialOrd for PeerId {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Debug for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PeerId").field(&self.to_base58()).finish()
+    }
+}
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_base58().fmt(f)
+    }
+}
+
+impl FromStr for PeerId {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s).into_vec().map_err(|_| ParseError::Base58)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// 可读格式（如 JSON）使用 base58 文本，其余格式使用 multihash 字节
+#[cfg(feature = "serde")]
+impl serde::Serialize for PeerId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_base58())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PeerId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = PeerId;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a base58 encoded or binary multihash peer id")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<PeerId, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<PeerId, E> {
+                PeerId::from_bytes(v).map_err(E::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::Keypair;
+
+    const RSA_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAkyvI45BqwQw2e/NSmEDQ
+R9P5/oNSmFXZwbDgU+jllkThbElbMorpyhrT/rnk5BW34Nn6n1IWPz3AZ2gDxouc
+asNDwBbLRV4TBBaxxPAiNYdzX8y6BBBPKD233wPWLtJjoR6IXab6bVMPVx5emAo1
+R79A6K5H1XA9tFSaTbemVtuiHUhohuHqab2SsbeidNzxootJH493YbXm5NdmBsm1
+kfWYch898hCvCAwq24KpEmLa6I1GRia7RuOgOiXbwRRtDlEUs5VJm36k4pQ5WLOM
+MWHYOuOlTwiA4jgcLRbiEB8JYoOtn+qkVCvQL6B0fAL6A9CDb6TO8ZPmM6B4LXqB
+mwIDAQAB
+-----END PUBLIC KEY-----
+";
+
+    /// rust-libp2p 测试中使用的 ed25519 私钥 protobuf 编码
+    const LIBP2P_ED25519_KEYPAIR: [u8; 68] = [
+        0x08, 0x01, 0x12, 0x40, 0xbe, 0xaf, 0x74, 0xa4, 0x2e, 0xce, 0x74, 0x28, 0xb1, 0x3a, 0xd6,
+        0xec, 0x55, 0x88, 0xf4, 0x0b, 0x7e, 0x5d, 0x7e, 0xc4, 0x05, 0xfd, 0x01, 0x9d, 0x92, 0xe1,
+        0x6f, 0xac, 0x3e, 0x37, 0xe5, 0xd2, 0x41, 0x27, 0x5f, 0x22, 0x79, 0x7a, 0x73, 0xb5, 0x38,
+        0x36, 0xec, 0x57, 0x26, 0x58, 0x4a, 0x71, 0x10, 0x65, 0x07, 0x0f, 0x5d, 0xd2, 0xd8, 0xf6,
+        0x75, 0xf0, 0x48, 0x55, 0xfe, 0xa6, 0x70, 0xf0,
+    ];
+
+    fn round_trip(peer_id: PeerId) {
+        let text = peer_id.to_string();
+        assert_eq!(text.parse::<PeerId>().unwrap(), peer_id);
+        assert_eq!(PeerId::from_bytes(&peer_id.to_bytes()).unwrap(), peer_id);
+    }
+
+    #[test]
+    fn ed25519_round_trip() {
+        let key = Keypair::generate_ed25519().public();
+        let peer_id = key.to_peer_id();
+        assert!(peer_id.to_string().starts_with("12D3KooW"));
+        assert_eq!(peer_id.public_key(), Some(key));
+        round_trip(peer_id);
+    }
+
+    #[test]
+    fn secp256k1_round_trip() {
+        let key = Keypair::generate_secp256k1().public();
+        let peer_id = key.to_peer_id();
+        assert!(peer_id.to_string().starts_with("16Uiu2HA"));
+        assert_eq!(peer_id.public_key(), Some(key));
+        round_trip(peer_id);
+    }
+
+    #[test]
+    fn ecdsa_round_trip() {
+        let peer_id = Keypair::generate_ecdsa().to_peer_id();
+        assert!(peer_id.to_string().starts_with("Qm"));
+        assert_eq!(peer_id.public_key(), None);
+        assert_eq!(peer_id.to_legacy_quic_bytes(), None);
+        round_trip(peer_id);
+    }
+
+    #[test]
+    fn rsa_round_trip() {
+        let key = PublicKey::from_pem(RSA_PUBLIC_KEY).unwrap();
+        let peer_id = key.to_peer_id();
+        assert!(peer_id.to_string().starts_with("Qm"));
+        round_trip(peer_id);
+    }
+
+    #[test]
+    fn libp2p_compatible() {
+        let keypair = Keypair::from_protobuf_encoding(&LIBP2P_ED25519_KEYPAIR).unwrap();
+        assert_eq!(
+            keypair.to_peer_id().to_string(),
+            "12D3KooWEChVMMMzV8acJ53mJHrw1pQ27UAGkCxWXLJutbeUMvVu"
+        );
+    }
+
+    #[test]
+    fn legacy_ids() {
+        let key = Keypair::generate_ed25519().public();
+        let raw: [u8; 32] = key.to_raw_bytes().try_into().unwrap();
+        assert_eq!(PeerId::from_legacy_ed25519(raw).unwrap(), key.to_peer_id());
+
+        let spki_digest: [u8; 32] = sha2::Sha256::digest(key.to_spki_der()).into();
+        assert_eq!(key.to_peer_id().to_legacy_quic_bytes(), Some(spki_digest));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(matches!("0OIl".parse::<PeerId>(), Err(ParseError::Base58)));
+        let mut bytes = Keypair::generate_ecdsa().to_peer_id().to_bytes();
+        bytes.pop();
+        assert!(matches!(
+            PeerId::from_bytes(&bytes),
+            Err(ParseError::InvalidMultihash)
+        ));
+        assert!(matches!(
+            PeerId::from_bytes(&[0x13, 0x01, 0x00]),
+            Err(ParseError::UnsupportedCode(0x13))
+        ));
+        assert!(matches!(
+            PeerId::from_bytes(&[0x12, 0x01, 0x00]),
+            Err(ParseError::InvalidMultihash)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+//! 与 libp2p 兼容的 protobuf 编码，密钥的消息格式为
+//!
+//! ```protobuf
+//! message PublicKey { required KeyType Type = 1; required bytes Data = 2; }
+//! message PrivateKey { required KeyType Type = 1; required bytes Data = 2; }
+//! ```
+//!
+//! 其余消息（签名信封、节点记录、identify 消息）通过 [`write_bytes_field`] 与 [`read_field`] 逐字段编解码。
+
+use super::DecodingError;
+
+const TYPE_TAG: u8 = 0x08;
+const DATA_TAG: u8 = 0x12;
+
+pub(crate) fn encode(key_type: u64, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 8);
+    out.push(TYPE_TAG);
+    write_varint(key_type, &mut out);
+    out.push(DATA_TAG);
+    write_varint(data.len() as u64, &mut out);
+    out.extend_from_slice(data);
+    out
+}
+
+pub(crate) fn decode(mut input: &[u8]) -> Result<(u64, &[u8]), DecodingError> {
+    let (mut key_type, mut data) = (None, None);
+    while let Some((&tag, rest)) = input.split_first() {
+        match tag {
+            TYPE_TAG => {
+                let (value, rest) = read_varint(rest)?;
+                key_type = Some(value);
+                input = rest;
+            }
+            DATA_TAG => {
+                let (len, rest) = read_varint(rest)?;
+                let len = usize::try_from(len).map_err(|_| DecodingError::InvalidProtobuf)?;
+                if rest.len() < len {
+                    return Err(DecodingError::InvalidProtobuf);
+                }
+                let (value, rest) = rest.split_at(len);
+                data = Some(value);
+                input = rest;
+            }
+            _ => return Err(DecodingError::InvalidProtobuf),
+        }
+    }
+    key_type.zip(data).ok_or(DecodingError::InvalidProtobuf)
+}
+
+/// 字段的值，只支持 varint 与 length-delimited 两种线路类型
+pub enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_BYTES: u64 = 2;
+
+pub fn write_varint_field(field: u64, value: u64, out: &mut Vec<u8>) {
+    write_varint(field << 3 | WIRE_VARINT, out);
+    write_varint(value, out);
+}
+
+pub fn write_bytes_field(field: u64, data: &[u8], out: &mut Vec<u8>) {
+    write_varint(field << 3 | WIRE_BYTES, out);
+    write_varint(data.len() as u64, out);
+    out.extend_from_slice(data);
+}
+
+/// 读取一个字段，返回字段编号、值与剩余输入
+pub fn read_field(input: &[u8]) -> Result<(u64, Value<'_>, &[u8]), DecodingError> {
+    let (key, rest) = read_varint(input)?;
+    match key & 0x07 {
+        WIRE_VARINT => {
+            let (value, rest) = read_varint(rest)?;
+            Ok((key >> 3, Value::Varint(value), rest))
+        }
+        WIRE_BYTES => {
+            let (len, rest) = read_varint(rest)?;
+            let len = usize::try_from(len).map_err(|_| DecodingError::InvalidProtobuf)?;
+            let (value, rest) = rest
+                .split_at_checked(len)
+                .ok_or(DecodingError::InvalidProtobuf)?;
+            Ok((key >> 3, Value::Bytes(value), rest))
+        }
+        _ => Err(DecodingError::InvalidProtobuf),
+    }
+}
+
+pub fn read_varint(input: &[u8]) -> Result<(u64, &[u8]), DecodingError> {
+    let mut value: u64 = 0;
+    for (i, byte) in input.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, &input[i + 1..]));
+        }
+    }
+    Err(DecodingError::InvalidProtobuf)
+}
+
+pub fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -1,8 +1,11 @@
 pub mod connection;
 pub mod either;
 mod extensions;
-mod identity;
+pub mod identity;
+pub mod multiaddr;
 pub mod muxing;
+pub mod peer_record;
+pub mod signed_envelope;
 pub mod transport;
 pub mod upgrade;
 pub mod utils;
@@ -10,8 +13,11 @@ pub mod utils;
 pub use connection::{ConnectedPoint, Endpoint};
 pub use extensions::Extensions;
 pub use identity::PeerId;
+pub use multiaddr::Multiaddr;
 pub use muxing::StreamMuxer;
-pub use transport::{ListenerEvent, Transport};
+pub use peer_record::PeerRecord;
+pub use signed_envelope::SignedEnvelope;
+pub use transport::{ListenerEvent, ListenerId, Transport, TransportError};
 pub use upgrade::{Upgrade, UpgradeInfo};
 
 pub type Negotiated<T> = airio_stream_select::Negotiated<T>;
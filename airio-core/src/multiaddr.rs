@@ -0,0 +1,347 @@
+mod protocol;
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    slice,
+    str::FromStr,
+    vec,
+};
+
+pub use protocol::Protocol;
+
+/// 可组合的传输地址，由多个 [`Protocol`] 段组成，
+/// 例如 `/ip4/127.0.0.1/tcp/8080/ws`。
+///
+/// 同时支持文本形式（[`FromStr`] / [`fmt::Display`]）与二进制形式（[`Multiaddr::to_vec`] / [`Multiaddr::try_from`]）。
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Multiaddr(Vec<Protocol>);
+
+impl Multiaddr {
+    /// 创建一个空地址
+    pub fn empty() -> Self {
+        Multiaddr(Vec::new())
+    }
+
+    /// 在地址末尾追加一个协议段
+    pub fn push(&mut self, protocol: Protocol) {
+        self.0.push(protocol);
+    }
+
+    /// 移除并返回地址末尾的协议段
+    pub fn pop(&mut self) -> Option<Protocol> {
+        self.0.pop()
+    }
+
+    /// 返回追加了一个协议段的新地址
+    pub fn with(mut self, protocol: Protocol) -> Self {
+        self.push(protocol);
+        self
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Protocol> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn protocols(&self) -> &[Protocol] {
+        &self.0
+    }
+
+    pub fn starts_with(&self, other: &Multiaddr) -> bool {
+        self.0.starts_with(&other.0)
+    }
+
+    pub fn ends_with(&self, other: &Multiaddr) -> bool {
+        self.0.ends_with(&other.0)
+    }
+
+    /// 转换为二进制形式
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for protocol in &self.0 {
+            protocol.write_bytes(&mut out);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Multiaddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for protocol in &self.0 {
+            protocol.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Multiaddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Multiaddr").field(&self.to_string()).finish()
+    }
+}
+
+impl FromStr for Multiaddr {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parts = input.split('/');
+        // 地址必须以 `/` 开头
+        if parts.next() != Some("") {
+            return Err(Error::InvalidMultiaddr);
+        }
+        let mut parts = parts.peekable();
+        let mut protocols = Vec::new();
+        while parts.peek().is_some() {
+            // 允许末尾多余的 `/`
+            if parts.peek() == Some(&"") {
+                parts.next();
+                if parts.peek().is_none() {
+                    break;
+                }
+                return Err(Error::InvalidMultiaddr);
+            }
+            protocols.push(Protocol::from_str_parts(&mut parts)?);
+        }
+        Ok(Multiaddr(protocols))
+    }
+}
+
+impl TryFrom<&[u8]> for Multiaddr {
+    type Error = Error;
+
+    fn try_from(mut input: &[u8]) -> Result<Self, Self::Error> {
+        let mut protocols = Vec::new();
+        while !input.is_empty() {
+            let (protocol, rest) = Protocol::from_bytes(input)?;
+            protocols.push(protocol);
+            input = rest;
+        }
+        Ok(Multiaddr(protocols))
+    }
+}
+
+impl TryFrom<Vec<u8>> for Multiaddr {
+    type Error = Error;
+
+    fn try_from(input: Vec<u8>) -> Result<Self, Self::Error> {
+        Multiaddr::try_from(input.as_slice())
+    }
+}
+
+impl TryFrom<&str> for Multiaddr {
+    type Error = Error;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+impl From<Protocol> for Multiaddr {
+    fn from(protocol: Protocol) -> Self {
+        Multiaddr(vec![protocol])
+    }
+}
+
+impl From<IpAddr> for Multiaddr {
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => Protocol::Ip4(addr).into(),
+            IpAddr::V6(addr) => Protocol::Ip6(addr).into(),
+        }
+    }
+}
+
+/// 将 [`SocketAddr`] 转换为 `/ip4/../tcp/..` 形式的地址
+impl From<SocketAddr> for Multiaddr {
+    fn from(addr: SocketAddr) -> Self {
+        Multiaddr::from(addr.ip()).with(Protocol::Tcp(addr.port()))
+    }
+}
+
+impl FromIterator<Protocol> for Multiaddr {
+    fn from_iter<I: IntoIterator<Item = Protocol>>(iter: I) -> Self {
+        Multiaddr(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Protocol> for Multiaddr {
+    fn extend<I: IntoIterator<Item = Protocol>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl IntoIterator for Multiaddr {
+    type Item = Protocol;
+    type IntoIter = vec::IntoIter<Protocol>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Multiaddr {
+    type Item = &'a Protocol;
+    type IntoIter = slice::Iter<'a, Protocol>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid multiaddr")]
+    InvalidMultiaddr,
+    #[error("Unknown protocol: {0}")]
+    UnknownProtocol(String),
+    #[error("Unknown protocol code: {0}")]
+    UnknownCode(u32),
+    #[error("Missing value for protocol: {0}")]
+    MissingValue(String),
+    #[error("Invalid value for protocol: {0}")]
+    InvalidValue(String),
+    #[error("Invalid UTF-8 in protocol value")]
+    InvalidUtf8,
+    #[error("Invalid varint")]
+    InvalidVarint,
+    #[error("Data is shorter than expected")]
+    DataTooShort,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::identity::Keypair;
+
+    fn round_trip(text: &str) -> Multiaddr {
+        let addr: Multiaddr = text.parse().unwrap();
+        assert_eq!(addr.to_string(), text);
+        assert_eq!(Multiaddr::try_from(addr.to_vec()).unwrap(), addr);
+        addr
+    }
+
+    #[test]
+    fn text_and_binary_round_trip() {
+        let addr = round_trip("/ip4/127.0.0.1/tcp/8080/ws");
+        assert_eq!(
+            addr.protocols(),
+            &[
+                Protocol::Ip4(Ipv4Addr::LOCALHOST),
+                Protocol::Tcp(8080),
+                Protocol::Ws
+            ]
+        );
+        let addr = round_trip("/ip6/::1/udp/443/quic-v1");
+        assert_eq!(addr.protocols()[0], Protocol::Ip6(Ipv6Addr::LOCALHOST));
+        round_trip("/dns4/example.com/tcp/443/wss");
+        round_trip("/dns/example.com/tcp/443/sni/example.com/wss");
+        round_trip("/dns6/example.com/tcp/80/ws");
+        round_trip("/ip4/10.0.0.1/tcp/80/ws/http-path/chat%2Fv1");
+        round_trip("/unix/%2Ftmp%2Fairio.sock");
+        round_trip("/memory/18446744073709551615");
+    }
+
+    #[test]
+    fn p2p_round_trip() {
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let addr = round_trip(&format!("/ip4/1.2.3.4/tcp/1/p2p/{peer_id}"));
+        assert_eq!(addr.iter().last(), Some(&Protocol::P2p(peer_id)));
+    }
+
+    #[test]
+    fn percent_encoded_values() {
+        let addr: Multiaddr = "/unix/%2Ftmp%2Fa%20b.sock".parse().unwrap();
+        assert_eq!(
+            addr.protocols(),
+            &[Protocol::Unix("/tmp/a b.sock".to_owned())]
+        );
+        assert!(matches!(
+            "/unix/%zz".parse::<Multiaddr>(),
+            Err(Error::InvalidValue(tag)) if tag == "unix"
+        ));
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/80/".parse().unwrap();
+        assert_eq!(addr.to_string(), "/ip4/127.0.0.1/tcp/80");
+        assert!(matches!(
+            "/ip4/127.0.0.1//tcp/80".parse::<Multiaddr>(),
+            Err(Error::InvalidMultiaddr)
+        ));
+        assert_eq!("/".parse::<Multiaddr>().unwrap(), Multiaddr::empty());
+    }
+
+    #[test]
+    fn rejects_invalid_text() {
+        assert!(matches!(
+            "ip4/127.0.0.1".parse::<Multiaddr>(),
+            Err(Error::InvalidMultiaddr)
+        ));
+        assert!(matches!(
+            "/foo/1".parse::<Multiaddr>(),
+            Err(Error::UnknownProtocol(tag)) if tag == "foo"
+        ));
+        assert!(matches!(
+            "/ip4".parse::<Multiaddr>(),
+            Err(Error::MissingValue(tag)) if tag == "ip4"
+        ));
+        assert!(matches!(
+            "/ip4/127.0.0.1/tcp/".parse::<Multiaddr>(),
+            Err(Error::MissingValue(tag)) if tag == "tcp"
+        ));
+        assert!(matches!(
+            "/ip4/256.0.0.1".parse::<Multiaddr>(),
+            Err(Error::InvalidValue(tag)) if tag == "ip4"
+        ));
+        assert!(matches!(
+            "/ip4/127.0.0.1/tcp/65536".parse::<Multiaddr>(),
+            Err(Error::InvalidValue(tag)) if tag == "tcp"
+        ));
+        assert!(matches!(
+            "/p2p/not-a-peer-id".parse::<Multiaddr>(),
+            Err(Error::InvalidValue(tag)) if tag == "p2p"
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_binary() {
+        let bytes = "/ip4/127.0.0.1/tcp/8080"
+            .parse::<Multiaddr>()
+            .unwrap()
+            .to_vec();
+        for len in [1, 3, bytes.len() - 1] {
+            assert!(matches!(
+                Multiaddr::try_from(&bytes[..len]),
+                Err(Error::DataTooShort)
+            ));
+        }
+        // 长度前缀超出剩余数据
+        assert!(matches!(
+            Multiaddr::try_from(&[0x36, 0x05, b'a'][..]),
+            Err(Error::DataTooShort)
+        ));
+        assert!(matches!(
+            Multiaddr::try_from(&[0x36, 0x02, 0xff, 0xfe][..]),
+            Err(Error::InvalidUtf8)
+        ));
+        assert!(matches!(
+            Multiaddr::try_from(&[0x7f][..]),
+            Err(Error::UnknownCode(0x7f))
+        ));
+        assert!(matches!(
+            Multiaddr::try_from(&[0x80, 0x80, 0x80, 0x80, 0x80][..]),
+            Err(Error::InvalidVarint)
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use super::Error;
+use crate::PeerId;
+
+const IP4: u32 = 0x04;
+const TCP: u32 = 0x06;
+const DNS: u32 = 0x35;
+const DNS4: u32 = 0x36;
+const DNS6: u32 = 0x37;
+const IP6: u32 = 0x29;
+const UDP: u32 = 0x0111;
+const UNIX: u32 = 0x0190;
+const P2P: u32 = 0x01a5;
+const SNI: u32 = 0x01c1;
+const QUIC_V1: u32 = 0x01cd;
+const WS: u32 = 0x01dd;
+const WSS: u32 = 0x01de;
+const HTTP_PATH: u32 = 0x01e1;
+const MEMORY: u32 = 0x0309;
+
+/// 地址中的单个协议段，例如 `/ip4/127.0.0.1` 或 `/tcp/80`。
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Protocol {
+    Ip4(Ipv4Addr),
+    Ip6(Ipv6Addr),
+    Dns(String),
+    Dns4(String),
+    Dns6(String),
+    Tcp(u16),
+    Udp(u16),
+    /// TLS/QUIC 的 SNI 以及 WebSocket 的 `Host`，通常由 DNS 解析时保留原始主机名
+    Sni(String),
+    QuicV1,
+    Ws,
+    Wss,
+    /// HTTP 请求路径，未进行百分号编码
+    HttpPath(String),
+    /// Unix 域套接字路径，以 `@` 开头表示抽象命名空间
+    Unix(String),
+    /// 进程内的内存端口，参见 [`MemoryTransport`](crate::transport::memory::MemoryTransport)
+    Memory(u64),
+    /// 地址所属节点的 [`PeerId`]，通常位于地址末尾
+    P2p(PeerId),
+}
+
+impl Protocol {
+    /// 协议的文本名称
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Protocol::Ip4(_) => "ip4",
+            Protocol::Ip6(_) => "ip6",
+            Protocol::Dns(_) => "dns",
+            Protocol::Dns4(_) => "dns4",
+            Protocol::Dns6(_) => "dns6",
+            Protocol::Tcp(_) => "tcp",
+            Protocol::Udp(_) => "udp",
+            Protocol::Sni(_) => "sni",
+            Protocol::QuicV1 => "quic-v1",
+            Protocol::Ws => "ws",
+            Protocol::Wss => "wss",
+            Protocol::HttpPath(_) => "http-path",
+            Protocol::Unix(_) => "unix",
+            Protocol::Memory(_) => "memory",
+            Protocol::P2p(_) => "p2p",
+        }
+    }
+
+    fn code(&self) -> u32 {
+        match self {
+            Protocol::Ip4(_) => IP4,
+            Protocol::Ip6(_) => IP6,
+            Protocol::Dns(_) => DNS,
+            Protocol::Dns4(_) => DNS4,
+            Protocol::Dns6(_) => DNS6,
+            Protocol::Tcp(_) => TCP,
+            Protocol::Udp(_) => UDP,
+            Protocol::Sni(_) => SNI,
+            Protocol::QuicV1 => QUIC_V1,
+            Protocol::Ws => WS,
+            Protocol::Wss => WSS,
+            Protocol::HttpPath(_) => HTTP_PATH,
+            Protocol::Unix(_) => UNIX,
+            Protocol::Memory(_) => MEMORY,
+            Protocol::P2p(_) => P2P,
+        }
+    }
+
+    /// 从 `/` 分隔的文本片段中解析一个协议段。
+    pub(crate) fn from_str_parts<'a, I>(mut parts: I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let tag = parts.next().ok_or(Error::InvalidMultiaddr)?;
+        let mut value = |tag: &str| {
+            parts
+                .next()
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| Error::MissingValue(tag.to_owned()))
+        };
+        let invalid = |tag: &str| Error::InvalidValue(tag.to_owned());
+        let protocol = match tag {
+            "ip4" => Protocol::Ip4(Ipv4Addr::from_str(value(tag)?).map_err(|_| invalid(tag))?),
+            "ip6" => Protocol::Ip6(Ipv6Addr::from_str(value(tag)?).map_err(|_| invalid(tag))?),
+            "dns" => Protocol::Dns(value(tag)?.to_owned()),
+            "dns4" => Protocol::Dns4(value(tag)?.to_owned()),
+            "dns6" => Protocol::Dns6(value(tag)?.to_owned()),
+            "tcp" => Protocol::Tcp(value(tag)?.parse().map_err(|_| invalid(tag))?),
+            "udp" => Protocol::Udp(value(tag)?.parse().map_err(|_| invalid(tag))?),
+            "sni" => Protocol::Sni(value(tag)?.to_owned()),
+            "quic-v1" => Protocol::QuicV1,
+            "ws" => Protocol::Ws,
+            "wss" => Protocol::Wss,
+            "http-path" => {
+                Protocol::HttpPath(percent_decode(value(tag)?).ok_or_else(|| invalid(tag))?)
+            }
+            "unix" => Protocol::Unix(percent_decode(value(tag)?).ok_or_else(|| invalid(tag))?),
+            "memory" => Protocol::Memory(value(tag)?.parse().map_err(|_| invalid(tag))?),
+            "p2p" => Protocol::P2p(value(tag)?.parse().map_err(|_| invalid(tag))?),
+            unknown => return Err(Error::UnknownProtocol(unknown.to_owned())),
+        };
+        Ok(protocol)
+    }
+
+    /// 从二进制形式中读取一个协议段，返回协议以及剩余的字节。
+    pub(crate) fn from_bytes(input: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (code, input) = read_varint(input)?;
+        let (protocol, rest) = match code {
+            IP4 => {
+                let (data, rest) = split_at(input, 4)?;
+                let octets: [u8; 4] = data.try_into().expect("length checked");
+                (Protocol::Ip4(Ipv4Addr::from(octets)), rest)
+            }
+            IP6 => {
+                let (data, rest) = split_at(input, 16)?;
+                let octets: [u8; 16] = data.try_into().expect("length checked");
+                (Protocol::Ip6(Ipv6Addr::from(octets)), rest)
+            }
+            TCP | UDP => {
+                let (data, rest) = split_at(input, 2)?;
+                let port = u16::from_be_bytes([data[0], data[1]]);
+                let protocol = if code == TCP {
+                    Protocol::Tcp(port)
+                } else {
+                    Protocol::Udp(port)
+                };
+                (protocol, rest)
+            }
+            DNS | DNS4 | DNS6 | SNI | HTTP_PATH | UNIX => {
+                let (data, rest) = read_length_prefixed(input)?;
+                let value = String::from_utf8(data.to_vec()).map_err(|_| Error::InvalidUtf8)?;
+                let protocol = match code {
+                    DNS => Protocol::Dns(value),
+                    DNS4 => Protocol::Dns4(value),
+                    DNS6 => Protocol::Dns6(value),
+                    SNI => Protocol::Sni(value),
+                    HTTP_PATH => Protocol::HttpPath(value),
+                    _ => Protocol::Unix(value),
+                };
+                (protocol, rest)
+            }
+            MEMORY => {
+                let (data, rest) = split_at(input, 8)?;
+                let port = u64::from_be_bytes(data.try_into().expect("length checked"));
+                (Protocol::Memory(port), rest)
+            }
+            P2P => {
+                let (data, rest) = read_length_prefixed(input)?;
+                let peer_id =
+                    PeerId::from_bytes(data).map_err(|_| Error::InvalidValue("p2p".to_owned()))?;
+                (Protocol::P2p(peer_id), rest)
+            }
+            QUIC_V1 => (Protocol::QuicV1, input),
+            WS => (Protocol::Ws, input),
+            WSS => (Protocol::Wss, input),
+            unknown => return Err(Error::UnknownCode(unknown)),
+        };
+        Ok((protocol, rest))
+    }
+
+    /// 将协议段以二进制形式写入 `out`。
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_varint(self.code(), out);
+        match self {
+            Protocol::Ip4(addr) => out.extend_from_slice(&addr.octets()),
+            Protocol::Ip6(addr) => out.extend_from_slice(&addr.octets()),
+            Protocol::Tcp(port) | Protocol::Udp(port) => out.extend_from_slice(&port.to_be_bytes()),
+            Protocol::Memory(port) => out.extend_from_slice(&port.to_be_bytes()),
+            Protocol::Dns(value)
+            | Protocol::Dns4(value)
+            | Protocol::Dns6(value)
+            | Protocol::Sni(value)
+            | Protocol::HttpPath(value)
+            | Protocol::Unix(value) => {
+                write_varint(value.len() as u32, out);
+                out.extend_from_slice(value.as_bytes());
+            }
+            Protocol::P2p(peer_id) => {
+                let bytes = peer_id.to_bytes();
+                write_varint(bytes.len() as u32, out);
+                out.extend_from_slice(&bytes);
+            }
+            Protocol::QuicV1 | Protocol::Ws | Protocol::Wss => {}
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.tag())?;
+        match self {
+            Protocol::Ip4(addr) => write!(f, "/{addr}"),
+            Protocol::Ip6(addr) => write!(f, "/{addr}"),
+            Protocol::Dns(host)
+            | Protocol::Dns4(host)
+            | Protocol::Dns6(host)
+            | Protocol::Sni(host) => {
+                write!(f, "/{host}")
+            }
+            Protocol::Tcp(port) | Protocol::Udp(port) => write!(f, "/{port}"),
+            Protocol::Memory(port) => write!(f, "/{port}"),
+            Protocol::P2p(peer_id) => write!(f, "/{peer_id}"),
+            Protocol::HttpPath(path) | Protocol::Unix(path) => {
+                write!(f, "/{}", percent_encode(path))
+            }
+            Protocol::QuicV1 | Protocol::Ws | Protocol::Wss => Ok(()),
+        }
+    }
+}
+
+fn split_at(input: &[u8], len: usize) -> Result<(&[u8], &[u8]), Error> {
+    if input.len() < len {
+        return Err(Error::DataTooShort);
+    }
+    Ok(input.split_at(len))
+}
+
+fn read_length_prefixed(input: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let (len, input) = read_varint(input)?;
+    split_at(input, len as usize)
+}
+
+fn read_varint(input: &[u8]) -> Result<(u32, &[u8]), Error> {
+    let mut value: u32 = 0;
+    for (i, byte) in input.iter().enumerate().take(5) {
+        value |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, &input[i + 1..]));
+        }
+    }
+    Err(Error::InvalidVarint)
+}
+
+fn write_varint(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'/' | b'%' => encoded.push_str(&format!("%{byte:02X}")),
+            byte if byte.is_ascii_graphic() => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
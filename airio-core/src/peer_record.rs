@@ -0,0 +1,254 @@
+//! 节点的地址记录，装在 [`SignedEnvelope`] 中传递，格式与 libp2p 的 `PeerRecord`（RFC 0003）相同：
+//!
+//! ```protobuf
+//! message PeerRecord {
+//!   message AddressInfo { bytes multiaddr = 1; }
+//!   bytes peer_id = 1;
+//!   uint64 seq = 2;
+//!   repeated AddressInfo addresses = 3;
+//! }
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    Multiaddr, PeerId,
+    identity::{
+        DecodingError, Keypair, ParseError,
+        proto::{self, Value},
+    },
+    multiaddr,
+    signed_envelope::{EnvelopeError, SignedEnvelope},
+};
+
+/// 签名时使用的域名
+pub const DOMAIN: &str = "libp2p-routing-state";
+/// multicodec `libp2p-peer-record`
+pub const PAYLOAD_TYPE: &[u8] = &[0x03, 0x01];
+
+const PEER_ID_FIELD: u64 = 1;
+const SEQ_FIELD: u64 = 2;
+const ADDRESSES_FIELD: u64 = 3;
+const MULTIADDR_FIELD: u64 = 1;
+
+/// 由节点自身签名的地址列表。
+///
+/// 记录只能由 [`PeerRecord::new`] 签发或由 [`PeerRecord::from_signed_envelope`] 验证后得到，
+/// 因此持有的 `PeerRecord` 总是由 `peer_id` 对应的密钥签名，可以原样转发给其他节点。
+/// 同一节点的记录以 `seq` 区分新旧。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerRecord {
+    peer_id: PeerId,
+    seq: u64,
+    addresses: Vec<Multiaddr>,
+    envelope: SignedEnvelope,
+}
+
+impl PeerRecord {
+    /// 签发记录，`seq` 取当前的 Unix 时间（纳秒），保证同一节点后签发的记录更新
+    pub fn new(key: &Keypair, addresses: Vec<Multiaddr>) -> Self {
+        let seq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        Self::with_seq(key, seq, addresses)
+    }
+
+    pub fn with_seq(key: &Keypair, seq: u64, addresses: Vec<Multiaddr>) -> Self {
+        let peer_id = key.to_peer_id();
+        let payload = encode(&peer_id, seq, &addresses);
+        let envelope = SignedEnvelope::new(key, DOMAIN, PAYLOAD_TYPE.to_vec(), payload);
+        Self {
+            peer_id,
+            seq,
+            addresses,
+            envelope,
+        }
+    }
+
+    /// 验证信封的签名与载荷类型，并确认记录中的 `PeerId` 属于签名者
+    pub fn from_signed_envelope(envelope: SignedEnvelope) -> Result<Self, PeerRecordError> {
+        let (payload, key) = envelope.payload_and_signing_key(DOMAIN, PAYLOAD_TYPE)?;
+        let (peer_id, seq, addresses) = decode(payload)?;
+        if peer_id != key.to_peer_id() {
+            return Err(PeerRecordError::MismatchedSignature);
+        }
+        Ok(Self {
+            peer_id,
+            seq,
+            addresses,
+            envelope,
+        })
+    }
+
+    /// 解码信封的 protobuf 编码并验证
+    pub fn from_protobuf_encoding(bytes: &[u8]) -> Result<Self, PeerRecordError> {
+        Self::from_signed_envelope(SignedEnvelope::from_protobuf_encoding(bytes)?)
+    }
+
+    pub fn to_protobuf_encoding(&self) -> Vec<u8> {
+        self.envelope.to_protobuf_encoding()
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn addresses(&self) -> &[Multiaddr] {
+        &self.addresses
+    }
+
+    pub fn signed_envelope(&self) -> &SignedEnvelope {
+        &self.envelope
+    }
+
+    pub fn into_signed_envelope(self) -> SignedEnvelope {
+        self.envelope
+    }
+
+    /// 是否应当以本记录替换 `other`：同一节点且序号更大
+    pub fn supersedes(&self, other: &PeerRecord) -> bool {
+        self.peer_id == other.peer_id && self.seq > other.seq
+    }
+}
+
+fn encode(peer_id: &PeerId, seq: u64, addresses: &[Multiaddr]) -> Vec<u8> {
+    let mut out = Vec::new();
+    proto::write_bytes_field(PEER_ID_FIELD, &peer_id.to_bytes(), &mut out);
+    proto::write_varint_field(SEQ_FIELD, seq, &mut out);
+    for address in addresses {
+        let mut info = Vec::new();
+        proto::write_bytes_field(MULTIADDR_FIELD, &address.to_vec(), &mut info);
+        proto::write_bytes_field(ADDRESSES_FIELD, &info, &mut out);
+    }
+    out
+}
+
+fn decode(mut input: &[u8]) -> Result<(PeerId, u64, Vec<Multiaddr>), PeerRecordError> {
+    let (mut peer_id, mut seq, mut addresses) = (None, 0, Vec::new());
+    while !input.is_empty() {
+        let (field, value, rest) = proto::read_field(input)?;
+        input = rest;
+        match (field, value) {
+            (PEER_ID_FIELD, Value::Bytes(bytes)) => peer_id = Some(PeerId::from_bytes(bytes)?),
+            (SEQ_FIELD, Value::Varint(value)) => seq = value,
+            (ADDRESSES_FIELD, Value::Bytes(mut info)) => {
+                while !info.is_empty() {
+                    let (field, value, rest) = proto::read_field(info)?;
+                    info = rest;
+                    if let (MULTIADDR_FIELD, Value::Bytes(bytes)) = (field, value) {
+                        addresses.push(Multiaddr::try_from(bytes)?);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let peer_id = peer_id.ok_or(PeerRecordError::MissingPeerId)?;
+    Ok((peer_id, seq, addresses))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeerRecordError {
+    #[error("Invalid envelope: {0}")]
+    Envelope(#[from] EnvelopeError),
+    #[error("Invalid peer record encoding: {0}")]
+    Decoding(#[from] DecodingError),
+    #[error("Missing peer id in peer record")]
+    MissingPeerId,
+    #[error("Invalid peer id in peer record: {0}")]
+    PeerId(#[from] ParseError),
+    #[error("Invalid address in peer record: {0}")]
+    Multiaddr(#[from] multiaddr::Error),
+    #[error("Peer record is not signed by its peer")]
+    MismatchedSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses() -> Vec<Multiaddr> {
+        vec![
+            "/ip4/127.0.0.1/tcp/4001".parse().unwrap(),
+            "/ip6/::1/udp/4001/quic-v1".parse().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = Keypair::generate_ed25519();
+        let record = PeerRecord::with_seq(&key, 7, addresses());
+        let decoded = PeerRecord::from_protobuf_encoding(&record.to_protobuf_encoding()).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.peer_id(), key.to_peer_id());
+        assert_eq!(decoded.seq(), 7);
+        assert_eq!(decoded.addresses(), addresses().as_slice());
+        assert_eq!(decoded.signed_envelope().signing_key(), &key.public());
+    }
+
+    #[test]
+    fn rejects_record_signed_by_other_key() {
+        let key = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let payload = encode(&key.to_peer_id(), 1, &addresses());
+        let envelope = SignedEnvelope::new(&other, DOMAIN, PAYLOAD_TYPE.to_vec(), payload);
+        assert!(matches!(
+            PeerRecord::from_signed_envelope(envelope),
+            Err(PeerRecordError::MismatchedSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_domain_and_payload_type() {
+        let key = Keypair::generate_ed25519();
+        let payload = encode(&key.to_peer_id(), 1, &addresses());
+
+        let envelope =
+            SignedEnvelope::new(&key, "other-domain", PAYLOAD_TYPE.to_vec(), payload.clone());
+        assert!(matches!(
+            PeerRecord::from_signed_envelope(envelope),
+            Err(PeerRecordError::Envelope(EnvelopeError::InvalidSignature))
+        ));
+
+        let envelope = SignedEnvelope::new(&key, DOMAIN, vec![0x01, 0x02], payload);
+        assert!(matches!(
+            PeerRecord::from_signed_envelope(envelope),
+            Err(PeerRecordError::Envelope(
+                EnvelopeError::UnexpectedPayloadType { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_peer_id() {
+        let key = Keypair::generate_ed25519();
+        let mut payload = Vec::new();
+        proto::write_varint_field(SEQ_FIELD, 1, &mut payload);
+        let envelope = SignedEnvelope::new(&key, DOMAIN, PAYLOAD_TYPE.to_vec(), payload);
+        assert!(matches!(
+            PeerRecord::from_signed_envelope(envelope),
+            Err(PeerRecordError::MissingPeerId)
+        ));
+    }
+
+    #[test]
+    fn supersedes_by_seq() {
+        let key = Keypair::generate_ed25519();
+        let older = PeerRecord::with_seq(&key, 1, addresses());
+        let newer = PeerRecord::with_seq(&key, 2, Vec::new());
+        assert!(newer.supersedes(&older));
+        assert!(!older.supersedes(&newer));
+        assert!(!newer.supersedes(&newer));
+
+        let other = PeerRecord::with_seq(&Keypair::generate_ed25519(), 3, addresses());
+        assert!(!other.supersedes(&older));
+
+        let first = PeerRecord::new(&key, addresses());
+        let second = PeerRecord::new(&key, addresses());
+        assert!(second.seq() >= first.seq());
+    }
+}
@@ -0,0 +1,220 @@
+//! 带签名的信封，用于传递可由第三方转发、接收方独立验证的数据。
+//!
+//! 编码与签名方式与 libp2p 的 `Envelope`（RFC 0002）相同：
+//!
+//! ```protobuf
+//! message Envelope {
+//!   PublicKey public_key = 1;
+//!   bytes payload_type = 2;
+//!   bytes payload = 3;
+//!   bytes signature = 5;
+//! }
+//! ```
+
+use crate::identity::{
+    DecodingError, Keypair, PublicKey,
+    proto::{self, Value},
+};
+
+const PUBLIC_KEY_FIELD: u64 = 1;
+const PAYLOAD_TYPE_FIELD: u64 = 2;
+const PAYLOAD_FIELD: u64 = 3;
+const SIGNATURE_FIELD: u64 = 5;
+
+/// 由签名者的身份密钥签名的载荷。
+///
+/// 签名覆盖域名、载荷类型与载荷，域名不随信封传输，由双方约定，
+/// 避免同一签名在不同用途间被挪用。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedEnvelope {
+    key: PublicKey,
+    payload_type: Vec<u8>,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedEnvelope {
+    pub fn new(key: &Keypair, domain: &str, payload_type: Vec<u8>, payload: Vec<u8>) -> Self {
+        let signature = key.sign(&signature_payload(domain, &payload_type, &payload));
+        Self {
+            key: key.public(),
+            payload_type,
+            payload,
+            signature,
+        }
+    }
+
+    /// 以 `domain` 验证签名
+    pub fn verify(&self, domain: &str) -> bool {
+        self.key.verify(
+            &signature_payload(domain, &self.payload_type, &self.payload),
+            &self.signature,
+        )
+    }
+
+    /// 验证签名与载荷类型后返回载荷及签名者公钥
+    pub fn payload_and_signing_key(
+        &self,
+        domain: &str,
+        expected_payload_type: &[u8],
+    ) -> Result<(&[u8], &PublicKey), EnvelopeError> {
+        if self.payload_type != expected_payload_type {
+            return Err(EnvelopeError::UnexpectedPayloadType {
+                expected: expected_payload_type.to_vec(),
+                actual: self.payload_type.clone(),
+            });
+        }
+        if !self.verify(domain) {
+            return Err(EnvelopeError::InvalidSignature);
+        }
+        Ok((&self.payload, &self.key))
+    }
+
+    pub fn signing_key(&self) -> &PublicKey {
+        &self.key
+    }
+
+    pub fn payload_type(&self) -> &[u8] {
+        &self.payload_type
+    }
+
+    pub fn to_protobuf_encoding(&self) -> Vec<u8> {
+        let key = self.key.to_protobuf_encoding();
+        let mut out = Vec::with_capacity(
+            key.len() + self.payload_type.len() + self.payload.len() + self.signature.len() + 16,
+        );
+        proto::write_bytes_field(PUBLIC_KEY_FIELD, &key, &mut out);
+        proto::write_bytes_field(PAYLOAD_TYPE_FIELD, &self.payload_type, &mut out);
+        proto::write_bytes_field(PAYLOAD_FIELD, &self.payload, &mut out);
+        proto::write_bytes_field(SIGNATURE_FIELD, &self.signature, &mut out);
+        out
+    }
+
+    /// 解码信封，不验证签名
+    pub fn from_protobuf_encoding(mut input: &[u8]) -> Result<Self, EnvelopeError> {
+        let (mut key, mut payload_type, mut payload, mut signature) = (None, None, None, None);
+        while !input.is_empty() {
+            let (field, value, rest) = proto::read_field(input)?;
+            input = rest;
+            let Value::Bytes(bytes) = value else {
+                continue;
+            };
+            match field {
+                PUBLIC_KEY_FIELD => key = Some(PublicKey::from_protobuf_encoding(bytes)?),
+                PAYLOAD_TYPE_FIELD => payload_type = Some(bytes.to_vec()),
+                PAYLOAD_FIELD => payload = Some(bytes.to_vec()),
+                SIGNATURE_FIELD => signature = Some(bytes.to_vec()),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            key: key.ok_or(EnvelopeError::MissingField("public_key"))?,
+            payload_type: payload_type.unwrap_or_default(),
+            payload: payload.unwrap_or_default(),
+            signature: signature.ok_or(EnvelopeError::MissingField("signature"))?,
+        })
+    }
+}
+
+/// 被签名的内容：域名、载荷类型与载荷，各自带 varint 长度前缀
+fn signature_payload(domain: &str, payload_type: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(domain.len() + payload_type.len() + payload.len() + 12);
+    for part in [domain.as_bytes(), payload_type, payload] {
+        proto::write_varint(part.len() as u64, &mut out);
+        out.extend_from_slice(part);
+    }
+    out
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("Invalid envelope encoding: {0}")]
+    Decoding(#[from] DecodingError),
+    #[error("Missing envelope field: {0}")]
+    MissingField(&'static str),
+    #[error("Unexpected payload type: expected {expected:?}, got {actual:?}")]
+    UnexpectedPayloadType { expected: Vec<u8>, actual: Vec<u8> },
+    #[error("Envelope signature verification failed")]
+    InvalidSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOMAIN: &str = "airio-test";
+    const PAYLOAD_TYPE: &[u8] = &[0x01, 0x02];
+
+    fn envelope(key: &Keypair) -> SignedEnvelope {
+        SignedEnvelope::new(key, DOMAIN, PAYLOAD_TYPE.to_vec(), b"payload".to_vec())
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = Keypair::generate_ed25519();
+        let envelope = envelope(&key);
+        let decoded =
+            SignedEnvelope::from_protobuf_encoding(&envelope.to_protobuf_encoding()).unwrap();
+        assert_eq!(decoded, envelope);
+        let (payload, signer) = decoded
+            .payload_and_signing_key(DOMAIN, PAYLOAD_TYPE)
+            .unwrap();
+        assert_eq!(payload, b"payload");
+        assert_eq!(signer, &key.public());
+    }
+
+    #[test]
+    fn rejects_tampered_payload() {
+        let mut envelope = envelope(&Keypair::generate_ed25519());
+        envelope.payload[0] ^= 1;
+        let decoded =
+            SignedEnvelope::from_protobuf_encoding(&envelope.to_protobuf_encoding()).unwrap();
+        assert!(matches!(
+            decoded.payload_and_signing_key(DOMAIN, PAYLOAD_TYPE),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_domain() {
+        let envelope = envelope(&Keypair::generate_ed25519());
+        assert!(!envelope.verify("other-domain"));
+        assert!(matches!(
+            envelope.payload_and_signing_key("other-domain", PAYLOAD_TYPE),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_payload_type() {
+        let envelope = envelope(&Keypair::generate_ed25519());
+        assert!(matches!(
+            envelope.payload_and_signing_key(DOMAIN, &[0x03, 0x01]),
+            Err(EnvelopeError::UnexpectedPayloadType { .. })
+        ));
+
+        // 载荷类型同样受签名保护
+        let mut envelope = envelope;
+        envelope.payload_type = vec![0x03, 0x01];
+        assert!(matches!(
+            envelope.payload_and_signing_key(DOMAIN, &[0x03, 0x01]),
+            Err(EnvelopeError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_signature() {
+        let envelope = envelope(&Keypair::generate_ed25519());
+        let mut encoded = Vec::new();
+        proto::write_bytes_field(
+            PUBLIC_KEY_FIELD,
+            &envelope.key.to_protobuf_encoding(),
+            &mut encoded,
+        );
+        proto::write_bytes_field(PAYLOAD_FIELD, &envelope.payload, &mut encoded);
+        assert!(matches!(
+            SignedEnvelope::from_protobuf_encoding(&encoded),
+            Err(EnvelopeError::MissingField("signature"))
+        ));
+    }
+}
@@ -1,28 +1,39 @@
 pub mod and_then;
+pub mod bandwidth;
+pub mod gate;
+pub mod happy_eyeballs;
 pub mod map;
 pub mod map_err;
+pub mod memory;
+pub mod or_transport;
+pub mod throttle;
+pub mod timeout;
 pub mod upgrade;
 
 mod boxed;
+mod listener;
 
-use std::{error, fmt, net::SocketAddr};
+use std::{error, fmt, time::Duration};
 
 use futures::{Stream, TryFuture, TryFutureExt, future};
 
-use crate::ConnectedPoint;
+use crate::{ConnectedPoint, Multiaddr};
 
-pub use boxed::Boxed;
+pub use boxed::{Boxed, BoxedListener};
+pub use listener::{Listener, ListenerHandle, ListenerId, UpgradeGuard};
 
 pub trait Transport {
     type Output;
     type Error: error::Error;
     type Dialer: Future<Output = Result<Self::Output, Self::Error>>;
     type ListenerUpgrade: Future<Output = Result<Self::Output, Self::Error>>;
-    type Listener: Stream<Item = ListenerEvent<Self::ListenerUpgrade, Self::Error>>;
+    type Listener: Stream<Item = ListenerEvent<Self::ListenerUpgrade, Self::Error>> + Listener;
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error>;
+    /// 在指定地址上监听，不支持的地址返回 [`TransportError::Unsupported`]
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error>;
+    /// 连接指定地址，不支持的地址返回 [`TransportError::Unsupported`]
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>>;
 
     fn map<F, O>(self, f: F) -> map::Map<Self, F>
     where
@@ -51,6 +62,36 @@ pub trait Transport {
         map_err::MapErr::new(self, f)
     }
 
+    /// 组合另一个 Transport，当前 Transport 不支持地址时回退到 `other`，
+    /// 两者都支持时同时在两者上监听
+    fn or_transport<U>(self, other: U) -> or_transport::OrTransport<Self, U>
+    where
+        Self: Sized,
+        U: Transport,
+    {
+        or_transport::OrTransport::new(self, other)
+    }
+
+    /// 为拨号与入站连接升级设置超时，超时返回 [`upgrade::TransportUpgradeError::Timeout`]
+    fn timeout(self, timeout: Duration) -> timeout::Timeout<Self>
+    where
+        Self: Sized,
+    {
+        timeout::Timeout::new(self, timeout)
+    }
+
+    /// 将 Transport 装箱为 [`Boxed`]，擦除具体类型
+    fn boxed(self) -> Boxed<Self::Output>
+    where
+        Self: Sized + Send + Unpin + 'static,
+        Self::Error: Send + Sync,
+        Self::Dialer: Send + 'static,
+        Self::ListenerUpgrade: Send + 'static,
+        Self::Listener: Send,
+    {
+        boxed::boxed(self)
+    }
+
     fn upgrade(self) -> upgrade::Builder<Self>
     where
         Self: Sized,
@@ -60,12 +101,41 @@ pub trait Transport {
     }
 }
 
+/// [`Transport::listen`] / [`Transport::connect`] 返回的错误
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError<E> {
+    /// Transport 不支持该地址
+    #[error("Unsupported address: {0}")]
+    Unsupported(Multiaddr),
+    #[error(transparent)]
+    Other(E),
+}
+
+impl<E> TransportError<E> {
+    pub fn map<F, O>(self, f: F) -> TransportError<O>
+    where
+        F: FnOnce(E) -> O,
+    {
+        match self {
+            TransportError::Unsupported(addr) => TransportError::Unsupported(addr),
+            TransportError::Other(err) => TransportError::Other(f(err)),
+        }
+    }
+}
+
 pub enum ListenerEvent<T, E> {
-    Listened(SocketAddr),
+    /// 开始在该地址上监听，监听通配地址时每个具体地址产生一次
+    Listened(Multiaddr),
+    /// 网络接口变化后新增的监听地址
+    AddressAdded(Multiaddr),
+    /// 网络接口变化后失效的监听地址
+    AddressExpired(Multiaddr),
     Incoming {
-        local_addr: SocketAddr,
-        remote_addr: SocketAddr,
+        local_addr: Multiaddr,
+        remote_addr: Multiaddr,
         upgrade: T,
+        /// 该连接的升级计数，包装监听器的升级 Future 持有其克隆，参见 [`UpgradeGuard`]
+        guard: UpgradeGuard,
     },
     Closed(Result<(), E>),
     Error(E),
@@ -78,34 +148,63 @@ impl<U, E> ListenerEvent<U, E> {
     {
         match self {
             ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr),
+            ListenerEvent::AddressAdded(addr) => ListenerEvent::AddressAdded(addr),
+            ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(addr),
             ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade,
+                guard,
             } => ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade: f(upgrade),
+                guard,
             },
             ListenerEvent::Closed(result) => ListenerEvent::Closed(result),
             ListenerEvent::Error(err) => ListenerEvent::Error(err),
         }
     }
 
+    /// 与 [`ListenerEvent::map_upgrade`] 相同，同时把连接的 [`UpgradeGuard`] 交给 `f`
+    pub fn map_upgrade_with_guard<F, O>(self, f: F) -> ListenerEvent<O, E>
+    where
+        F: FnOnce(U, &UpgradeGuard) -> O,
+    {
+        match self {
+            ListenerEvent::Incoming {
+                local_addr,
+                remote_addr,
+                upgrade,
+                guard,
+            } => ListenerEvent::Incoming {
+                local_addr,
+                remote_addr,
+                upgrade: f(upgrade, &guard),
+                guard,
+            },
+            event => event.map_upgrade(|_| unreachable!("only incoming events carry an upgrade")),
+        }
+    }
+
     pub fn map_err<F, O>(self, f: F) -> ListenerEvent<U, O>
     where
         F: FnOnce(E) -> O,
     {
         match self {
             ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr),
+            ListenerEvent::AddressAdded(addr) => ListenerEvent::AddressAdded(addr),
+            ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(addr),
             ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade,
+                guard,
             } => ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade,
+                guard,
             },
             ListenerEvent::Closed(result) => ListenerEvent::Closed(result.map_err(f)),
             ListenerEvent::Error(err) => ListenerEvent::Error(f(err)),
@@ -119,14 +218,18 @@ impl<U, E> ListenerEvent<U, E> {
     {
         match self {
             ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr),
+            ListenerEvent::AddressAdded(addr) => ListenerEvent::AddressAdded(addr),
+            ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(addr),
             ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade,
+                guard,
             } => ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
                 upgrade: upgrade.map_err(f),
+                guard,
             },
             ListenerEvent::Closed(result) => ListenerEvent::Closed(result),
             ListenerEvent::Error(err) => ListenerEvent::Error(err),
@@ -141,10 +244,12 @@ where
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ListenerEvent::Listened(addr) => write!(f, "Listened({})", addr),
+            ListenerEvent::AddressAdded(addr) => write!(f, "AddressAdded({})", addr),
+            ListenerEvent::AddressExpired(addr) => write!(f, "AddressExpired({})", addr),
             ListenerEvent::Incoming {
                 local_addr,
                 remote_addr,
-                upgrade: _,
+                ..
             } => write!(
                 f,
                 "Incoming(local: {}, remote: {})",
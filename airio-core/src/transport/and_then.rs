@@ -1,7 +1,6 @@
 use std::{
     error,
     marker::{PhantomData, PhantomPinned},
-    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -9,7 +8,10 @@ use std::{
 use either::Either;
 use futures::{Stream, TryFuture};
 
-use crate::{ConnectedPoint, ListenerEvent, Transport};
+use crate::{
+    ConnectedPoint, ListenerEvent, Multiaddr, Transport, TransportError,
+    transport::{Listener, ListenerHandle, UpgradeGuard},
+};
 
 /// 在T::Output上应用函数C, 生成一个新的 Future<Output = D>。
 #[pin_project::pin_project]
@@ -42,18 +44,25 @@ where
     type Dialer = AndThenFuture<T::Dialer, TMap, TFut>;
     type Listener = AndThenListener<T, TMap>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
-        let dialer = self.transport.connect(addr).map_err(Either::Left)?;
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let dialer = self
+            .transport
+            .connect(addr.clone())
+            .map_err(|e| e.map(Either::Left))?;
         let connected_point = ConnectedPoint::Dialer { addr };
         Ok(AndThenFuture {
             inner: Either::Left(Box::pin(dialer)),
             args: Some((self.map.clone(), connected_point)),
+            _guard: None,
             _marker: PhantomPinned,
         })
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
-        let listener = self.transport.listen(addr).map_err(Either::Left)?;
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .transport
+            .listen(addr)
+            .map_err(|e| e.map(Either::Left))?;
         Ok(AndThenListener {
             inner: listener,
             map: self.map.clone(),
@@ -89,24 +98,29 @@ where
         let event = match Pin::new(&mut this.inner).as_mut().poll_next(cx) {
             Poll::Ready(Some(event)) => match event {
                 ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr),
+                ListenerEvent::AddressAdded(addr) => ListenerEvent::AddressAdded(addr),
+                ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(addr),
                 ListenerEvent::Incoming {
                     local_addr,
                     remote_addr,
                     upgrade,
+                    guard,
                 } => ListenerEvent::Incoming {
-                    local_addr,
-                    remote_addr,
                     upgrade: AndThenFuture {
                         inner: Either::Left(Box::pin(upgrade)),
                         args: Some((
                             this.map.clone(),
                             ConnectedPoint::Listener {
-                                local_addr,
-                                remote_addr,
+                                local_addr: local_addr.clone(),
+                                remote_addr: remote_addr.clone(),
                             },
                         )),
+                        _guard: Some(guard.clone()),
                         _marker: PhantomPinned,
                     },
+                    local_addr,
+                    remote_addr,
+                    guard,
                 },
                 ListenerEvent::Closed(result) => {
                     ListenerEvent::Closed(result.map_err(Either::Left))
@@ -117,7 +131,19 @@ where
             Poll::Pending => return Poll::Pending,
         };
 
-        return Poll::Ready(Some(event));
+        Poll::Ready(Some(event))
+    }
+}
+
+impl<O, T, TMap, TFut> Listener for AndThenListener<T, TMap>
+where
+    T: Transport,
+    TMap: FnOnce(T::Output, ConnectedPoint) -> TFut + Clone,
+    TFut: TryFuture<Ok = O>,
+    TFut::Error: error::Error,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
     }
 }
 
@@ -125,6 +151,9 @@ where
 pub struct AndThenFuture<TFut, TMap, TMapFut> {
     inner: Either<Pin<Box<TFut>>, Pin<Box<TMapFut>>>,
     args: Option<(TMap, ConnectedPoint)>,
+    /// 入站升级完成前计入监听器的进行中升级。错误类型无法表示中止，
+    /// 监听器关闭超时后由内部持有 guard 的升级负责中止
+    _guard: Option<UpgradeGuard>,
     _marker: PhantomPinned,
 }
 
@@ -0,0 +1,672 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    io,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use futures::{AsyncRead, AsyncWrite, Stream, TryFuture};
+
+use crate::{
+    ListenerEvent, Multiaddr, PeerId, StreamMuxer, Transport, TransportError, Upgrade, UpgradeInfo,
+    muxing::{StreamMuxerBox, StreamMuxerEvent, SubstreamBox},
+    transport::{Listener, ListenerHandle},
+};
+
+/// 统计流量的共享句柄，克隆开销很小。
+///
+/// 数据来自三处：[`Bandwidth`] 统计每个连接的原始字节数（计入总量与连接），
+/// [`BandwidthStats::meter_muxer`] 统计认证之后每个子流的字节数（计入 PeerId），
+/// [`BandwidthStats::meter_protocol`] 统计协商完成之后的字节数（计入协议）。
+/// 节点与协议的统计只保留仍有连接或子流在使用的条目。
+#[derive(Clone, Default)]
+pub struct BandwidthStats {
+    inner: Arc<StatsInner>,
+}
+
+#[derive(Default)]
+struct StatsInner {
+    total: Arc<Counters>,
+    next_connection_id: AtomicU64,
+    connections: Mutex<HashMap<ConnectionId, Arc<Counters>>>,
+    peers: Mutex<HashMap<PeerId, Arc<Counters>>>,
+    protocols: Mutex<HashMap<String, Arc<Counters>>>,
+}
+
+/// 入站与出站的字节数
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Traffic {
+    pub inbound: u64,
+    pub outbound: u64,
+}
+
+/// [`Bandwidth`] 为每个连接分配的编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(u64);
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    inbound: AtomicU64,
+    outbound: AtomicU64,
+}
+
+impl Counters {
+    fn traffic(&self) -> Traffic {
+        Traffic {
+            inbound: self.inbound.load(Ordering::Relaxed),
+            outbound: self.outbound.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl BandwidthStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 所有连接的总流量，包括已关闭的连接
+    pub fn total(&self) -> Traffic {
+        self.inner.total.traffic()
+    }
+
+    /// 仍然存活的连接的流量
+    pub fn connection(&self, id: ConnectionId) -> Option<Traffic> {
+        lock(&self.inner.connections).get(&id).map(|c| c.traffic())
+    }
+
+    pub fn connections(&self) -> Vec<(ConnectionId, Traffic)> {
+        snapshot(&self.inner.connections)
+    }
+
+    /// 与该节点所有子流的累计流量，与该节点的连接都关闭后返回 `None`
+    pub fn peer(&self, peer_id: &PeerId) -> Option<Traffic> {
+        lock(&self.inner.peers)
+            .get(peer_id)
+            .filter(|c| in_use(c))
+            .map(|c| c.traffic())
+    }
+
+    pub fn peers(&self) -> Vec<(PeerId, Traffic)> {
+        snapshot(&self.inner.peers)
+    }
+
+    /// 该协议协商完成之后的累计流量，该协议的子流都关闭后返回 `None`
+    pub fn protocol(&self, protocol: &str) -> Option<Traffic> {
+        lock(&self.inner.protocols)
+            .get(protocol)
+            .filter(|c| in_use(c))
+            .map(|c| c.traffic())
+    }
+
+    pub fn protocols(&self) -> Vec<(String, Traffic)> {
+        snapshot(&self.inner.protocols)
+    }
+
+    /// 包装已认证连接的多路复用器，之后打开的每个子流的流量都计入 `peer_id`。
+    ///
+    /// 子流为 [`Metered`]，单个子流的流量可由 [`Metered::traffic`] 读取。
+    pub fn meter_muxer(&self, peer_id: PeerId, muxer: StreamMuxerBox) -> MeteredMuxer {
+        let counters = counters_for(&self.inner.peers, peer_id);
+        MeteredMuxer {
+            inner: muxer,
+            counters,
+        }
+    }
+
+    /// 包装子流上的升级，协商出的协议之后的流量计入该协议
+    pub fn meter_protocol<U>(&self, upgrade: U) -> MeteredUpgrade<U> {
+        MeteredUpgrade {
+            inner: upgrade,
+            stats: self.clone(),
+        }
+    }
+
+    fn register_connection(&self) -> (Vec<Arc<Counters>>, Registration) {
+        let id = ConnectionId(
+            self.inner
+                .next_connection_id
+                .fetch_add(1, Ordering::Relaxed),
+        );
+        let counters = Arc::new(Counters::default());
+        lock(&self.inner.connections).insert(id, counters.clone());
+        let registration = Registration {
+            stats: self.clone(),
+            id,
+        };
+        (vec![self.inner.total.clone(), counters], registration)
+    }
+}
+
+impl fmt::Debug for BandwidthStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthStats")
+            .field("total", &self.total())
+            .finish()
+    }
+}
+
+fn lock<K, V>(map: &Mutex<HashMap<K, V>>) -> std::sync::MutexGuard<'_, HashMap<K, V>> {
+    map.lock().expect("bandwidth stats poisoned")
+}
+
+/// 统计表之外是否还有持有者
+fn in_use(counters: &Arc<Counters>) -> bool {
+    Arc::strong_count(counters) > 1
+}
+
+/// 取出 `key` 的计数器，新建条目时顺带移除已无人使用的条目
+fn counters_for<K: Hash + Eq>(map: &Mutex<HashMap<K, Arc<Counters>>>, key: K) -> Arc<Counters> {
+    let mut map = lock(map);
+    if let Some(counters) = map.get(&key) {
+        return counters.clone();
+    }
+    map.retain(|_, counters| in_use(counters));
+    map.entry(key).or_default().clone()
+}
+
+fn snapshot<K: Clone + Hash + Eq>(map: &Mutex<HashMap<K, Arc<Counters>>>) -> Vec<(K, Traffic)> {
+    let mut map = lock(map);
+    map.retain(|_, counters| in_use(counters));
+    map.iter().map(|(k, c)| (k.clone(), c.traffic())).collect()
+}
+
+/// 连接关闭时从统计中移除
+struct Registration {
+    stats: BandwidthStats,
+    id: ConnectionId,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock(&self.stats.inner.connections).remove(&self.id);
+    }
+}
+
+/// 统计每个连接流量的 Transport 包装，输出 [`Metered`]
+#[derive(Debug, Clone)]
+pub struct Bandwidth<T> {
+    inner: T,
+    stats: BandwidthStats,
+}
+
+impl<T> Bandwidth<T> {
+    pub fn new(inner: T, stats: BandwidthStats) -> Self {
+        Bandwidth { inner, stats }
+    }
+
+    pub fn stats(&self) -> &BandwidthStats {
+        &self.stats
+    }
+}
+
+impl<T> Transport for Bandwidth<T>
+where
+    T: Transport,
+    T::Output: AsyncRead + AsyncWrite,
+{
+    type Output = Metered<T::Output>;
+    type Error = T::Error;
+    type Dialer = BandwidthFuture<T::Dialer>;
+    type ListenerUpgrade = BandwidthFuture<T::ListenerUpgrade>;
+    type Listener = BandwidthListener<T::Listener>;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        Ok(BandwidthListener {
+            inner: self.inner.listen(addr)?,
+            stats: self.stats.clone(),
+        })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        Ok(BandwidthFuture {
+            inner: self.inner.connect(addr)?,
+            stats: self.stats.clone(),
+        })
+    }
+}
+
+#[pin_project::pin_project]
+pub struct BandwidthListener<L> {
+    #[pin]
+    inner: L,
+    stats: BandwidthStats,
+}
+
+impl<L, U, E> Stream for BandwidthListener<L>
+where
+    L: Stream<Item = ListenerEvent<U, E>>,
+{
+    type Item = ListenerEvent<BandwidthFuture<U>, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let stats = this.stats.clone();
+        this.inner.poll_next(cx).map(|event| {
+            event.map(|event| event.map_upgrade(|inner| BandwidthFuture { inner, stats }))
+        })
+    }
+}
+
+impl<L, U, E> Listener for BandwidthListener<L>
+where
+    L: Listener<Item = ListenerEvent<U, E>>,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+#[pin_project::pin_project]
+pub struct BandwidthFuture<F> {
+    #[pin]
+    inner: F,
+    stats: BandwidthStats,
+}
+
+impl<F> Future for BandwidthFuture<F>
+where
+    F: TryFuture,
+{
+    type Output = Result<Metered<F::Ok>, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let stream = futures::ready!(this.inner.try_poll(cx))?;
+        let (counters, registration) = this.stats.register_connection();
+        Poll::Ready(Ok(Metered {
+            inner: stream,
+            counters,
+            traffic: Traffic::default(),
+            registration: Some(registration),
+        }))
+    }
+}
+
+/// 把读写的字节数计入统计的流
+#[pin_project::pin_project]
+pub struct Metered<S> {
+    #[pin]
+    inner: S,
+    counters: Vec<Arc<Counters>>,
+    /// 该流自身的流量
+    traffic: Traffic,
+    registration: Option<Registration>,
+}
+
+impl<S> Metered<S> {
+    /// 由 [`Bandwidth`] 建立的连接的编号
+    pub fn connection_id(&self) -> Option<ConnectionId> {
+        self.registration.as_ref().map(|r| r.id)
+    }
+
+    /// 经由该流读写的字节数
+    pub fn traffic(&self) -> Traffic {
+        self.traffic
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Metered<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metered")
+            .field("inner", &self.inner)
+            .field("connection_id", &self.connection_id())
+            .field("traffic", &self.traffic)
+            .finish()
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for Metered<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let n = futures::ready!(this.inner.poll_read(cx, buf))?;
+        for counters in this.counters.iter() {
+            counters.inbound.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        this.traffic.inbound += n as u64;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let n = futures::ready!(this.inner.poll_read_vectored(cx, bufs))?;
+        for counters in this.counters.iter() {
+            counters.inbound.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        this.traffic.inbound += n as u64;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for Metered<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let n = futures::ready!(this.inner.poll_write(cx, buf))?;
+        for counters in this.counters.iter() {
+            counters.outbound.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        this.traffic.outbound += n as u64;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let n = futures::ready!(this.inner.poll_write_vectored(cx, bufs))?;
+        for counters in this.counters.iter() {
+            counters.outbound.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        this.traffic.outbound += n as u64;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+/// [`BandwidthStats::meter_muxer`] 返回的多路复用器，子流计入同一个节点
+pub struct MeteredMuxer {
+    inner: StreamMuxerBox,
+    counters: Arc<Counters>,
+}
+
+impl MeteredMuxer {
+    fn meter(&self, substream: SubstreamBox) -> Metered<SubstreamBox> {
+        Metered {
+            inner: substream,
+            counters: vec![self.counters.clone()],
+            traffic: Traffic::default(),
+            registration: None,
+        }
+    }
+}
+
+impl fmt::Debug for MeteredMuxer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredMuxer")
+            .field("traffic", &self.counters.traffic())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamMuxer for MeteredMuxer {
+    type Substream = Metered<SubstreamBox>;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let substream = futures::ready!(Pin::new(&mut this.inner).poll_inbound(cx))?;
+        Poll::Ready(Ok(this.meter(substream)))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let substream = futures::ready!(Pin::new(&mut this.inner).poll_outbound(cx))?;
+        Poll::Ready(Ok(this.meter(substream)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll(cx)
+    }
+}
+
+/// [`BandwidthStats::meter_protocol`] 返回的升级
+#[derive(Debug, Clone)]
+pub struct MeteredUpgrade<U> {
+    inner: U,
+    stats: BandwidthStats,
+}
+
+impl<U> MeteredUpgrade<U> {
+    fn meter<S>(&self, stream: S, protocol: &str) -> Metered<S> {
+        let counters = counters_for(&self.stats.inner.protocols, protocol.to_owned());
+        Metered {
+            inner: stream,
+            counters: vec![counters],
+            traffic: Traffic::default(),
+            registration: None,
+        }
+    }
+}
+
+impl<U: UpgradeInfo> UpgradeInfo for MeteredUpgrade<U> {
+    type Info = U::Info;
+    type InfoIter = U::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.inner.protocol_info()
+    }
+}
+
+impl<S, U> Upgrade<S> for MeteredUpgrade<U>
+where
+    U: Upgrade<Metered<S>>,
+{
+    type Output = U::Output;
+    type Error = U::Error;
+    type Future = U::Future;
+
+    fn upgrade_inbound(self, stream: S, info: Self::Info) -> Self::Future {
+        let stream = self.meter(stream, info.as_ref());
+        self.inner.upgrade_inbound(stream, info)
+    }
+
+    fn upgrade_outbound(self, stream: S, info: Self::Info) -> Self::Future {
+        let stream = self.meter(stream, info.as_ref());
+        self.inner.upgrade_outbound(stream, info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{AsyncReadExt, AsyncWriteExt, StreamExt, executor::block_on, future};
+
+    use super::*;
+    use crate::{
+        identity::Keypair,
+        muxing::StreamMuxerExt,
+        transport::memory::{Channel, MemoryTransport},
+        upgrade::ReadyUpgrade,
+    };
+
+    /// 只打开一个出站子流的多路复用器
+    struct OneOutbound<C>(Option<C>);
+
+    impl<C> StreamMuxer for OneOutbound<C>
+    where
+        C: AsyncRead + AsyncWrite + Unpin,
+    {
+        type Substream = C;
+        type Error = io::Error;
+
+        fn poll_inbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Pending
+        }
+
+        fn poll_outbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            match self.get_mut().0.take() {
+                Some(stream) => Poll::Ready(Ok(stream)),
+                None => Poll::Pending,
+            }
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+            Poll::Pending
+        }
+    }
+
+    fn traffic(inbound: u64, outbound: u64) -> Traffic {
+        Traffic { inbound, outbound }
+    }
+
+    /// 经 [`Bandwidth`] 拨号，返回拨号端与监听端
+    fn connect(transport: &Bandwidth<MemoryTransport>) -> (Metered<Channel>, Channel) {
+        let mut listener = MemoryTransport::new()
+            .listen("/memory/0".parse().unwrap())
+            .unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            let dialer = transport.connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            (dialer, upgrade.await.unwrap())
+        })
+    }
+
+    async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+        local: &mut S,
+        remote: &mut Channel,
+        send: usize,
+        receive: usize,
+    ) {
+        local.write_all(&vec![0; send]).await.unwrap();
+        local.flush().await.unwrap();
+        remote.read_exact(&mut vec![0; send]).await.unwrap();
+        remote.write_all(&vec![0; receive]).await.unwrap();
+        remote.flush().await.unwrap();
+        local.read_exact(&mut vec![0; receive]).await.unwrap();
+    }
+
+    #[test]
+    fn counts_connection_peer_and_protocol() {
+        let stats = BandwidthStats::new();
+        let transport = Bandwidth::new(MemoryTransport::new(), stats.clone());
+        let (mut conn, mut remote) = connect(&transport);
+        let id = conn.connection_id().unwrap();
+        block_on(exchange(&mut conn, &mut remote, 5, 3));
+        assert_eq!(stats.connection(id), Some(traffic(3, 5)));
+        assert_eq!(stats.total(), traffic(3, 5));
+        assert_eq!(conn.traffic(), traffic(3, 5));
+
+        // 子流计入节点，同时计入所在的连接
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let mut muxer = stats.meter_muxer(peer_id, StreamMuxerBox::new(OneOutbound(Some(conn))));
+        let mut substream = block_on(future::poll_fn(|cx| muxer.poll_outbound_unpin(cx))).unwrap();
+        block_on(exchange(&mut substream, &mut remote, 4, 2));
+        assert_eq!(substream.traffic(), traffic(2, 4));
+        assert_eq!(stats.peer(&peer_id), Some(traffic(2, 4)));
+        assert_eq!(stats.connection(id), Some(traffic(5, 9)));
+
+        // 协商之后的流量计入协议，同时计入节点与连接
+        let upgrade = stats.meter_protocol(ReadyUpgrade::new("/test/1"));
+        let Ok(mut stream) = block_on(upgrade.upgrade_outbound(substream, "/test/1"));
+        block_on(exchange(&mut stream, &mut remote, 10, 1));
+        assert_eq!(stream.traffic(), traffic(1, 10));
+        assert_eq!(stream.get_ref().traffic(), traffic(3, 14));
+        assert_eq!(stats.protocol("/test/1"), Some(traffic(1, 10)));
+        assert_eq!(stats.peer(&peer_id), Some(traffic(3, 14)));
+        assert_eq!(stats.connection(id), Some(traffic(6, 19)));
+        assert_eq!(stats.total(), traffic(6, 19));
+        assert_eq!(stats.connections(), [(id, traffic(6, 19))]);
+        assert_eq!(stats.peers(), [(peer_id, traffic(3, 14))]);
+        assert_eq!(stats.protocols(), [("/test/1".to_owned(), traffic(1, 10))]);
+
+        // 第二个连接单独计数
+        let (mut second, mut second_remote) = connect(&transport);
+        block_on(exchange(&mut second, &mut second_remote, 1, 1));
+        assert_eq!(stats.connection(id), Some(traffic(6, 19)));
+        assert_eq!(stats.total(), traffic(7, 20));
+    }
+
+    #[test]
+    fn evicts_closed_entries() {
+        let stats = BandwidthStats::new();
+        let transport = Bandwidth::new(MemoryTransport::new(), stats.clone());
+        let (conn, _remote) = connect(&transport);
+        let id = conn.connection_id().unwrap();
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let mut muxer = stats.meter_muxer(peer_id, StreamMuxerBox::new(OneOutbound(Some(conn))));
+        let substream = block_on(future::poll_fn(|cx| muxer.poll_outbound_unpin(cx))).unwrap();
+        let upgrade = stats.meter_protocol(ReadyUpgrade::new("/test/1"));
+        let Ok(stream) = block_on(upgrade.upgrade_outbound(substream, "/test/1"));
+
+        // 子流仍在，节点与协议的条目保留
+        drop(muxer);
+        assert!(stats.peer(&peer_id).is_some());
+        assert!(stats.protocol("/test/1").is_some());
+
+        drop(stream);
+        assert_eq!(stats.connection(id), None);
+        assert_eq!(stats.peer(&peer_id), None);
+        assert_eq!(stats.protocol("/test/1"), None);
+        assert!(stats.peers().is_empty());
+        assert!(stats.protocols().is_empty());
+        assert!(lock(&stats.inner.peers).is_empty());
+        assert!(lock(&stats.inner.protocols).is_empty());
+
+        // 新的条目从零开始计数，并清理旧条目
+        let other = Keypair::generate_ed25519().to_peer_id();
+        let (conn, _remote) = connect(&transport);
+        let _muxer = stats.meter_muxer(other, StreamMuxerBox::new(OneOutbound(Some(conn))));
+        assert_eq!(stats.peer(&other), Some(traffic(0, 0)));
+        assert_eq!(lock(&stats.inner.peers).len(), 1);
+    }
+}
@@ -1,14 +1,21 @@
-use crate::{ListenerEvent, Transport};
+use crate::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    transport::{Listener, ListenerHandle},
+};
 use futures::{Stream, StreamExt, TryFutureExt};
-use std::{error, io, net::SocketAddr, pin::Pin};
+use std::{
+    error, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 pub struct Boxed<O> {
     inner: Box<dyn Abstract<O> + Send + Unpin>,
 }
 
 trait Abstract<O> {
-    fn connect(&self, addr: SocketAddr) -> io::Result<Dial<O>>;
-    fn listen(&self, addr: SocketAddr) -> io::Result<BoxedListener<O>>;
+    fn connect(&self, addr: Multiaddr) -> Result<Dial<O>, TransportError<io::Error>>;
+    fn listen(&self, addr: Multiaddr) -> Result<BoxedListener<O>, TransportError<io::Error>>;
 }
 
 impl<T, O> Abstract<O> for T
@@ -19,15 +26,16 @@ where
     T::ListenerUpgrade: Send + 'static,
     T::Listener: Send,
 {
-    fn connect(&self, addr: SocketAddr) -> io::Result<Dial<O>> {
+    fn connect(&self, addr: Multiaddr) -> Result<Dial<O>, TransportError<io::Error>> {
         let fut = Transport::connect(self, addr)
-            .map_err(box_err)?
-            .map_err(|e| box_err(e));
+            .map_err(|e| e.map(box_err))?
+            .map_err(box_err);
         Ok(Box::pin(fut) as Dial<O>)
     }
 
-    fn listen(&self, addr: SocketAddr) -> io::Result<BoxedListener<O>> {
-        let listener = Transport::listen(self, addr).map_err(box_err)?;
+    fn listen(&self, addr: Multiaddr) -> Result<BoxedListener<O>, TransportError<io::Error>> {
+        let listener = Transport::listen(self, addr).map_err(|e| e.map(box_err))?;
+        let handle = listener.handle().clone();
         let stream = listener
             .map(|event| {
                 event
@@ -36,14 +44,34 @@ where
                     .map_upgrade(|up| Box::pin(up) as ListenerUpgrade<O>)
             })
             .boxed();
-        Ok(stream)
+        Ok(BoxedListener {
+            inner: stream,
+            handle,
+        })
     }
 }
 
 type Dial<O> = Pin<Box<dyn Future<Output = io::Result<O>> + Send>>;
 type ListenerUpgrade<O> = Pin<Box<dyn Future<Output = io::Result<O>> + Send>>;
-type BoxedListener<O> =
-    Pin<Box<dyn Stream<Item = ListenerEvent<ListenerUpgrade<O>, io::Error>> + Send>>;
+
+pub struct BoxedListener<O> {
+    inner: Pin<Box<dyn Stream<Item = ListenerEvent<ListenerUpgrade<O>, io::Error>> + Send>>,
+    handle: ListenerHandle,
+}
+
+impl<O> Stream for BoxedListener<O> {
+    type Item = ListenerEvent<ListenerUpgrade<O>, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl<O> Listener for BoxedListener<O> {
+    fn handle(&self) -> &ListenerHandle {
+        &self.handle
+    }
+}
 
 impl<O> Transport for Boxed<O> {
     type Output = O;
@@ -52,11 +80,11 @@ impl<O> Transport for Boxed<O> {
     type Dialer = Dial<O>;
     type Listener = BoxedListener<O>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
         self.inner.connect(addr)
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
         self.inner.listen(addr)
     }
 }
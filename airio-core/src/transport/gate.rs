@@ -0,0 +1,723 @@
+use std::{
+    collections::HashMap,
+    error, fmt, io,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::{AsyncRead, AsyncWrite, Stream, TryFuture};
+use ipnet::IpNet;
+
+use crate::{
+    ListenerEvent, Multiaddr, PeerId, StreamMuxer, Transport, TransportError,
+    multiaddr::Protocol,
+    muxing::StreamMuxerEvent,
+    transport::{Listener, ListenerHandle},
+};
+
+/// 在升级之前拒绝连接的 Transport 包装。
+///
+/// 入站连接在 `Incoming` 事件产生时按对端 IP 与进行中的升级数检查，被拒绝的连接直接丢弃，
+/// 以 [`ListenerEvent::Error`] 报告；拨号在 [`Transport::connect`] 时检查。
+/// 内部 Transport 需已完成身份认证（输出 `(PeerId, M)`），升级完成后再按总数、IP 与 PeerId
+/// 检查已建立的连接数，输出的 [`Gated`] 被 drop 时释放名额。
+pub struct Gate<T> {
+    inner: T,
+    keeper: Gatekeeper,
+}
+
+impl<T> Gate<T> {
+    pub fn new(inner: T) -> Self {
+        Gate {
+            inner,
+            keeper: Gatekeeper {
+                limits: Arc::new(Limits::default()),
+                state: Arc::new(Mutex::new(State::default())),
+            },
+        }
+    }
+
+    /// 只允许来自或拨向 `net` 的连接，可多次调用；未设置时允许所有地址。
+    ///
+    /// 设置后无法确定 IP 的地址（如 `/dns`）一律拒绝，需先解析再交给 [`Gate`]。
+    pub fn allow(mut self, net: impl Into<IpNet>) -> Self {
+        self.limits_mut().allow.push(net.into());
+        self
+    }
+
+    /// 拒绝来自或拨向 `net` 的连接，优先于 [`Gate::allow`]
+    pub fn deny(mut self, net: impl Into<IpNet>) -> Self {
+        self.limits_mut().deny.push(net.into());
+        self
+    }
+
+    /// 进行中的升级（包括拨号）的最大数量
+    pub fn max_pending_upgrades(mut self, max: usize) -> Self {
+        self.limits_mut().max_pending = Some(max);
+        self
+    }
+
+    /// 已建立连接的最大数量
+    pub fn max_established(mut self, max: usize) -> Self {
+        self.limits_mut().max_established = Some(max);
+        self
+    }
+
+    /// 与同一 IP 的已建立连接的最大数量
+    pub fn max_established_per_ip(mut self, max: usize) -> Self {
+        self.limits_mut().max_established_per_ip = Some(max);
+        self
+    }
+
+    /// 与同一节点的已建立连接的最大数量
+    pub fn max_established_per_peer(mut self, max: usize) -> Self {
+        self.limits_mut().max_established_per_peer = Some(max);
+        self
+    }
+
+    /// 当前已建立的连接数
+    pub fn established(&self) -> usize {
+        self.keeper.state().established
+    }
+
+    /// 当前进行中的升级数
+    pub fn pending(&self) -> usize {
+        self.keeper.state().pending
+    }
+
+    fn limits_mut(&mut self) -> &mut Limits {
+        Arc::make_mut(&mut self.keeper.limits)
+    }
+}
+
+impl<T> fmt::Debug for Gate<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gate")
+            .field("limits", &self.keeper.limits)
+            .finish()
+    }
+}
+
+impl<T, M> Transport for Gate<T>
+where
+    T: Transport<Output = (PeerId, M)>,
+{
+    type Output = (PeerId, Gated<M>);
+    type Error = GateError<T::Error>;
+    type Dialer = GateFuture<T::Dialer>;
+    type ListenerUpgrade = GateFuture<T::ListenerUpgrade>;
+    type Listener = GateListener<T::Listener>;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .inner
+            .listen(addr)
+            .map_err(|e| e.map(GateError::Transport))?;
+        Ok(GateListener {
+            inner: listener,
+            keeper: self.keeper.clone(),
+        })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let ip = ip_of(&addr);
+        let pending = self
+            .keeper
+            .admit(&addr, ip)
+            .map_err(TransportError::Other)?;
+        let dialer = self
+            .inner
+            .connect(addr)
+            .map_err(|e| e.map(GateError::Transport))?;
+        Ok(GateFuture {
+            inner: dialer,
+            keeper: self.keeper.clone(),
+            ip,
+            pending: Some(pending),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Limits {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    max_pending: Option<usize>,
+    max_established: Option<usize>,
+    max_established_per_ip: Option<usize>,
+    max_established_per_peer: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: usize,
+    established: usize,
+    per_ip: HashMap<IpAddr, usize>,
+    per_peer: HashMap<PeerId, usize>,
+}
+
+#[derive(Clone)]
+struct Gatekeeper {
+    limits: Arc<Limits>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Gatekeeper {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("gate state poisoned")
+    }
+
+    /// 升级开始前的检查：地址列表、进行中的升级数，以及可以提前判断的已建立连接数
+    fn admit<E>(
+        &self,
+        addr: &Multiaddr,
+        ip: Option<IpAddr>,
+    ) -> Result<PendingPermit, GateError<E>> {
+        let limits = &self.limits;
+        match ip {
+            Some(ip) => {
+                if limits.deny.iter().any(|net| net.contains(&ip)) {
+                    return Err(GateError::Denied(ip));
+                }
+                if !limits.allow.is_empty() && !limits.allow.iter().any(|net| net.contains(&ip)) {
+                    return Err(GateError::NotAllowed(ip));
+                }
+            }
+            None if !limits.allow.is_empty() => {
+                return Err(GateError::NotIpAddress(addr.clone()));
+            }
+            None => {}
+        }
+        let mut state = self.state();
+        if let Some(limit) = limits.max_pending
+            && state.pending >= limit
+        {
+            return Err(GateError::PendingLimit(limit));
+        }
+        check_established(limits, &state, ip)?;
+        state.pending += 1;
+        Ok(PendingPermit {
+            keeper: self.clone(),
+        })
+    }
+
+    /// 升级完成后登记连接，超出限制时返回错误
+    fn establish<E>(
+        &self,
+        ip: Option<IpAddr>,
+        peer_id: PeerId,
+    ) -> Result<ConnectionPermit, GateError<E>> {
+        let limits = &self.limits;
+        let mut state = self.state();
+        check_established(limits, &state, ip)?;
+        if let Some(limit) = limits.max_established_per_peer
+            && state.per_peer.get(&peer_id).copied().unwrap_or(0) >= limit
+        {
+            return Err(GateError::PerPeerLimit { peer_id, limit });
+        }
+        state.established += 1;
+        if let Some(ip) = ip {
+            *state.per_ip.entry(ip).or_default() += 1;
+        }
+        *state.per_peer.entry(peer_id).or_default() += 1;
+        Ok(ConnectionPermit {
+            keeper: self.clone(),
+            ip,
+            peer_id,
+        })
+    }
+}
+
+fn check_established<E>(
+    limits: &Limits,
+    state: &State,
+    ip: Option<IpAddr>,
+) -> Result<(), GateError<E>> {
+    if let Some(limit) = limits.max_established
+        && state.established >= limit
+    {
+        return Err(GateError::EstablishedLimit(limit));
+    }
+    if let Some(limit) = limits.max_established_per_ip
+        && let Some(ip) = ip
+        && state.per_ip.get(&ip).copied().unwrap_or(0) >= limit
+    {
+        return Err(GateError::PerIpLimit { ip, limit });
+    }
+    Ok(())
+}
+
+/// 地址首段的 IP，IPv4 映射的 IPv6 地址按 IPv4 处理
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.protocols().first()? {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(*ip)),
+        Protocol::Ip6(ip) => Some(
+            ip.to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(*ip)),
+        ),
+        _ => None,
+    }
+}
+
+/// 进行中的升级占用的名额
+struct PendingPermit {
+    keeper: Gatekeeper,
+}
+
+impl Drop for PendingPermit {
+    fn drop(&mut self) {
+        self.keeper.state().pending -= 1;
+    }
+}
+
+/// 已建立连接占用的名额
+struct ConnectionPermit {
+    keeper: Gatekeeper,
+    ip: Option<IpAddr>,
+    peer_id: PeerId,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut state = self.keeper.state();
+        state.established -= 1;
+        if let Some(ip) = self.ip {
+            decrement(&mut state.per_ip, &ip);
+        }
+        decrement(&mut state.per_peer, &self.peer_id);
+    }
+}
+
+fn decrement<K: std::hash::Hash + Eq>(map: &mut HashMap<K, usize>, key: &K) {
+    if let Some(count) = map.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            map.remove(key);
+        }
+    }
+}
+
+#[pin_project::pin_project]
+pub struct GateListener<L> {
+    #[pin]
+    inner: L,
+    keeper: Gatekeeper,
+}
+
+impl<L, U, E, M> Stream for GateListener<L>
+where
+    L: Stream<Item = ListenerEvent<U, E>>,
+    U: TryFuture<Ok = (PeerId, M), Error = E>,
+    E: error::Error,
+{
+    type Item = ListenerEvent<GateFuture<U>, GateError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let event = match futures::ready!(this.inner.poll_next(cx)) {
+            Some(ListenerEvent::Incoming {
+                local_addr,
+                remote_addr,
+                upgrade,
+                guard,
+            }) => {
+                let ip = ip_of(&remote_addr);
+                match this.keeper.admit(&remote_addr, ip) {
+                    Ok(pending) => ListenerEvent::Incoming {
+                        local_addr,
+                        remote_addr,
+                        upgrade: GateFuture {
+                            inner: upgrade,
+                            keeper: this.keeper.clone(),
+                            ip,
+                            pending: Some(pending),
+                        },
+                        guard,
+                    },
+                    Err(e) => {
+                        // drop 升级即关闭连接
+                        tracing::debug!("Rejected incoming connection from {}: {}", remote_addr, e);
+                        ListenerEvent::Error(e)
+                    }
+                }
+            }
+            Some(ListenerEvent::Listened(addr)) => ListenerEvent::Listened(addr),
+            Some(ListenerEvent::AddressAdded(addr)) => ListenerEvent::AddressAdded(addr),
+            Some(ListenerEvent::AddressExpired(addr)) => ListenerEvent::AddressExpired(addr),
+            Some(ListenerEvent::Closed(result)) => {
+                ListenerEvent::Closed(result.map_err(GateError::Transport))
+            }
+            Some(ListenerEvent::Error(e)) => ListenerEvent::Error(GateError::Transport(e)),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(event))
+    }
+}
+
+impl<L, U, E, M> Listener for GateListener<L>
+where
+    L: Listener<Item = ListenerEvent<U, E>>,
+    U: TryFuture<Ok = (PeerId, M), Error = E>,
+    E: error::Error,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+/// 升级完成后登记连接的 Future
+#[pin_project::pin_project]
+pub struct GateFuture<F> {
+    #[pin]
+    inner: F,
+    keeper: Gatekeeper,
+    ip: Option<IpAddr>,
+    pending: Option<PendingPermit>,
+}
+
+impl<F, M> Future for GateFuture<F>
+where
+    F: TryFuture<Ok = (PeerId, M)>,
+{
+    type Output = Result<(PeerId, Gated<M>), GateError<F::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures::ready!(this.inner.try_poll(cx));
+        this.pending.take();
+        let (peer_id, inner) = result.map_err(GateError::Transport)?;
+        let permit = this.keeper.establish(*this.ip, peer_id)?;
+        Poll::Ready(Ok((
+            peer_id,
+            Gated {
+                inner,
+                _permit: permit,
+            },
+        )))
+    }
+}
+
+/// 通过 [`Gate`] 建立的连接，被 drop 时释放占用的名额
+#[pin_project::pin_project]
+pub struct Gated<M> {
+    #[pin]
+    inner: M,
+    _permit: ConnectionPermit,
+}
+
+impl<M> Gated<M> {
+    pub fn get_ref(&self) -> &M {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for Gated<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Gated").field(&self.inner).finish()
+    }
+}
+
+impl<M: StreamMuxer> StreamMuxer for Gated<M> {
+    type Substream = M::Substream;
+    type Error = M::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        self.project().inner.poll_inbound(cx)
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        self.project().inner.poll_outbound(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.project().inner.poll(cx)
+    }
+}
+
+impl<M: AsyncRead> AsyncRead for Gated<M> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+}
+
+impl<M: AsyncWrite> AsyncWrite for Gated<M> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+/// 被 [`Gate`] 拒绝的原因，每种限制对应一个变体
+#[derive(Debug, thiserror::Error)]
+pub enum GateError<E> {
+    #[error("Address {0} is in the deny list")]
+    Denied(IpAddr),
+    #[error("Address {0} is not in the allow list")]
+    NotAllowed(IpAddr),
+    #[error("Address {0} has no IP to check against the allow list")]
+    NotIpAddress(Multiaddr),
+    #[error("Too many pending upgrades (limit {0})")]
+    PendingLimit(usize),
+    #[error("Too many established connections (limit {0})")]
+    EstablishedLimit(usize),
+    #[error("Too many established connections with {ip} (limit {limit})")]
+    PerIpLimit { ip: IpAddr, limit: usize },
+    #[error("Too many established connections with {peer_id} (limit {limit})")]
+    PerPeerLimit { peer_id: PeerId, limit: usize },
+    #[error(transparent)]
+    Transport(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, StreamExt, executor::block_on, future::Ready};
+
+    use super::*;
+    use crate::identity::Keypair;
+
+    /// 拨号总是成功的 Transport，对端的 `PeerId` 取自地址末尾的 `/p2p`，没有时取 `default_peer`；
+    /// 监听时依次产生 `incoming` 中的连接
+    struct MockTransport {
+        default_peer: PeerId,
+        incoming: Vec<Multiaddr>,
+    }
+
+    impl MockTransport {
+        fn new() -> Self {
+            Self {
+                default_peer: Keypair::generate_ed25519().to_peer_id(),
+                incoming: Vec::new(),
+            }
+        }
+    }
+
+    type Event = ListenerEvent<Ready<io::Result<(PeerId, ())>>, io::Error>;
+
+    struct MockListener {
+        events: Vec<Event>,
+        handle: ListenerHandle,
+    }
+
+    impl Stream for MockListener {
+        type Item = Event;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Event>> {
+            match self.events.pop() {
+                Some(event) => Poll::Ready(Some(event)),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    impl Listener for MockListener {
+        fn handle(&self) -> &ListenerHandle {
+            &self.handle
+        }
+    }
+
+    impl Transport for MockTransport {
+        type Output = (PeerId, ());
+        type Error = io::Error;
+        type Dialer = Ready<io::Result<(PeerId, ())>>;
+        type ListenerUpgrade = Ready<io::Result<(PeerId, ())>>;
+        type Listener = MockListener;
+
+        fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+            let handle = ListenerHandle::new();
+            let events = self
+                .incoming
+                .iter()
+                .rev()
+                .map(|remote_addr| ListenerEvent::Incoming {
+                    local_addr: addr.clone(),
+                    remote_addr: remote_addr.clone(),
+                    upgrade: futures::future::ok((self.default_peer, ())),
+                    guard: handle.begin_incoming(),
+                })
+                .collect();
+            Ok(MockListener { events, handle })
+        }
+
+        fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+            let peer_id = match addr.iter().last() {
+                Some(Protocol::P2p(peer_id)) => *peer_id,
+                _ => self.default_peer,
+            };
+            Ok(futures::future::ok((peer_id, ())))
+        }
+    }
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    fn dial<T>(gate: &Gate<T>, s: &str) -> Result<Gated<()>, GateError<io::Error>>
+    where
+        T: Transport<Output = (PeerId, ()), Error = io::Error>,
+    {
+        match gate.connect(addr(s)) {
+            Ok(dialer) => block_on(dialer).map(|(_, gated)| gated),
+            Err(TransportError::Other(e)) => Err(e),
+            Err(TransportError::Unsupported(addr)) => panic!("unsupported {addr}"),
+        }
+    }
+
+    #[test]
+    fn deny_and_allow_lists() {
+        let gate = Gate::new(MockTransport::new())
+            .allow(net("10.0.0.0/8"))
+            .deny(net("10.0.0.1/32"));
+        assert!(matches!(
+            dial(&gate, "/ip4/10.0.0.1/tcp/1"),
+            Err(GateError::Denied(_))
+        ));
+        // IPv4 映射的 IPv6 地址按 IPv4 检查
+        assert!(matches!(
+            dial(&gate, "/ip6/::ffff:10.0.0.1/tcp/1"),
+            Err(GateError::Denied(_))
+        ));
+        assert!(matches!(
+            dial(&gate, "/ip4/192.0.2.1/tcp/1"),
+            Err(GateError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            dial(&gate, "/dns4/example.com/tcp/1"),
+            Err(GateError::NotIpAddress(_))
+        ));
+        assert!(dial(&gate, "/ip4/10.0.0.2/tcp/1").is_ok());
+
+        // 没有允许列表时只检查拒绝列表
+        let gate = Gate::new(MockTransport::new()).deny(net("10.0.0.1/32"));
+        assert!(dial(&gate, "/dns4/example.com/tcp/1").is_ok());
+        assert!(dial(&gate, "/ip4/192.0.2.1/tcp/1").is_ok());
+        assert_eq!(gate.pending(), 0);
+    }
+
+    #[test]
+    fn rejects_incoming_connections() {
+        let mut transport = MockTransport::new();
+        transport.incoming = vec![
+            addr("/ip4/10.0.0.1/tcp/1"),
+            addr("/ip4/10.0.0.2/tcp/1"),
+            addr("/memory/1"),
+        ];
+        let gate = Gate::new(transport)
+            .allow(net("10.0.0.0/8"))
+            .deny(net("10.0.0.1/32"));
+        let mut listener = gate.listen(addr("/ip4/0.0.0.0/tcp/1")).unwrap();
+        let mut next = || listener.next().now_or_never().flatten().unwrap();
+        assert!(matches!(next(), ListenerEvent::Error(GateError::Denied(_))));
+        let ListenerEvent::Incoming { upgrade, .. } = next() else {
+            panic!("expected Incoming");
+        };
+        assert_eq!(gate.pending(), 1);
+        assert!(matches!(
+            next(),
+            ListenerEvent::Error(GateError::NotIpAddress(_))
+        ));
+        let (_, _gated) = block_on(upgrade).unwrap();
+        assert_eq!((gate.pending(), gate.established()), (0, 1));
+    }
+
+    #[test]
+    fn pending_limit() {
+        let gate = Gate::new(MockTransport::new()).max_pending_upgrades(1);
+        let dialer = gate.connect(addr("/ip4/10.0.0.1/tcp/1")).unwrap();
+        assert_eq!(gate.pending(), 1);
+        assert!(matches!(
+            dial(&gate, "/ip4/10.0.0.2/tcp/1"),
+            Err(GateError::PendingLimit(1))
+        ));
+        drop(dialer);
+        assert_eq!(gate.pending(), 0);
+        let _gated = dial(&gate, "/ip4/10.0.0.2/tcp/1").unwrap();
+        // 已建立的连接不占用升级名额
+        assert_eq!((gate.pending(), gate.established()), (0, 1));
+        assert!(dial(&gate, "/ip4/10.0.0.3/tcp/1").is_ok());
+    }
+
+    #[test]
+    fn established_limits() {
+        let gate = Gate::new(MockTransport::new()).max_established(2);
+        let first = dial(&gate, "/ip4/10.0.0.1/tcp/1").unwrap();
+        let _second = dial(&gate, "/ip4/10.0.0.2/tcp/1").unwrap();
+        assert!(matches!(
+            dial(&gate, "/ip4/10.0.0.3/tcp/1"),
+            Err(GateError::EstablishedLimit(2))
+        ));
+        drop(first);
+        assert_eq!(gate.established(), 1);
+        assert!(dial(&gate, "/ip4/10.0.0.3/tcp/1").is_ok());
+    }
+
+    #[test]
+    fn per_ip_limit() {
+        let gate = Gate::new(MockTransport::new()).max_established_per_ip(1);
+        let first = dial(&gate, "/ip4/10.0.0.1/tcp/1").unwrap();
+        assert!(matches!(
+            dial(&gate, "/ip4/10.0.0.1/tcp/2"),
+            Err(GateError::PerIpLimit { limit: 1, .. })
+        ));
+        let _other = dial(&gate, "/ip4/10.0.0.2/tcp/1").unwrap();
+        drop(first);
+        assert!(dial(&gate, "/ip4/10.0.0.1/tcp/2").is_ok());
+    }
+
+    #[test]
+    fn per_peer_limit() {
+        let gate = Gate::new(MockTransport::new()).max_established_per_peer(1);
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let first = dial(&gate, &format!("/ip4/10.0.0.1/tcp/1/p2p/{peer_id}")).unwrap();
+        // 对端的 PeerId 在升级完成后才能确定
+        let dialer = gate
+            .connect(addr(&format!("/ip4/10.0.0.2/tcp/1/p2p/{peer_id}")))
+            .unwrap();
+        assert!(matches!(
+            block_on(dialer),
+            Err(GateError::PerPeerLimit { peer_id: p, limit: 1 }) if p == peer_id
+        ));
+        let _other = dial(&gate, "/ip4/10.0.0.2/tcp/1").unwrap();
+        assert_eq!(gate.established(), 2);
+        drop(first);
+        assert!(dial(&gate, &format!("/ip4/10.0.0.2/tcp/1/p2p/{peer_id}")).is_ok());
+    }
+}
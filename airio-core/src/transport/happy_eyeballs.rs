@@ -0,0 +1,577 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{FutureExt, StreamExt, stream::FuturesUnordered};
+use futures_timer::Delay;
+
+use crate::{Multiaddr, PeerId, Transport, TransportError, multiaddr::Protocol};
+
+/// RFC 8305 推荐的 Connection Attempt Delay
+const DEFAULT_STAGGER: Duration = Duration::from_millis(250);
+
+/// 以 RFC 8305（Happy Eyeballs）的方式并发拨号多个候选地址。
+///
+/// 地址按地址族交替排序（默认 IPv6 优先），每隔 [`HappyEyeballs::stagger`] 启动下一个尝试，
+/// 某个尝试失败时立即启动下一个。内部 Transport 的输出需带有对端的 [`PeerId`]，
+/// 即已完成身份认证，第一个通过 PeerId 校验的连接胜出，其余尝试被取消。
+pub struct HappyEyeballs<T> {
+    transport: Arc<T>,
+    stagger: Duration,
+    prefer_ipv6: bool,
+}
+
+impl<T> HappyEyeballs<T> {
+    pub fn new(transport: T) -> Self {
+        HappyEyeballs {
+            transport: Arc::new(transport),
+            stagger: DEFAULT_STAGGER,
+            prefer_ipv6: true,
+        }
+    }
+
+    /// 相邻两次尝试的启动间隔
+    pub fn stagger(mut self, stagger: Duration) -> Self {
+        self.stagger = stagger;
+        self
+    }
+
+    /// 是否优先尝试 IPv6 地址
+    pub fn prefer_ipv6(mut self, prefer: bool) -> Self {
+        self.prefer_ipv6 = prefer;
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+}
+
+impl<T, M> HappyEyeballs<T>
+where
+    T: Transport<Output = (PeerId, M)>,
+{
+    /// 拨号一组地址，以 `/p2p/<peer-id>` 结尾的地址会校验对端的 PeerId
+    pub fn dial<I>(&self, addrs: I) -> Dial<T>
+    where
+        I: IntoIterator<Item = Multiaddr>,
+    {
+        self.dial_inner(None, addrs)
+    }
+
+    /// 拨号指定节点的一组地址，所有连接都必须属于 `peer_id`
+    pub fn dial_peer<I>(&self, peer_id: PeerId, addrs: I) -> Dial<T>
+    where
+        I: IntoIterator<Item = Multiaddr>,
+    {
+        self.dial_inner(Some(peer_id), addrs)
+    }
+
+    fn dial_inner<I>(&self, peer_id: Option<PeerId>, addrs: I) -> Dial<T>
+    where
+        I: IntoIterator<Item = Multiaddr>,
+    {
+        let mut errors = Vec::new();
+        let mut candidates = Vec::new();
+        for addr in addrs {
+            let (dial_addr, addr_peer_id) = split_peer_id(&addr);
+            match (peer_id, addr_peer_id) {
+                (Some(expected), Some(actual)) if expected != actual => {
+                    errors.push((addr, DialAttemptError::PeerIdMismatch { expected, actual }));
+                }
+                (expected, actual) => candidates.push(Candidate {
+                    addr,
+                    dial_addr,
+                    expected: expected.or(actual),
+                }),
+            }
+        }
+        Dial {
+            transport: self.transport.clone(),
+            pending: sort_candidates(candidates, self.prefer_ipv6),
+            attempts: FuturesUnordered::new(),
+            stagger: self.stagger,
+            timer: None,
+            errors,
+        }
+    }
+}
+
+impl<T> fmt::Debug for HappyEyeballs<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HappyEyeballs")
+            .field("stagger", &self.stagger)
+            .field("prefer_ipv6", &self.prefer_ipv6)
+            .finish()
+    }
+}
+
+struct Candidate {
+    /// 调用方给出的原始地址
+    addr: Multiaddr,
+    /// 去掉 `/p2p` 后交给 Transport 的地址
+    dial_addr: Multiaddr,
+    expected: Option<PeerId>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+    Unknown,
+}
+
+impl Family {
+    fn of(addr: &Multiaddr) -> Self {
+        match addr.iter().next() {
+            Some(Protocol::Ip4(_) | Protocol::Dns4(_)) => Family::V4,
+            Some(Protocol::Ip6(_) | Protocol::Dns6(_)) => Family::V6,
+            _ => Family::Unknown,
+        }
+    }
+}
+
+/// 按 RFC 8305 第 4 节交替排列两个地址族，优先的地址族在前，无法判断地址族的地址排在最后
+fn sort_candidates(candidates: Vec<Candidate>, prefer_ipv6: bool) -> VecDeque<Candidate> {
+    let preferred = if prefer_ipv6 { Family::V6 } else { Family::V4 };
+    let mut first = VecDeque::new();
+    let mut second = VecDeque::new();
+    let mut unknown = VecDeque::new();
+    for candidate in candidates {
+        match Family::of(&candidate.dial_addr) {
+            Family::Unknown => unknown.push_back(candidate),
+            family if family == preferred => first.push_back(candidate),
+            _ => second.push_back(candidate),
+        }
+    }
+    let mut sorted = VecDeque::with_capacity(first.len() + second.len() + unknown.len());
+    loop {
+        match (first.pop_front(), second.pop_front()) {
+            (None, None) => break,
+            (a, b) => sorted.extend(a.into_iter().chain(b)),
+        }
+    }
+    sorted.extend(unknown);
+    sorted
+}
+
+fn split_peer_id(addr: &Multiaddr) -> (Multiaddr, Option<PeerId>) {
+    match addr.protocols() {
+        [rest @ .., Protocol::P2p(peer_id)] => (rest.iter().cloned().collect(), Some(*peer_id)),
+        _ => (addr.clone(), None),
+    }
+}
+
+type AttemptOutput<T> = (
+    Multiaddr,
+    Option<PeerId>,
+    Result<<T as Transport>::Output, <T as Transport>::Error>,
+);
+
+struct Attempt<T: Transport> {
+    addr: Option<Multiaddr>,
+    expected: Option<PeerId>,
+    dialer: Pin<Box<T::Dialer>>,
+}
+
+impl<T: Transport> Future for Attempt<T> {
+    type Output = AttemptOutput<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = futures::ready!(self.dialer.as_mut().poll(cx));
+        let addr = self.addr.take().expect("attempt polled after completion");
+        Poll::Ready((addr, self.expected, result))
+    }
+}
+
+/// [`HappyEyeballs::dial`] 返回的 Future，被 drop 时取消所有进行中的尝试
+pub struct Dial<T: Transport> {
+    transport: Arc<T>,
+    pending: VecDeque<Candidate>,
+    attempts: FuturesUnordered<Attempt<T>>,
+    stagger: Duration,
+    timer: Option<Delay>,
+    errors: Vec<(Multiaddr, DialAttemptError<T::Error>)>,
+}
+
+impl<T: Transport> Unpin for Dial<T> {}
+
+impl<T: Transport> Dial<T> {
+    /// 启动下一个候选地址，`connect` 同步失败时继续尝试后面的地址
+    fn start_next(&mut self) {
+        while let Some(candidate) = self.pending.pop_front() {
+            tracing::debug!("Happy eyeballs dialing {}", candidate.addr);
+            match self.transport.connect(candidate.dial_addr) {
+                Ok(dialer) => {
+                    self.attempts.push(Attempt {
+                        addr: Some(candidate.addr),
+                        expected: candidate.expected,
+                        dialer: Box::pin(dialer),
+                    });
+                    self.timer = Some(Delay::new(self.stagger));
+                    return;
+                }
+                Err(e) => self
+                    .errors
+                    .push((candidate.addr, DialAttemptError::Transport(e))),
+            }
+        }
+        self.timer = None;
+    }
+}
+
+impl<T, M> Future for Dial<T>
+where
+    T: Transport<Output = (PeerId, M)>,
+{
+    type Output = Result<(Multiaddr, T::Output), DialError<T::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if this.attempts.is_empty() {
+                if this.pending.is_empty() {
+                    let errors = std::mem::take(&mut this.errors);
+                    if errors.is_empty() {
+                        return Poll::Ready(Err(DialError::NoAddresses));
+                    }
+                    return Poll::Ready(Err(DialError::AllFailed(errors)));
+                }
+                this.start_next();
+                continue;
+            }
+            match this.attempts.poll_next_unpin(cx) {
+                Poll::Ready(Some((addr, expected, Ok((peer_id, output))))) => {
+                    match expected {
+                        Some(expected) if expected != peer_id => {
+                            tracing::debug!("Happy eyeballs dial to {} reached {}", addr, peer_id);
+                            let error = DialAttemptError::PeerIdMismatch {
+                                expected,
+                                actual: peer_id,
+                            };
+                            this.errors.push((addr, error));
+                            this.start_next();
+                        }
+                        _ => {
+                            // 取消其余的尝试
+                            this.attempts.clear();
+                            this.pending.clear();
+                            this.timer = None;
+                            return Poll::Ready(Ok((addr, (peer_id, output))));
+                        }
+                    }
+                }
+                Poll::Ready(Some((addr, _, Err(e)))) => {
+                    tracing::debug!("Happy eyeballs dial to {} failed", addr);
+                    this.errors
+                        .push((addr, DialAttemptError::Transport(TransportError::Other(e))));
+                    this.start_next();
+                }
+                Poll::Ready(None) | Poll::Pending => {
+                    let stagger_elapsed = this
+                        .timer
+                        .as_mut()
+                        .is_some_and(|timer| timer.poll_unpin(cx).is_ready());
+                    if !stagger_elapsed {
+                        return Poll::Pending;
+                    }
+                    this.start_next();
+                }
+            }
+        }
+    }
+}
+
+/// 单个地址的拨号失败原因
+#[derive(Debug, thiserror::Error)]
+pub enum DialAttemptError<E> {
+    #[error(transparent)]
+    Transport(TransportError<E>),
+    #[error("Peer id mismatch: expected {expected}, got {actual}")]
+    PeerIdMismatch { expected: PeerId, actual: PeerId },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DialError<E> {
+    #[error("No addresses to dial")]
+    NoAddresses,
+    /// 所有地址都失败，按失败的先后顺序列出
+    #[error("All {} dial attempts failed", .0.len())]
+    AllFailed(Vec<(Multiaddr, DialAttemptError<E>)>),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io,
+        sync::{
+            Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Instant,
+    };
+
+    use futures::{executor::block_on, future::BoxFuture};
+
+    use super::*;
+    use crate::{identity::Keypair, transport::boxed::BoxedListener};
+
+    #[derive(Clone)]
+    enum Behaviour {
+        Succeed(PeerId),
+        Fail,
+        /// 永不完成，被 drop 时计数
+        Hang(Arc<AtomicUsize>),
+    }
+
+    /// 按地址预设拨号结果的 Transport，未预设的地址返回 `Unsupported`
+    #[derive(Default)]
+    struct MockTransport {
+        behaviours: HashMap<Multiaddr, Behaviour>,
+        started: Mutex<Vec<(Multiaddr, Instant)>>,
+    }
+
+    impl MockTransport {
+        fn with(mut self, addr: &Multiaddr, behaviour: Behaviour) -> Self {
+            self.behaviours.insert(addr.clone(), behaviour);
+            self
+        }
+
+        fn started(&self) -> Vec<Multiaddr> {
+            let started = self.started.lock().unwrap();
+            started.iter().map(|(addr, _)| addr.clone()).collect()
+        }
+    }
+
+    struct Hang(Arc<AtomicUsize>);
+
+    impl Future for Hang {
+        type Output = io::Result<(PeerId, ())>;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+            Poll::Pending
+        }
+    }
+
+    impl Drop for Hang {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Transport for MockTransport {
+        type Output = (PeerId, ());
+        type Error = io::Error;
+        type Dialer = BoxFuture<'static, io::Result<Self::Output>>;
+        type ListenerUpgrade = BoxFuture<'static, io::Result<Self::Output>>;
+        type Listener = BoxedListener<Self::Output>;
+
+        fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+            Err(TransportError::Unsupported(addr))
+        }
+
+        fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+            let Some(behaviour) = self.behaviours.get(&addr).cloned() else {
+                return Err(TransportError::Unsupported(addr));
+            };
+            self.started.lock().unwrap().push((addr, Instant::now()));
+            Ok(match behaviour {
+                Behaviour::Succeed(peer_id) => futures::future::ok((peer_id, ())).boxed(),
+                Behaviour::Fail => {
+                    futures::future::err(io::ErrorKind::ConnectionRefused.into()).boxed()
+                }
+                Behaviour::Hang(dropped) => Hang(dropped).boxed(),
+            })
+        }
+    }
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    fn failed_addrs<E>(result: Result<(Multiaddr, (PeerId, ())), DialError<E>>) -> Vec<String> {
+        let Err(DialError::AllFailed(errors)) = result else {
+            panic!("expected AllFailed");
+        };
+        errors.iter().map(|(addr, _)| addr.to_string()).collect()
+    }
+
+    #[test]
+    fn interleaves_address_families() {
+        let addrs = [
+            addr("/ip4/10.0.0.1/tcp/1"),
+            addr("/ip4/10.0.0.2/tcp/1"),
+            addr("/memory/1"),
+            addr("/ip6/::1/tcp/1"),
+            addr("/ip6/::2/tcp/1"),
+        ];
+        let transport = addrs
+            .iter()
+            .fold(MockTransport::default(), |t, a| t.with(a, Behaviour::Fail));
+        let dialer = HappyEyeballs::new(transport);
+        let failed = failed_addrs(block_on(dialer.dial(addrs.clone())));
+        let expected = [
+            "/ip6/::1/tcp/1",
+            "/ip4/10.0.0.1/tcp/1",
+            "/ip6/::2/tcp/1",
+            "/ip4/10.0.0.2/tcp/1",
+            "/memory/1",
+        ];
+        assert_eq!(failed, expected);
+        let started = dialer.transport().started();
+        assert_eq!(
+            started.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            expected
+        );
+
+        let dialer = HappyEyeballs::new(
+            addrs
+                .iter()
+                .fold(MockTransport::default(), |t, a| t.with(a, Behaviour::Fail)),
+        )
+        .prefer_ipv6(false);
+        let failed = failed_addrs(block_on(dialer.dial(addrs)));
+        assert_eq!(failed[..2], ["/ip4/10.0.0.1/tcp/1", "/ip6/::1/tcp/1"]);
+    }
+
+    #[test]
+    fn staggers_attempts_and_cancels_losers() {
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (v6, v4, late) = (
+            addr("/ip6/::1/tcp/1"),
+            addr("/ip4/10.0.0.1/tcp/1"),
+            addr("/ip4/10.0.0.2/tcp/1"),
+        );
+        let transport = MockTransport::default()
+            .with(&v6, Behaviour::Hang(dropped.clone()))
+            .with(&v4, Behaviour::Succeed(peer_id))
+            .with(&late, Behaviour::Hang(dropped.clone()));
+        let dialer = HappyEyeballs::new(transport);
+
+        let (winner, (connected, ())) =
+            block_on(dialer.dial([v4.clone(), late, v6.clone()])).unwrap();
+        assert_eq!(winner, v4);
+        assert_eq!(connected, peer_id);
+
+        // IPv6 先启动，250ms 后才启动 IPv4，IPv4 成功后不再启动剩余地址
+        let started = dialer.transport().started.lock().unwrap().clone();
+        assert_eq!(started.len(), 2);
+        assert_eq!((&started[0].0, &started[1].0), (&v6, &v4));
+        let stagger = started[1].1 - started[0].1;
+        assert!(stagger >= DEFAULT_STAGGER, "stagger was {stagger:?}");
+        assert!(stagger < DEFAULT_STAGGER * 4, "stagger was {stagger:?}");
+        // 未完成的 IPv6 尝试被取消
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failure_starts_next_attempt_immediately() {
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let (v6, v4) = (addr("/ip6/::1/tcp/1"), addr("/ip4/10.0.0.1/tcp/1"));
+        let transport = MockTransport::default()
+            .with(&v6, Behaviour::Fail)
+            .with(&v4, Behaviour::Succeed(peer_id));
+        let dialer = HappyEyeballs::new(transport).stagger(Duration::from_secs(60));
+        let start = Instant::now();
+        let (winner, _) = block_on(dialer.dial([v4.clone(), v6])).unwrap();
+        assert_eq!(winner, v4);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn rejects_peer_id_mismatch() {
+        let expected = Keypair::generate_ed25519().to_peer_id();
+        let other = Keypair::generate_ed25519().to_peer_id();
+        let reached_other = addr("/ip4/10.0.0.1/tcp/1");
+        let transport = MockTransport::default().with(&reached_other, Behaviour::Succeed(other));
+        let dialer = HappyEyeballs::new(transport);
+
+        // `/p2p` 与期望的 PeerId 不符时不拨号
+        let wrong_p2p = addr(&format!("/ip4/10.0.0.1/tcp/1/p2p/{other}"));
+        let Err(DialError::AllFailed(errors)) =
+            block_on(dialer.dial_peer(expected, [wrong_p2p.clone(), reached_other.clone()]))
+        else {
+            panic!("expected AllFailed");
+        };
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, wrong_p2p);
+        assert!(matches!(
+            errors[0].1,
+            DialAttemptError::PeerIdMismatch { expected: e, actual: a } if e == expected && a == other
+        ));
+        // 连接到的节点与期望不符
+        assert_eq!(errors[1].0, reached_other);
+        assert!(matches!(
+            errors[1].1,
+            DialAttemptError::PeerIdMismatch { actual, .. } if actual == other
+        ));
+        assert_eq!(
+            dialer.transport().started(),
+            std::slice::from_ref(&reached_other)
+        );
+
+        // 地址中的 `/p2p` 作为期望的 PeerId，拨号时去掉
+        let with_p2p = addr(&format!("/ip4/10.0.0.1/tcp/1/p2p/{expected}"));
+        let Err(DialError::AllFailed(errors)) = block_on(dialer.dial([with_p2p.clone()])) else {
+            panic!("expected AllFailed");
+        };
+        assert_eq!(errors[0].0, with_p2p);
+        assert!(matches!(
+            errors[0].1,
+            DialAttemptError::PeerIdMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn reports_every_failure() {
+        let peer_id = Keypair::generate_ed25519().to_peer_id();
+        let (refused, unsupported, mismatch) = (
+            addr("/ip6/::1/tcp/1"),
+            addr("/ip4/10.0.0.9/tcp/1"),
+            addr("/ip4/10.0.0.1/tcp/1"),
+        );
+        let transport = MockTransport::default()
+            .with(&refused, Behaviour::Fail)
+            .with(
+                &mismatch,
+                Behaviour::Succeed(Keypair::generate_ed25519().to_peer_id()),
+            );
+        let dialer = HappyEyeballs::new(transport);
+        let Err(DialError::AllFailed(errors)) = block_on(dialer.dial_peer(
+            peer_id,
+            [unsupported.clone(), refused.clone(), mismatch.clone()],
+        )) else {
+            panic!("expected AllFailed");
+        };
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|(a, e)| *a == unsupported
+            && matches!(
+                e,
+                DialAttemptError::Transport(TransportError::Unsupported(_))
+            )));
+        assert!(errors.iter().any(|(a, e)| *a == refused
+            && matches!(
+                e,
+                DialAttemptError::Transport(TransportError::Other(e))
+                    if e.kind() == io::ErrorKind::ConnectionRefused
+            )));
+        assert!(
+            errors.iter().any(
+                |(a, e)| *a == mismatch && matches!(e, DialAttemptError::PeerIdMismatch { .. })
+            )
+        );
+
+        let empty: [Multiaddr; 0] = [];
+        assert!(matches!(
+            block_on(dialer.dial(empty)),
+            Err(DialError::NoAddresses)
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{FutureExt, Stream, task::AtomicWaker};
+use futures_timer::Delay;
+
+/// 监听器的唯一标识
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ListenerId(u64);
+
+impl ListenerId {
+    /// 分配一个新的、进程内唯一的 ID
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        ListenerId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for ListenerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// [`Transport::Listener`](super::Transport::Listener) 需要实现的 trait，用于获取监听器句柄。
+///
+/// 直接接受连接的监听器为每个 `Incoming` 事件调用 [`ListenerHandle::begin_incoming`]，
+/// 并将得到的 [`UpgradeGuard`] 放入事件中；包装其他监听器的实现直接返回内部监听器的句柄。
+pub trait Listener: Stream {
+    fn handle(&self) -> &ListenerHandle;
+
+    fn id(&self) -> ListenerId {
+        self.handle().id()
+    }
+}
+
+/// 监听器句柄，可克隆后在其他任务中关闭监听器。
+///
+/// 调用 [`ListenerHandle::close`] 后监听器停止接受新连接，等待进行中的升级完成
+/// （最多等待给定的时长），然后产生最终的 `ListenerEvent::Closed(Ok(()))` 并结束。
+/// 超过等待时长仍未完成的升级通过 [`UpgradeGuard`] 中止。
+#[derive(Clone)]
+pub struct ListenerHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    id: ListenerId,
+    closing: AtomicBool,
+    in_flight: AtomicUsize,
+    /// 进行中的入站连接，关闭超时后逐个中止
+    slots: Mutex<Vec<Weak<Slot>>>,
+    /// 关闭时给定的等待时长，及等待进行中升级的截止时间
+    deadline: Mutex<Option<(Duration, Delay)>>,
+    waker: AtomicWaker,
+}
+
+impl ListenerHandle {
+    pub fn new() -> Self {
+        ListenerHandle {
+            shared: Arc::new(Shared {
+                id: ListenerId::next(),
+                closing: AtomicBool::new(false),
+                in_flight: AtomicUsize::new(0),
+                slots: Mutex::new(Vec::new()),
+                deadline: Mutex::new(None),
+                waker: AtomicWaker::new(),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> ListenerId {
+        self.shared.id
+    }
+
+    /// 优雅关闭监听器，最多等待 `grace` 让进行中的升级完成，重复调用无效
+    pub fn close(&self, grace: Duration) {
+        {
+            // 先设置截止时间再标记关闭，避免监听流看到关闭标记时截止时间尚未设置
+            let mut deadline = self
+                .shared
+                .deadline
+                .lock()
+                .expect("listener deadline poisoned");
+            if self.is_closing() {
+                return;
+            }
+            *deadline = Some((grace, Delay::new(grace)));
+            self.shared.closing.store(true, Ordering::Release);
+        }
+        tracing::debug!(
+            "Closing listener {} with grace period {:?}",
+            self.id(),
+            grace
+        );
+        self.shared.waker.wake();
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.shared.closing.load(Ordering::Acquire)
+    }
+
+    /// 关闭时给定的等待时长，未关闭时为 `None`
+    pub fn grace_period(&self) -> Option<Duration> {
+        self.shared
+            .deadline
+            .lock()
+            .expect("listener deadline poisoned")
+            .as_ref()
+            .map(|(grace, _)| *grace)
+    }
+
+    /// 进行中的升级数量
+    pub fn in_flight(&self) -> usize {
+        self.shared.in_flight.load(Ordering::Acquire)
+    }
+
+    /// 为一个新的入站连接计数，返回该连接的 guard。
+    ///
+    /// guard 随 `Incoming` 事件交给上层，多层升级嵌套时各层持有它的克隆，
+    /// 同一连接只计数一次，全部 drop 后计数减一。
+    pub fn begin_incoming(&self) -> UpgradeGuard {
+        self.shared.in_flight.fetch_add(1, Ordering::AcqRel);
+        let slot = Arc::new(Slot {
+            shared: self.shared.clone(),
+            aborted: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        let mut slots = self.lock_slots();
+        slots.retain(|slot| slot.strong_count() > 0);
+        slots.push(Arc::downgrade(&slot));
+        UpgradeGuard { slot }
+    }
+
+    fn lock_slots(&self) -> MutexGuard<'_, Vec<Weak<Slot>>> {
+        self.shared
+            .slots
+            .lock()
+            .expect("listener upgrade slots poisoned")
+    }
+
+    /// 供监听流的实现调用。
+    ///
+    /// 未关闭时返回 `Poll::Pending`，并在 [`ListenerHandle::close`] 或升级完成时唤醒当前任务；
+    /// 关闭后在所有升级完成或超过截止时间时返回 `Poll::Ready(())`。
+    pub fn poll_closed(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.shared.waker.register(cx.waker());
+        if !self.is_closing() {
+            return Poll::Pending;
+        }
+        if self.in_flight() == 0 {
+            return Poll::Ready(());
+        }
+        let mut deadline = self
+            .shared
+            .deadline
+            .lock()
+            .expect("listener deadline poisoned");
+        match deadline.as_mut().map(|(_, delay)| delay.poll_unpin(cx)) {
+            Some(Poll::Ready(())) | None => {
+                tracing::debug!(
+                    "Listener {} closed, aborting {} upgrades still in flight",
+                    self.id(),
+                    self.in_flight()
+                );
+                for slot in self.lock_slots().drain(..) {
+                    if let Some(slot) = slot.upgrade() {
+                        slot.abort();
+                    }
+                }
+                Poll::Ready(())
+            }
+            Some(Poll::Pending) => Poll::Pending,
+        }
+    }
+}
+
+impl Default for ListenerHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ListenerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListenerHandle")
+            .field("id", &self.id())
+            .field("closing", &self.is_closing())
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+/// 入站连接进行中升级的计数 guard，由 [`ListenerHandle::begin_incoming`] 创建。
+///
+/// 持有 guard 的升级 Future 应在 poll 时检查 [`UpgradeGuard::poll_aborted`]，
+/// 监听器关闭超时后以错误结束。
+#[derive(Clone)]
+pub struct UpgradeGuard {
+    slot: Arc<Slot>,
+}
+
+impl UpgradeGuard {
+    /// 监听器关闭超时、升级需要中止时返回 `Poll::Ready(())`，否则在中止时唤醒当前任务
+    pub fn poll_aborted(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.slot.waker.register(cx.waker());
+        if self.is_aborted() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.slot.aborted.load(Ordering::Acquire)
+    }
+}
+
+impl fmt::Debug for UpgradeGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UpgradeGuard")
+            .field(&self.slot.shared.id)
+            .finish()
+    }
+}
+
+/// 一个入站连接的计数，最后一个 guard drop 时计数减一
+struct Slot {
+    shared: Arc<Shared>,
+    aborted: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Slot {
+    fn abort(&self) {
+        self.aborted.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.shared.in_flight.fetch_sub(1, Ordering::AcqRel);
+        self.shared.waker.wake();
+    }
+}
@@ -1,13 +1,15 @@
 use std::{
     marker::PhantomData,
-    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
 };
 
 use futures::{Stream, TryFuture};
 
-use crate::{ConnectedPoint, ListenerEvent, Transport};
+use crate::{
+    ConnectedPoint, ListenerEvent, Multiaddr, Transport, TransportError,
+    transport::{Listener, ListenerHandle},
+};
 
 /// 将 Transport 的输出使用函数映射到另一个类型。
 #[derive(Debug, Copy, Clone)]
@@ -39,8 +41,8 @@ where
     type Dialer = MapFuture<T::Dialer, TMap>;
     type Listener = MapListener<T, TMap>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
-        let dialer = self.transport.connect(addr)?;
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let dialer = self.transport.connect(addr.clone())?;
         let connected_point = ConnectedPoint::Dialer { addr };
         Ok(MapFuture {
             inner: dialer,
@@ -48,7 +50,7 @@ where
         })
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
         let listener = self.transport.listen(addr)?;
         Ok(MapListener {
             inner: listener,
@@ -82,23 +84,27 @@ where
         let event = match Pin::new(&mut this.inner).as_mut().poll_next(cx) {
             Poll::Ready(Some(event)) => match event {
                 ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr),
+                ListenerEvent::AddressAdded(addr) => ListenerEvent::AddressAdded(addr),
+                ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(addr),
                 ListenerEvent::Incoming {
                     local_addr,
                     remote_addr,
                     upgrade,
+                    guard,
                 } => ListenerEvent::Incoming {
-                    local_addr,
-                    remote_addr,
                     upgrade: MapFuture {
                         inner: upgrade,
                         args: Some((
                             this.fun.clone(),
                             ConnectedPoint::Listener {
-                                local_addr,
-                                remote_addr,
+                                local_addr: local_addr.clone(),
+                                remote_addr: remote_addr.clone(),
                             },
                         )),
                     },
+                    local_addr,
+                    remote_addr,
+                    guard,
                 },
                 ListenerEvent::Closed(result) => ListenerEvent::Closed(result),
                 ListenerEvent::Error(err) => ListenerEvent::Error(err),
@@ -107,7 +113,17 @@ where
             Poll::Pending => return Poll::Pending,
         };
 
-        return Poll::Ready(Some(event));
+        Poll::Ready(Some(event))
+    }
+}
+
+impl<D, T, TMap> Listener for MapListener<T, TMap>
+where
+    T: Transport,
+    TMap: FnOnce(T::Output, ConnectedPoint) -> D + Clone,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
     }
 }
 
@@ -1,14 +1,16 @@
 use std::{
     error,
     marker::PhantomData,
-    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
 };
 
 use futures::{Stream, TryFutureExt, future};
 
-use crate::{ListenerEvent, Transport};
+use crate::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    transport::{Listener, ListenerHandle},
+};
 
 /// 将对 Transport的错误类型进行映射。
 #[derive(Debug, Copy, Clone)]
@@ -37,14 +39,15 @@ where
     type Dialer = future::MapErr<T::Dialer, TMap>;
     type Listener = MapErrListener<T, TMap>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
         let map = self.fun.clone();
-        let dialer = self.transport.connect(addr).map_err(map)?;
+        let dialer = self.transport.connect(addr).map_err(|e| e.map(map))?;
         Ok(dialer.map_err(self.fun.clone()))
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
-        let listener = self.transport.listen(addr).map_err(self.fun.clone())?;
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let map = self.fun.clone();
+        let listener = self.transport.listen(addr).map_err(|e| e.map(map))?;
         Ok(MapErrListener {
             inner: listener,
             map: self.fun.clone(),
@@ -81,6 +84,16 @@ where
             Poll::Ready(None) => return Poll::Ready(None),
             Poll::Pending => return Poll::Pending,
         };
-        return Poll::Ready(Some(event));
+        Poll::Ready(Some(event))
+    }
+}
+
+impl<T, TMap, TErr> Listener for MapErrListener<T, TMap>
+where
+    T: Transport,
+    TMap: FnOnce(T::Error) -> TErr + Clone,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
     }
 }
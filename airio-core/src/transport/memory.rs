@@ -0,0 +1,659 @@
+use std::{
+    collections::HashMap,
+    io,
+    num::NonZeroU64,
+    pin::Pin,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use futures::{
+    Sink, Stream,
+    channel::mpsc,
+    future::{self, Ready},
+    ready,
+};
+
+use crate::{
+    ListenerEvent, Multiaddr, Transport, TransportError, Upgrade,
+    multiaddr::Protocol,
+    transport::{Listener, ListenerHandle},
+    utils::RwStreamSink,
+};
+
+/// 投递给监听端的连接，附带拨号端的端口
+type PendingConnection = (Channel, NonZeroU64);
+
+/// 进程内已注册的监听端口
+static HUB: LazyLock<Mutex<HashMap<NonZeroU64, mpsc::Sender<PendingConnection>>>> =
+    LazyLock::new(Default::default);
+
+/// 在 `Channel` 上升级的结果
+type UpgradeResult<U> = Result<<U as Upgrade<Channel>>::Output, <U as Upgrade<Channel>>::Error>;
+
+/// 用于分配端口的计数器
+static NEXT_PORT: AtomicU64 = AtomicU64::new(1);
+
+const CHANNEL_BUFFER_SIZE: usize = 16;
+
+/// 进程内的 Transport，使用 `/memory/<port>` 地址。
+///
+/// 连接由一对 channel 组成的双工管道实现，不会打开任何真实的套接字，
+/// 监听 `/memory/0` 时会自动分配一个空闲端口。
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MemoryTransport;
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        MemoryTransport
+    }
+}
+
+impl Transport for MemoryTransport {
+    type Output = Channel;
+    type Error = MemoryTransportError;
+    type Dialer = DialFuture;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+    type Listener = MemoryListener;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let port = multiaddr_to_port(&addr).ok_or(TransportError::Unsupported(addr))?;
+        let mut hub = HUB.lock().expect("memory hub poisoned");
+        let port = match NonZeroU64::new(port) {
+            Some(port) if hub.contains_key(&port) => {
+                return Err(TransportError::Other(MemoryTransportError::AlreadyInUse));
+            }
+            Some(port) => port,
+            None => loop {
+                let port = allocate_port();
+                if !hub.contains_key(&port) {
+                    break port;
+                }
+            },
+        };
+        let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        hub.insert(port, sender);
+        tracing::trace!("Listening on /memory/{}", port);
+        Ok(MemoryListener {
+            port,
+            addr: Protocol::Memory(port.get()).into(),
+            receiver,
+            handle: ListenerHandle::new(),
+            registered: true,
+            tell_listen_addr: true,
+            closed: false,
+        })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let port = multiaddr_to_port(&addr)
+            .and_then(NonZeroU64::new)
+            .ok_or(TransportError::Unsupported(addr))?;
+        let sender = HUB
+            .lock()
+            .expect("memory hub poisoned")
+            .get(&port)
+            .cloned()
+            .ok_or(TransportError::Other(MemoryTransportError::Unreachable))?;
+        let (local, remote) = Channel::pair();
+        Ok(DialFuture {
+            sender,
+            dial_port: allocate_port(),
+            channel_to_send: Some(remote),
+            channel_to_return: Some(local),
+        })
+    }
+}
+
+fn allocate_port() -> NonZeroU64 {
+    loop {
+        if let Some(port) = NonZeroU64::new(NEXT_PORT.fetch_add(1, Ordering::Relaxed)) {
+            return port;
+        }
+    }
+}
+
+fn multiaddr_to_port(addr: &Multiaddr) -> Option<u64> {
+    match addr.protocols() {
+        [Protocol::Memory(port)] => Some(*port),
+        _ => None,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryTransportError {
+    #[error("No listener on the given memory port")]
+    Unreachable,
+    #[error("Memory port already in use")]
+    AlreadyInUse,
+}
+
+/// 连接到内存端口的 Future
+pub struct DialFuture {
+    sender: mpsc::Sender<PendingConnection>,
+    dial_port: NonZeroU64,
+    channel_to_send: Option<Channel>,
+    channel_to_return: Option<Channel>,
+}
+
+impl Future for DialFuture {
+    type Output = Result<Channel, MemoryTransportError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(self.sender.poll_ready(cx)).map_err(|_| MemoryTransportError::Unreachable)?;
+        let channel = self
+            .channel_to_send
+            .take()
+            .expect("DialFuture polled after completion");
+        let dial_port = self.dial_port;
+        self.sender
+            .start_send((channel, dial_port))
+            .map_err(|_| MemoryTransportError::Unreachable)?;
+        let channel = self
+            .channel_to_return
+            .take()
+            .expect("DialFuture polled after completion");
+        Poll::Ready(Ok(channel))
+    }
+}
+
+/// [`MemoryTransport`] 的监听流
+pub struct MemoryListener {
+    port: NonZeroU64,
+    addr: Multiaddr,
+    receiver: mpsc::Receiver<PendingConnection>,
+    handle: ListenerHandle,
+    /// 端口是否仍登记在 [`HUB`] 中
+    registered: bool,
+    tell_listen_addr: bool,
+    closed: bool,
+}
+
+impl MemoryListener {
+    /// 注销端口，之后的拨号返回 [`MemoryTransportError::Unreachable`]
+    fn unregister(&mut self) {
+        if !self.registered {
+            return;
+        }
+        self.registered = false;
+        self.receiver.close();
+        if let Ok(mut hub) = HUB.lock() {
+            hub.remove(&self.port);
+        }
+    }
+}
+
+impl Stream for MemoryListener {
+    type Item = ListenerEvent<Ready<Result<Channel, MemoryTransportError>>, MemoryTransportError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.tell_listen_addr {
+            self.tell_listen_addr = false;
+            return Poll::Ready(Some(ListenerEvent::Listened(self.addr.clone())));
+        }
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        if self.handle.poll_closed(cx).is_ready() {
+            self.unregister();
+            self.closed = true;
+            return Poll::Ready(Some(ListenerEvent::Closed(Ok(()))));
+        }
+        if self.handle.is_closing() {
+            self.unregister();
+            return Poll::Pending;
+        }
+        match ready!(Pin::new(&mut self.receiver).poll_next(cx)) {
+            Some((channel, dial_port)) => Poll::Ready(Some(ListenerEvent::Incoming {
+                local_addr: self.addr.clone(),
+                remote_addr: Protocol::Memory(dial_port.get()).into(),
+                upgrade: future::ok(channel),
+                guard: self.handle.begin_incoming(),
+            })),
+            None => {
+                self.closed = true;
+                Poll::Ready(Some(ListenerEvent::Closed(Ok(()))))
+            }
+        }
+    }
+}
+
+impl Listener for MemoryListener {
+    fn handle(&self) -> &ListenerHandle {
+        &self.handle
+    }
+}
+
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// 内存连接，基于 [`Chan`] 实现 `AsyncRead` / `AsyncWrite`
+pub type Channel = RwStreamSink<Chan>;
+
+impl Channel {
+    /// 一对直接相连的内存连接，不经过监听与拨号
+    #[doc(hidden)]
+    pub fn pair() -> (Channel, Channel) {
+        let (a_tx, a_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        let (b_tx, b_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+        (
+            RwStreamSink::new(Chan {
+                incoming: a_rx,
+                outgoing: b_tx,
+            }),
+            RwStreamSink::new(Chan {
+                incoming: b_rx,
+                outgoing: a_tx,
+            }),
+        )
+    }
+
+    /// 在一对内存连接上同时进行 `dialer` 的出站升级与 `listener` 的入站升级，
+    /// 返回两端的结果
+    #[doc(hidden)]
+    pub async fn upgrade_pair<U>(
+        dialer: U,
+        listener: U,
+        info: U::Info,
+    ) -> (UpgradeResult<U>, UpgradeResult<U>)
+    where
+        U: Upgrade<Channel>,
+    {
+        let (outbound, inbound) = Channel::pair();
+        future::join(
+            dialer.upgrade_outbound(outbound, info.clone()),
+            listener.upgrade_inbound(inbound, info),
+        )
+        .await
+    }
+}
+
+/// 双工管道的一端，发送与接收 `Vec<u8>` 数据块
+pub struct Chan {
+    incoming: mpsc::Receiver<Vec<u8>>,
+    outgoing: mpsc::Sender<Vec<u8>>,
+}
+
+impl Stream for Chan {
+    type Item = Result<Vec<u8>, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming)
+            .poll_next(cx)
+            .map(|v| v.map(Ok))
+    }
+}
+
+impl Sink<Vec<u8>> for Chan {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.outgoing.poll_ready(cx).map_err(|_| broken_pipe())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        self.outgoing.start_send(item).map_err(|_| broken_pipe())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.outgoing.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn broken_pipe() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "memory channel closed")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{iter, time::Duration};
+
+    use futures::{
+        AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, FutureExt, StreamExt,
+        executor::block_on,
+        future::{self, BoxFuture},
+    };
+
+    use super::*;
+    use crate::{
+        PeerId, StreamMuxer, Upgrade, UpgradeInfo,
+        identity::{Keypair, PublicKey},
+        muxing::{StreamMuxerEvent, StreamMuxerExt},
+        transport::upgrade::TransportUpgradeError,
+    };
+
+    fn listen(transport: &MemoryTransport) -> (MemoryListener, Multiaddr) {
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        let Some(ListenerEvent::Listened(addr)) = block_on(listener.next()) else {
+            panic!("expected Listened");
+        };
+        (listener, addr)
+    }
+
+    #[test]
+    fn listen_reports_address() {
+        let (_listener, addr) = listen(&MemoryTransport::new());
+        assert!(matches!(addr.protocols(), [Protocol::Memory(port)] if *port != 0));
+    }
+
+    #[test]
+    fn dial_and_echo() {
+        let transport = MemoryTransport::new();
+        let (mut listener, addr) = listen(&transport);
+        block_on(async {
+            let mut dialer = transport.connect(addr.clone()).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming {
+                local_addr,
+                remote_addr,
+                upgrade,
+                ..
+            }) = listener.next().await
+            else {
+                panic!("expected Incoming");
+            };
+            assert_eq!(local_addr, addr);
+            assert!(matches!(remote_addr.protocols(), [Protocol::Memory(_)]));
+            let mut listener_side = upgrade.await.unwrap();
+
+            dialer.write_all(b"hello").await.unwrap();
+            dialer.flush().await.unwrap();
+            let mut buf = [0u8; 5];
+            listener_side.read_exact(&mut buf).await.unwrap();
+            listener_side.write_all(&buf).await.unwrap();
+            listener_side.flush().await.unwrap();
+            let mut echo = [0u8; 5];
+            dialer.read_exact(&mut echo).await.unwrap();
+            assert_eq!(&echo, b"hello");
+        });
+    }
+
+    #[test]
+    fn dial_unregistered_port() {
+        let addr = format!("/memory/{}", u64::MAX).parse().unwrap();
+        assert!(matches!(
+            MemoryTransport::new().connect(addr),
+            Err(TransportError::Other(MemoryTransportError::Unreachable))
+        ));
+        assert!(matches!(
+            MemoryTransport::new().connect("/memory/0".parse().unwrap()),
+            Err(TransportError::Unsupported(_))
+        ));
+        assert!(matches!(
+            MemoryTransport::new().connect("/ip4/127.0.0.1/tcp/1".parse().unwrap()),
+            Err(TransportError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn port_in_use_until_listener_dropped() {
+        let transport = MemoryTransport::new();
+        let (listener, addr) = listen(&transport);
+        assert!(matches!(
+            transport.listen(addr.clone()),
+            Err(TransportError::Other(MemoryTransportError::AlreadyInUse))
+        ));
+        drop(listener);
+        assert!(matches!(
+            transport.connect(addr.clone()),
+            Err(TransportError::Other(MemoryTransportError::Unreachable))
+        ));
+        assert!(transport.listen(addr).is_ok());
+    }
+
+    /// 交换公钥的明文身份协商，仅用于测试
+    #[derive(Clone)]
+    pub(crate) struct Plaintext(pub(crate) Keypair);
+
+    impl UpgradeInfo for Plaintext {
+        type Info = &'static str;
+        type InfoIter = iter::Once<Self::Info>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            iter::once("/plaintext/test")
+        }
+    }
+
+    impl Plaintext {
+        async fn handshake<C>(self, mut stream: C) -> io::Result<(PeerId, C)>
+        where
+            C: AsyncRead + AsyncWrite + Unpin,
+        {
+            let local = self.0.public().to_protobuf_encoding();
+            stream.write_all(&[local.len() as u8]).await?;
+            stream.write_all(&local).await?;
+            stream.flush().await?;
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut remote = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut remote).await?;
+            let remote = PublicKey::from_protobuf_encoding(&remote)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok((remote.to_peer_id(), stream))
+        }
+    }
+
+    impl<C> Upgrade<C> for Plaintext
+    where
+        C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        type Output = (PeerId, C);
+        type Error = io::Error;
+        type Future = BoxFuture<'static, io::Result<(PeerId, C)>>;
+
+        fn upgrade_inbound(self, stream: C, _: Self::Info) -> Self::Future {
+            Box::pin(self.handshake(stream))
+        }
+
+        fn upgrade_outbound(self, stream: C, _: Self::Info) -> Self::Future {
+            Box::pin(self.handshake(stream))
+        }
+    }
+
+    /// 只有一个子流的多路复用器：拨号端作为出站、监听端作为进站取出底层连接，仅用于测试
+    #[derive(Clone)]
+    struct SingleStream;
+
+    struct SingleStreamMuxer<C> {
+        stream: Option<C>,
+        outbound: bool,
+    }
+
+    impl UpgradeInfo for SingleStream {
+        type Info = &'static str;
+        type InfoIter = iter::Once<Self::Info>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            iter::once("/single/test")
+        }
+    }
+
+    impl<C> Upgrade<C> for SingleStream {
+        type Output = SingleStreamMuxer<C>;
+        type Error = io::Error;
+        type Future = future::Ready<io::Result<SingleStreamMuxer<C>>>;
+
+        fn upgrade_inbound(self, stream: C, _: Self::Info) -> Self::Future {
+            future::ok(SingleStreamMuxer {
+                stream: Some(stream),
+                outbound: false,
+            })
+        }
+
+        fn upgrade_outbound(self, stream: C, _: Self::Info) -> Self::Future {
+            future::ok(SingleStreamMuxer {
+                stream: Some(stream),
+                outbound: true,
+            })
+        }
+    }
+
+    impl<C> SingleStreamMuxer<C> {
+        fn take(&mut self, outbound: bool) -> Poll<io::Result<C>> {
+            match self.stream.take() {
+                Some(stream) if self.outbound == outbound => Poll::Ready(Ok(stream)),
+                stream => {
+                    self.stream = stream;
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl<C> StreamMuxer for SingleStreamMuxer<C>
+    where
+        C: AsyncRead + AsyncWrite + Unpin,
+    {
+        type Substream = C;
+        type Error = io::Error;
+
+        fn poll_inbound(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<io::Result<Self::Substream>> {
+            self.take(false)
+        }
+
+        fn poll_outbound(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<io::Result<Self::Substream>> {
+            self.take(true)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<StreamMuxerEvent>> {
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn upgrade_authenticate_multiplex() {
+        let (listener_key, dialer_key) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let transport = |key: &Keypair| {
+            MemoryTransport::new()
+                .upgrade()
+                .authenticate(Plaintext(key.clone()))
+                .multiplex(SingleStream)
+        };
+        let listener_transport = transport(&listener_key);
+        let dialer_transport = transport(&dialer_key);
+
+        block_on(async {
+            let mut listener = listener_transport
+                .listen("/memory/0".parse().unwrap())
+                .unwrap();
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            // 拨号端只有一个候选协议时协商是惰性的，首次写入时才发出，因此两端需要同时进行
+            let dial = async {
+                let (peer_id, mut muxer) = dialer_transport.connect(addr).unwrap().await.unwrap();
+                let mut outbound = future::poll_fn(|cx| muxer.poll_outbound_unpin(cx))
+                    .await
+                    .unwrap();
+                outbound.write_all(b"over the muxer").await.unwrap();
+                outbound.close().await.unwrap();
+                // 保持连接直到监听端回复协商结果
+                (peer_id, outbound)
+            };
+            let accept = async {
+                let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                    panic!("expected Incoming");
+                };
+                let (peer_id, mut muxer) = upgrade.await.unwrap();
+                let mut inbound = future::poll_fn(|cx| muxer.poll_inbound_unpin(cx))
+                    .await
+                    .unwrap();
+                let mut buf = Vec::new();
+                inbound.read_to_end(&mut buf).await.unwrap();
+                (peer_id, buf)
+            };
+            let ((listener_peer, _outbound), (dialer_peer, buf)) = future::join(dial, accept).await;
+            assert_eq!(listener_peer, listener_key.to_peer_id());
+            assert_eq!(dialer_peer, dialer_key.to_peer_id());
+            assert_eq!(buf, b"over the muxer");
+        });
+    }
+
+    #[test]
+    fn close_without_in_flight_upgrades() {
+        let (mut listener, addr) = listen(&MemoryTransport::new());
+        listener.handle().close(Duration::from_secs(60));
+        block_on(async {
+            assert!(matches!(
+                listener.next().await,
+                Some(ListenerEvent::Closed(Ok(())))
+            ));
+            assert!(listener.next().await.is_none());
+        });
+        assert!(matches!(
+            MemoryTransport::new().connect(addr),
+            Err(TransportError::Other(MemoryTransportError::Unreachable))
+        ));
+    }
+
+    #[test]
+    fn close_waits_for_in_flight_upgrades() {
+        let transport = MemoryTransport::new();
+        let (mut listener, addr) = listen(&transport);
+        block_on(async {
+            let _dialer = transport.connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { guard, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            assert_eq!(listener.handle().in_flight(), 1);
+            listener.handle().close(Duration::from_secs(60));
+            assert!(listener.next().now_or_never().is_none());
+
+            drop(guard);
+            assert!(matches!(
+                listener.next().await,
+                Some(ListenerEvent::Closed(Ok(())))
+            ));
+            assert!(listener.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn close_aborts_upgrades_after_grace_period() {
+        let transport = MemoryTransport::new()
+            .upgrade()
+            .authenticate(Plaintext(Keypair::generate_ed25519()))
+            .multiplex(SingleStream);
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            // 拨号端不进行协商，入站升级一直进行中
+            let _dialer = MemoryTransport::new().connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { upgrade, guard, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            drop(guard);
+            assert_eq!(listener.handle().in_flight(), 1);
+
+            listener.handle().close(Duration::from_millis(20));
+            assert!(matches!(
+                listener.next().await,
+                Some(ListenerEvent::Closed(Ok(())))
+            ));
+            assert!(matches!(upgrade.await, Err(TransportUpgradeError::Aborted)));
+            assert_eq!(listener.handle().in_flight(), 0);
+        });
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use either::Either;
+use futures::Stream;
+
+use crate::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    either::EitherFuture,
+    transport::{Listener, ListenerHandle},
+};
+
+/// 组合两个 Transport，优先使用第一个，第一个不支持该地址时回退到第二个。
+///
+/// 拨号只使用其中一侧；监听时两侧都接受该地址则同时监听，参见 [`OrListener`]。
+#[derive(Debug, Copy, Clone)]
+pub struct OrTransport<A, B>(A, B);
+
+impl<A, B> OrTransport<A, B> {
+    pub(crate) fn new(a: A, b: B) -> Self {
+        OrTransport(a, b)
+    }
+}
+
+impl<A, B> Transport for OrTransport<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    type Output = futures::future::Either<A::Output, B::Output>;
+    type Error = Either<A::Error, B::Error>;
+    type Dialer = EitherFuture<A::Dialer, B::Dialer>;
+    type ListenerUpgrade = EitherFuture<A::ListenerUpgrade, B::ListenerUpgrade>;
+    type Listener = OrListener<A, B>;
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let addr = match self.0.connect(addr) {
+            Ok(dialer) => return Ok(EitherFuture::Left(dialer)),
+            Err(TransportError::Unsupported(addr)) => addr,
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Left(err)));
+            }
+        };
+        tracing::trace!(
+            "Left transport does not support {}, trying right transport",
+            addr
+        );
+        let dialer = self.1.connect(addr).map_err(|e| e.map(Either::Right))?;
+        Ok(EitherFuture::Right(dialer))
+    }
+
+    /// 两侧都接受该地址时同时监听，监听流合并两侧的事件
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let left = match self.0.listen(addr.clone()) {
+            Ok(listener) => Some(listener),
+            Err(TransportError::Unsupported(_)) => None,
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Left(err)));
+            }
+        };
+        let right = match self.1.listen(addr) {
+            Ok(listener) => listener,
+            Err(TransportError::Unsupported(addr)) => {
+                return left
+                    .map(OrListener::Left)
+                    .ok_or(TransportError::Unsupported(addr));
+            }
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Right(err)));
+            }
+        };
+        Ok(match left {
+            Some(left) => OrListener::Both {
+                left,
+                right,
+                handle: ListenerHandle::new(),
+                left_done: false,
+                right_done: false,
+            },
+            None => OrListener::Right(right),
+        })
+    }
+}
+
+/// [`OrTransport`] 的监听流。
+///
+/// 只有一侧接受监听地址时即该侧的监听流；两侧都接受时合并两侧的事件，
+/// 两侧都结束后才产生最终的 `Closed`，先结束一侧的错误以 `Error` 报告。
+/// 合并时的句柄只用于关闭，关闭时转发给两侧，进行中的升级由两侧各自的句柄计数。
+#[pin_project::pin_project(project = OrListenerProj)]
+pub enum OrListener<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    Left(#[pin] A::Listener),
+    Right(#[pin] B::Listener),
+    Both {
+        #[pin]
+        left: A::Listener,
+        #[pin]
+        right: B::Listener,
+        handle: ListenerHandle,
+        left_done: bool,
+        right_done: bool,
+    },
+}
+
+type OrListenerEvent<A, B> = ListenerEvent<
+    EitherFuture<<A as Transport>::ListenerUpgrade, <B as Transport>::ListenerUpgrade>,
+    Either<<A as Transport>::Error, <B as Transport>::Error>,
+>;
+
+impl<A, B> Stream for OrListener<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    type Item = OrListenerEvent<A, B>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.project() {
+            OrListenerProj::Left(inner) => inner
+                .poll_next(cx)
+                .map(|event| event.map(left_event::<A, B>)),
+            OrListenerProj::Right(inner) => inner
+                .poll_next(cx)
+                .map(|event| event.map(right_event::<A, B>)),
+            OrListenerProj::Both {
+                mut left,
+                mut right,
+                handle,
+                left_done,
+                right_done,
+            } => {
+                if handle.poll_closed(cx).is_ready() {
+                    let grace = handle
+                        .grace_period()
+                        .expect("closed listener has a grace period");
+                    left.handle().close(grace);
+                    right.handle().close(grace);
+                }
+                loop {
+                    if !*left_done && let Poll::Ready(event) = left.as_mut().poll_next(cx) {
+                        match merge(event.map(left_event::<A, B>), left_done, *right_done) {
+                            Some(event) => return Poll::Ready(Some(event)),
+                            None => continue,
+                        }
+                    }
+                    if !*right_done && let Poll::Ready(event) = right.as_mut().poll_next(cx) {
+                        match merge(event.map(right_event::<A, B>), right_done, *left_done) {
+                            Some(event) => return Poll::Ready(Some(event)),
+                            None => continue,
+                        }
+                    }
+                    return if *left_done && *right_done {
+                        Poll::Ready(None)
+                    } else {
+                        Poll::Pending
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn left_event<A, B>(event: ListenerEvent<A::ListenerUpgrade, A::Error>) -> OrListenerEvent<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    event.map_upgrade(EitherFuture::Left).map_err(Either::Left)
+}
+
+fn right_event<A, B>(event: ListenerEvent<B::ListenerUpgrade, B::Error>) -> OrListenerEvent<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    event
+        .map_upgrade(EitherFuture::Right)
+        .map_err(Either::Right)
+}
+
+/// 处理合并时一侧的事件，该侧结束时标记 `done`。
+/// 另一侧仍在监听时，该侧的 `Closed` 不转发，失败改为 `Error`；返回 `None` 表示没有要产生的事件
+fn merge<U, E>(
+    event: Option<ListenerEvent<U, E>>,
+    done: &mut bool,
+    other_done: bool,
+) -> Option<ListenerEvent<U, E>> {
+    match event {
+        None => {
+            *done = true;
+            None
+        }
+        Some(ListenerEvent::Closed(result)) => {
+            *done = true;
+            if other_done {
+                Some(ListenerEvent::Closed(result))
+            } else {
+                result.err().map(ListenerEvent::Error)
+            }
+        }
+        Some(event) => Some(event),
+    }
+}
+
+impl<A, B> Listener for OrListener<A, B>
+where
+    A: Transport,
+    B: Transport,
+{
+    fn handle(&self) -> &ListenerHandle {
+        match self {
+            OrListener::Left(inner) => inner.handle(),
+            OrListener::Right(inner) => inner.handle(),
+            OrListener::Both { handle, .. } => handle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{AsyncReadExt, AsyncWriteExt, StreamExt, executor::block_on, future};
+
+    use super::*;
+    use crate::transport::memory::{
+        DialFuture, MemoryListener, MemoryTransport, MemoryTransportError,
+    };
+
+    /// 不支持任何地址的 Transport
+    struct Unsupported;
+
+    impl Transport for Unsupported {
+        type Output = <MemoryTransport as Transport>::Output;
+        type Error = MemoryTransportError;
+        type Dialer = DialFuture;
+        type ListenerUpgrade = <MemoryTransport as Transport>::ListenerUpgrade;
+        type Listener = MemoryListener;
+
+        fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+            Err(TransportError::Unsupported(addr))
+        }
+
+        fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+            Err(TransportError::Unsupported(addr))
+        }
+    }
+
+    #[test]
+    fn falls_back_to_right() {
+        block_on(async {
+            let transport = Unsupported.or_transport(MemoryTransport::new());
+            let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+            assert!(matches!(listener, OrListener::Right(_)));
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+
+            let dialer = transport.connect(addr).unwrap();
+            assert!(matches!(dialer, EitherFuture::Right(_)));
+            let future::Either::Right(mut dialed) = dialer.await.unwrap() else {
+                panic!("expected right output");
+            };
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            let future::Either::Right(mut accepted) = upgrade.await.unwrap() else {
+                panic!("expected right output");
+            };
+
+            dialed.write_all(b"ping").await.unwrap();
+            dialed.flush().await.unwrap();
+            let mut buf = [0u8; 4];
+            accepted.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn prefers_left() {
+        let transport = MemoryTransport::new().or_transport(Unsupported);
+        let listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        assert!(matches!(listener, OrListener::Left(_)));
+    }
+
+    #[test]
+    fn listens_on_both() {
+        block_on(async {
+            let transport = MemoryTransport::new().or_transport(MemoryTransport::new());
+            let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+            assert!(matches!(listener, OrListener::Both { .. }));
+
+            let mut addrs = Vec::new();
+            for _ in 0..2 {
+                let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                    panic!("expected Listened");
+                };
+                addrs.push(addr);
+            }
+            assert_ne!(addrs[0], addrs[1]);
+
+            // 两侧的入站连接都从合并后的监听流产生
+            let mut sides = Vec::new();
+            for addr in addrs {
+                let dialed = transport.connect(addr).unwrap();
+                let (dialed, event) = future::join(dialed, listener.next()).await;
+                dialed.unwrap();
+                let Some(ListenerEvent::Incoming { upgrade, .. }) = event else {
+                    panic!("expected Incoming");
+                };
+                sides.push(matches!(upgrade.await.unwrap(), future::Either::Left(_)));
+            }
+            sides.sort();
+            assert_eq!(sides, [false, true]);
+
+            listener.handle().close(Duration::from_secs(60));
+            assert!(matches!(
+                listener.next().await,
+                Some(ListenerEvent::Closed(Ok(())))
+            ));
+            assert!(listener.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn merge_reports_first_failure_as_error() {
+        let mut done = false;
+        let closed = ListenerEvent::<(), _>::Closed(Err(MemoryTransportError::Unreachable));
+        assert!(matches!(
+            merge(Some(closed), &mut done, false),
+            Some(ListenerEvent::Error(MemoryTransportError::Unreachable))
+        ));
+        assert!(done);
+
+        let mut done = false;
+        assert!(
+            merge(
+                Some(ListenerEvent::<(), MemoryTransportError>::Closed(Ok(()))),
+                &mut done,
+                false
+            )
+            .is_none()
+        );
+        assert!(matches!(
+            merge(
+                Some(ListenerEvent::<(), MemoryTransportError>::Closed(Ok(()))),
+                &mut done,
+                true
+            ),
+            Some(ListenerEvent::Closed(Ok(())))
+        ));
+    }
+
+    #[test]
+    fn unsupported_by_both() {
+        let transport = Unsupported.or_transport(MemoryTransport::new());
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/80".parse().unwrap();
+        assert!(matches!(
+            transport.connect(addr.clone()),
+            Err(TransportError::Unsupported(a)) if a == addr
+        ));
+        assert!(matches!(
+            transport.listen(addr),
+            Err(TransportError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn left_error_does_not_fall_back() {
+        let transport = MemoryTransport::new().or_transport(MemoryTransport::new());
+        // 左侧支持该地址但拨号失败，不再尝试右侧
+        assert!(matches!(
+            transport.connect(format!("/memory/{}", u64::MAX).parse().unwrap()),
+            Err(TransportError::Other(Either::Left(
+                MemoryTransportError::Unreachable
+            )))
+        ));
+    }
+}
@@ -0,0 +1,795 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{AsyncRead, AsyncWrite, FutureExt, Stream, TryFuture};
+use futures_timer::Delay;
+
+use crate::{
+    ListenerEvent, Multiaddr, PeerId, StreamMuxer, Transport, TransportError,
+    muxing::{StreamMuxerBox, StreamMuxerEvent, SubstreamBox},
+    transport::{Listener, ListenerHandle},
+};
+
+/// 令牌桶的速率与突发上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    bytes_per_sec: u64,
+    burst: u64,
+}
+
+impl Rate {
+    /// 每秒 `bytes_per_sec` 字节，突发上限默认为一秒的量
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1);
+        Rate {
+            bytes_per_sec,
+            burst: bytes_per_sec,
+        }
+    }
+
+    /// 令牌桶的容量，即空闲之后可以一次读写的最大字节数
+    pub fn burst(mut self, burst: u64) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+}
+
+/// 读与写两个方向的限制，`None` 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub read: Option<Rate>,
+    pub write: Option<Rate>,
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn read(mut self, rate: Rate) -> Self {
+        self.read = Some(rate);
+        self
+    }
+
+    pub fn write(mut self, rate: Rate) -> Self {
+        self.write = Some(rate);
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Read,
+    Write,
+}
+
+struct Bucket {
+    rate: Option<Rate>,
+    /// 允许为负：多个连接同时读写时可能透支，之后需等待更久
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Option<Rate>) -> Self {
+        Bucket {
+            rate,
+            tokens: rate.map(|r| r.burst as f64).unwrap_or_default(),
+            updated: Instant::now(),
+        }
+    }
+
+    fn set_rate(&mut self, rate: Option<Rate>) {
+        self.refill(Instant::now());
+        self.tokens = match (self.rate, rate) {
+            (None, Some(rate)) => rate.burst as f64,
+            (_, Some(rate)) => self.tokens.min(rate.burst as f64),
+            (_, None) => 0.0,
+        };
+        self.rate = rate;
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate {
+            let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+            self.tokens =
+                (self.tokens + elapsed * rate.bytes_per_sec as f64).min(rate.burst as f64);
+        }
+        // `now` 在加锁前取得，并发读写时可能早于上次更新的时间
+        self.updated = self.updated.max(now);
+    }
+
+    /// 当前可读写的字节数，令牌不足 `want` 时只接受至少约 10ms 的量，否则返回需要等待的时间
+    fn available(&mut self, now: Instant, want: usize) -> Result<usize, Duration> {
+        let Some(rate) = self.rate else {
+            return Ok(usize::MAX);
+        };
+        self.refill(now);
+        // 令牌耗尽后每次唤醒只攒下几个字节，逐字节读写会带来大量系统调用与定时器，
+        // 因此攒够一小块（或整个请求）再继续
+        let chunk = (rate.bytes_per_sec / 100).clamp(1, rate.burst);
+        let needed = (want as u64).clamp(1, chunk) as f64;
+        if self.tokens >= needed {
+            return Ok(self.tokens as usize);
+        }
+        let missing = needed - self.tokens;
+        Err(Duration::from_secs_f64(missing / rate.bytes_per_sec as f64))
+    }
+
+    fn consume(&mut self, n: usize) {
+        if self.rate.is_some() {
+            self.tokens -= n as f64;
+        }
+    }
+}
+
+struct Buckets {
+    read: Bucket,
+    write: Bucket,
+}
+
+impl Buckets {
+    fn get(&mut self, direction: Direction) -> &mut Bucket {
+        match direction {
+            Direction::Read => &mut self.read,
+            Direction::Write => &mut self.write,
+        }
+    }
+}
+
+/// 一组读写令牌桶，克隆得到的句柄共享同一份额度，限制可以在运行时修改
+#[derive(Clone)]
+pub struct Budget {
+    inner: Arc<Mutex<Buckets>>,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Budget {
+            inner: Arc::new(Mutex::new(Buckets {
+                read: Bucket::new(limits.read),
+                write: Bucket::new(limits.write),
+            })),
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        let buckets = self.lock();
+        Limits {
+            read: buckets.read.rate,
+            write: buckets.write.rate,
+        }
+    }
+
+    pub fn set_limits(&self, limits: Limits) {
+        let mut buckets = self.lock();
+        buckets.read.set_rate(limits.read);
+        buckets.write.set_rate(limits.write);
+    }
+
+    pub fn set_read_rate(&self, rate: Option<Rate>) {
+        self.lock().read.set_rate(rate);
+    }
+
+    pub fn set_write_rate(&self, rate: Option<Rate>) {
+        self.lock().write.set_rate(rate);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.inner.lock().expect("budget poisoned")
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new(Limits::unlimited())
+    }
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Budget").field(&self.limits()).finish()
+    }
+}
+
+/// 限速设置的共享句柄。
+///
+/// 包含所有连接共享的全局额度，以及新连接、新节点使用的默认限制。
+/// [`Throttle`] 为每个连接应用全局额度与单独的连接额度，
+/// [`RateLimiter::limit_muxer`] 为认证之后的子流应用节点额度，同一节点的所有子流共享。
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    inner: Arc<LimiterInner>,
+}
+
+#[derive(Default)]
+struct LimiterInner {
+    global: Budget,
+    connection_limits: Mutex<Limits>,
+    peer_limits: Mutex<Limits>,
+    peers: Mutex<HashMap<PeerId, Weak<Mutex<Buckets>>>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 所有连接共享的额度
+    pub fn global(&self) -> &Budget {
+        &self.inner.global
+    }
+
+    /// 之后建立的连接各自的限制
+    pub fn set_connection_limits(&self, limits: Limits) {
+        *self
+            .inner
+            .connection_limits
+            .lock()
+            .expect("limits poisoned") = limits;
+    }
+
+    /// 之后出现的节点各自的限制，已有节点通过 [`RateLimiter::peer`] 修改
+    pub fn set_peer_limits(&self, limits: Limits) {
+        *self.inner.peer_limits.lock().expect("limits poisoned") = limits;
+    }
+
+    /// 仍有连接存活的节点的额度
+    pub fn peer(&self, peer_id: &PeerId) -> Option<Budget> {
+        let peers = self.inner.peers.lock().expect("limiter poisoned");
+        let inner = peers.get(peer_id)?.upgrade()?;
+        Some(Budget { inner })
+    }
+
+    /// 包装已认证连接的多路复用器，每个子流都受该节点额度的限制
+    pub fn limit_muxer(&self, peer_id: PeerId, muxer: StreamMuxerBox) -> StreamMuxerBox {
+        let budget = self.peer_budget(peer_id);
+        StreamMuxerBox::new(ThrottledMuxer {
+            inner: muxer,
+            budget,
+        })
+    }
+
+    fn peer_budget(&self, peer_id: PeerId) -> Budget {
+        let mut peers = self.inner.peers.lock().expect("limiter poisoned");
+        if let Some(inner) = peers.get(&peer_id).and_then(Weak::upgrade) {
+            return Budget { inner };
+        }
+        peers.retain(|_, budget| budget.strong_count() > 0);
+        let limits = *self.inner.peer_limits.lock().expect("limits poisoned");
+        let budget = Budget::new(limits);
+        peers.insert(peer_id, Arc::downgrade(&budget.inner));
+        budget
+    }
+
+    fn connection_budget(&self) -> Budget {
+        Budget::new(
+            *self
+                .inner
+                .connection_limits
+                .lock()
+                .expect("limits poisoned"),
+        )
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("global", &self.inner.global)
+            .finish()
+    }
+}
+
+/// 为每个连接限速的 Transport 包装，输出 [`Throttled`]
+#[derive(Debug, Clone)]
+pub struct Throttle<T> {
+    inner: T,
+    limiter: RateLimiter,
+}
+
+impl<T> Throttle<T> {
+    pub fn new(inner: T, limiter: RateLimiter) -> Self {
+        Throttle { inner, limiter }
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+impl<T> Transport for Throttle<T>
+where
+    T: Transport,
+    T::Output: AsyncRead + AsyncWrite,
+{
+    type Output = Throttled<T::Output>;
+    type Error = T::Error;
+    type Dialer = ThrottleFuture<T::Dialer>;
+    type ListenerUpgrade = ThrottleFuture<T::ListenerUpgrade>;
+    type Listener = ThrottleListener<T::Listener>;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        Ok(ThrottleListener {
+            inner: self.inner.listen(addr)?,
+            limiter: self.limiter.clone(),
+        })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        Ok(ThrottleFuture {
+            inner: self.inner.connect(addr)?,
+            limiter: self.limiter.clone(),
+        })
+    }
+}
+
+#[pin_project::pin_project]
+pub struct ThrottleListener<L> {
+    #[pin]
+    inner: L,
+    limiter: RateLimiter,
+}
+
+impl<L, U, E> Stream for ThrottleListener<L>
+where
+    L: Stream<Item = ListenerEvent<U, E>>,
+{
+    type Item = ListenerEvent<ThrottleFuture<U>, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let limiter = this.limiter.clone();
+        this.inner.poll_next(cx).map(|event| {
+            event.map(|event| event.map_upgrade(|inner| ThrottleFuture { inner, limiter }))
+        })
+    }
+}
+
+impl<L, U, E> Listener for ThrottleListener<L>
+where
+    L: Listener<Item = ListenerEvent<U, E>>,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+#[pin_project::pin_project]
+pub struct ThrottleFuture<F> {
+    #[pin]
+    inner: F,
+    limiter: RateLimiter,
+}
+
+impl<F> Future for ThrottleFuture<F>
+where
+    F: TryFuture,
+{
+    type Output = Result<Throttled<F::Ok>, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let stream = futures::ready!(this.inner.try_poll(cx))?;
+        let connection = this.limiter.connection_budget();
+        let budgets = vec![this.limiter.global().clone(), connection];
+        Poll::Ready(Ok(Throttled::new(stream, budgets)))
+    }
+}
+
+/// 按令牌桶限制读写速率的流
+#[pin_project::pin_project]
+pub struct Throttled<S> {
+    #[pin]
+    inner: S,
+    /// 依次检查的额度，最后一个为该流自己的额度
+    budgets: Vec<Budget>,
+    read_timer: Option<Delay>,
+    write_timer: Option<Delay>,
+}
+
+impl<S> Throttled<S> {
+    fn new(inner: S, budgets: Vec<Budget>) -> Self {
+        Throttled {
+            inner,
+            budgets,
+            read_timer: None,
+            write_timer: None,
+        }
+    }
+
+    /// 该连接自己的额度，可在运行时修改
+    pub fn budget(&self) -> &Budget {
+        self.budgets.last().expect("at least one budget")
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Throttled<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Throttled")
+            .field("inner", &self.inner)
+            .field("budget", self.budget())
+            .finish()
+    }
+}
+
+/// 等到所有额度都有令牌，返回本次最多可读写的字节数
+fn poll_budgets(
+    budgets: &[Budget],
+    direction: Direction,
+    timer: &mut Option<Delay>,
+    cx: &mut Context<'_>,
+    want: usize,
+) -> Poll<usize> {
+    loop {
+        if let Some(delay) = timer.as_mut() {
+            futures::ready!(delay.poll_unpin(cx));
+            *timer = None;
+        }
+        let now = Instant::now();
+        let mut allowed = want;
+        let mut wait = Duration::ZERO;
+        for budget in budgets {
+            match budget.lock().get(direction).available(now, want) {
+                Ok(n) => allowed = allowed.min(n),
+                Err(d) => wait = wait.max(d),
+            }
+        }
+        if wait.is_zero() {
+            return Poll::Ready(allowed);
+        }
+        *timer = Some(Delay::new(wait));
+    }
+}
+
+fn consume(budgets: &[Budget], direction: Direction, n: usize) {
+    for budget in budgets {
+        budget.lock().get(direction).consume(n);
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for Throttled<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return self.project().inner.poll_read(cx, buf);
+        }
+        let this = self.project();
+        let allowed = futures::ready!(poll_budgets(
+            this.budgets,
+            Direction::Read,
+            this.read_timer,
+            cx,
+            buf.len()
+        ));
+        let n = futures::ready!(this.inner.poll_read(cx, &mut buf[..allowed]))?;
+        consume(this.budgets, Direction::Read, n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for Throttled<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return self.project().inner.poll_write(cx, buf);
+        }
+        let this = self.project();
+        let allowed = futures::ready!(poll_budgets(
+            this.budgets,
+            Direction::Write,
+            this.write_timer,
+            cx,
+            buf.len()
+        ));
+        let n = futures::ready!(this.inner.poll_write(cx, &buf[..allowed]))?;
+        consume(this.budgets, Direction::Write, n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+/// 子流共享同一个节点额度的多路复用器
+struct ThrottledMuxer {
+    inner: StreamMuxerBox,
+    budget: Budget,
+}
+
+impl ThrottledMuxer {
+    fn throttle(&self, substream: SubstreamBox) -> SubstreamBox {
+        SubstreamBox::new(Throttled::new(substream, vec![self.budget.clone()]))
+    }
+}
+
+impl StreamMuxer for ThrottledMuxer {
+    type Substream = SubstreamBox;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let substream = futures::ready!(Pin::new(&mut this.inner).poll_inbound(cx))?;
+        Poll::Ready(Ok(this.throttle(substream)))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.get_mut();
+        let substream = futures::ready!(Pin::new(&mut this.inner).poll_outbound(cx))?;
+        Poll::Ready(Ok(this.throttle(substream)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{AsyncWriteExt, FutureExt, StreamExt, executor::block_on, future, io::Cursor};
+
+    use super::*;
+    use crate::{
+        identity::Keypair,
+        muxing::StreamMuxerExt,
+        transport::memory::{Channel, MemoryTransport},
+    };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn bucket(rate: Rate) -> (Bucket, Instant) {
+        let bucket = Bucket::new(Some(rate));
+        let start = bucket.updated;
+        (bucket, start)
+    }
+
+    /// 不等待地写入，额度不足时返回 `None`
+    fn try_write<S: AsyncWrite + Unpin>(stream: &mut S, len: usize) -> Option<usize> {
+        stream
+            .write(&vec![0; len])
+            .now_or_never()
+            .map(Result::unwrap)
+    }
+
+    #[test]
+    fn refills_up_to_burst() {
+        let (mut bucket, start) = bucket(Rate::new(1000).burst(500));
+        assert_eq!(bucket.available(start, 1), Ok(500));
+        bucket.consume(500);
+        assert_eq!(bucket.available(start + ms(100), 1), Ok(100));
+        assert_eq!(bucket.available(start + ms(300), 1), Ok(300));
+        // 空闲再久也不超过突发上限
+        assert_eq!(
+            bucket.available(start + Duration::from_secs(10), 1),
+            Ok(500)
+        );
+    }
+
+    #[test]
+    fn stale_instant_is_not_credited_twice() {
+        let (mut bucket, start) = bucket(Rate::new(1000));
+        bucket.consume(1000);
+        assert_eq!(bucket.available(start + ms(100), 1), Ok(100));
+        // 加锁前取得的较早时间点不改变已累计的时间
+        assert_eq!(bucket.available(start + ms(50), 1), Ok(100));
+        assert_eq!(bucket.available(start + ms(100), 1), Ok(100));
+        assert_eq!(bucket.available(start + ms(200), 1), Ok(200));
+    }
+
+    #[test]
+    fn waits_for_missing_tokens() {
+        let (mut bucket, start) = bucket(Rate::new(1000).burst(100));
+        bucket.consume(100);
+        assert_eq!(bucket.available(start, 1), Err(ms(1)));
+        // 透支之后需要补齐欠下的令牌
+        bucket.consume(499);
+        assert_eq!(bucket.available(start, 1), Err(ms(500)));
+        assert_eq!(bucket.available(start + ms(500), 1), Ok(1));
+    }
+
+    #[test]
+    fn waits_for_a_chunk() {
+        // 一块不超过突发上限
+        let (mut small, start) = bucket(Rate::new(1000).burst(4));
+        small.consume(4);
+        assert_eq!(small.available(start, 100), Err(ms(4)));
+
+        // 每秒 1000 字节，一块为 10 字节
+        let (mut bucket, start) = bucket(Rate::new(1000));
+        bucket.consume(1000);
+        assert_eq!(bucket.available(start + ms(1), 100), Err(ms(9)));
+        assert_eq!(bucket.available(start + ms(10), 100), Ok(10));
+        // 请求小于一块时只等待请求的量
+        bucket.consume(10);
+        assert_eq!(bucket.available(start + ms(12), 3), Err(ms(1)));
+        assert_eq!(bucket.available(start + ms(13), 3), Ok(3));
+    }
+
+    #[test]
+    fn changes_rate_at_runtime() {
+        let (mut bucket, start) = bucket(Rate::new(1000));
+        bucket.consume(200);
+        bucket.set_rate(Some(Rate::new(100)));
+        assert_eq!(bucket.tokens.round(), 100.0);
+        bucket.set_rate(None);
+        assert_eq!(bucket.available(start, 1), Ok(usize::MAX));
+        bucket.consume(1 << 20);
+        bucket.set_rate(Some(Rate::new(10)));
+        assert_eq!(bucket.tokens, 10.0);
+    }
+
+    #[test]
+    fn write_waits_for_budget() {
+        let budget = Budget::new(Limits::unlimited().write(Rate::new(1000).burst(100)));
+        let mut stream = Throttled::new(Cursor::new(Vec::new()), vec![budget]);
+        let start = Instant::now();
+        block_on(stream.write_all(&[0; 150])).unwrap();
+        assert!(start.elapsed() >= ms(50), "{:?}", start.elapsed());
+        assert_eq!(stream.get_ref().get_ref().len(), 150);
+    }
+
+    #[test]
+    fn global_budget_is_shared() {
+        let limiter = RateLimiter::new();
+        limiter
+            .global()
+            .set_limits(Limits::unlimited().write(Rate::new(1).burst(100)));
+        let budgets = || vec![limiter.global().clone(), Budget::default()];
+        let mut first = Throttled::new(Cursor::new(Vec::new()), budgets());
+        let mut second = Throttled::new(Cursor::new(Vec::new()), budgets());
+        assert_eq!(try_write(&mut first, 80), Some(80));
+        assert_eq!(try_write(&mut second, 80), Some(20));
+        assert_eq!(try_write(&mut first, 80), None);
+        // 读不受写额度的限制
+        assert_eq!(limiter.global().limits().read, None);
+    }
+
+    fn connect(transport: &Throttle<MemoryTransport>) -> (Throttled<Channel>, Channel) {
+        let mut listener = MemoryTransport::new()
+            .listen("/memory/0".parse().unwrap())
+            .unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            let dialer = transport.connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            (dialer, upgrade.await.unwrap())
+        })
+    }
+
+    #[test]
+    fn connection_budgets_are_separate() {
+        let limiter = RateLimiter::new();
+        let slow = Limits::unlimited().write(Rate::new(1).burst(100));
+        limiter.set_connection_limits(slow);
+        let transport = Throttle::new(MemoryTransport::new(), limiter.clone());
+        let (mut first, _first_remote) = connect(&transport);
+        let (mut second, _second_remote) = connect(&transport);
+        assert_eq!(first.budget().limits(), slow);
+        assert_eq!(try_write(&mut first, 150), Some(100));
+        assert_eq!(try_write(&mut second, 150), Some(100));
+
+        // 修改单个连接的限制不影响其他连接
+        first.budget().set_write_rate(None);
+        assert_eq!(try_write(&mut first, 150), Some(150));
+        assert_eq!(try_write(&mut second, 150), None);
+    }
+
+    /// 只打开一个出站子流的多路复用器
+    struct OneOutbound<C>(Option<C>);
+
+    impl<C> StreamMuxer for OneOutbound<C>
+    where
+        C: AsyncRead + AsyncWrite + Unpin,
+    {
+        type Substream = C;
+        type Error = io::Error;
+
+        fn poll_inbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Pending
+        }
+
+        fn poll_outbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            match self.get_mut().0.take() {
+                Some(stream) => Poll::Ready(Ok(stream)),
+                None => Poll::Pending,
+            }
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+            Poll::Pending
+        }
+    }
+
+    fn open_substream(limiter: &RateLimiter, peer_id: PeerId) -> (StreamMuxerBox, SubstreamBox) {
+        let muxer = StreamMuxerBox::new(OneOutbound(Some(Cursor::new(Vec::new()))));
+        let mut muxer = limiter.limit_muxer(peer_id, muxer);
+        let substream = block_on(future::poll_fn(|cx| muxer.poll_outbound_unpin(cx))).unwrap();
+        (muxer, substream)
+    }
+
+    #[test]
+    fn peer_budget_is_shared_across_connections() {
+        let limiter = RateLimiter::new();
+        limiter.set_peer_limits(Limits::unlimited().write(Rate::new(1).burst(100)));
+        let (peer_id, other) = (
+            Keypair::generate_ed25519().to_peer_id(),
+            Keypair::generate_ed25519().to_peer_id(),
+        );
+        let (first_muxer, mut first) = open_substream(&limiter, peer_id);
+        let (second_muxer, mut second) = open_substream(&limiter, peer_id);
+        let (_other_muxer, mut third) = open_substream(&limiter, other);
+        assert_eq!(try_write(&mut first, 80), Some(80));
+        assert_eq!(try_write(&mut second, 80), Some(20));
+        assert_eq!(try_write(&mut third, 80), Some(80));
+
+        // 修改节点额度影响该节点的所有子流
+        limiter.peer(&peer_id).unwrap().set_write_rate(None);
+        assert_eq!(try_write(&mut second, 1000), Some(1000));
+
+        // 该节点的连接与子流都关闭后额度被释放
+        drop((first_muxer, second_muxer, first, second));
+        assert!(limiter.peer(&peer_id).is_none());
+        assert!(limiter.peer(&other).is_some());
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{FutureExt, Stream, TryFuture};
+use futures_timer::Delay;
+
+use crate::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    transport::{Listener, ListenerHandle, upgrade::TransportUpgradeError},
+};
+
+/// 超时为 [`TransportUpgradeError::Timeout`]，内部 Transport 的错误为 `Transport`
+type TimeoutError<E> = TransportUpgradeError<E, Infallible>;
+
+/// 为 Transport 的连接建立（包括拨号与入站升级）设置超时。
+#[derive(Debug, Copy, Clone)]
+pub struct Timeout<T> {
+    inner: T,
+    outgoing_timeout: Duration,
+    incoming_timeout: Duration,
+}
+
+impl<T> Timeout<T> {
+    pub(crate) fn new(inner: T, timeout: Duration) -> Self {
+        Timeout {
+            inner,
+            outgoing_timeout: timeout,
+            incoming_timeout: timeout,
+        }
+    }
+
+    /// 单独设置拨号的超时时间
+    pub fn outgoing_timeout(mut self, timeout: Duration) -> Self {
+        self.outgoing_timeout = timeout;
+        self
+    }
+
+    /// 单独设置入站连接升级的超时时间
+    pub fn incoming_timeout(mut self, timeout: Duration) -> Self {
+        self.incoming_timeout = timeout;
+        self
+    }
+}
+
+impl<T> Transport for Timeout<T>
+where
+    T: Transport,
+    T::Error: 'static,
+{
+    type Output = T::Output;
+    type Error = TimeoutError<T::Error>;
+    type Dialer = TimeoutFuture<T::Dialer>;
+    type ListenerUpgrade = TimeoutFuture<T::ListenerUpgrade>;
+    type Listener = TimeoutListener<T::Listener>;
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let dialer = self
+            .inner
+            .connect(addr)
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
+        Ok(TimeoutFuture::new(dialer, self.outgoing_timeout))
+    }
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .inner
+            .listen(addr)
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
+        Ok(TimeoutListener {
+            inner: listener,
+            timeout: self.incoming_timeout,
+        })
+    }
+}
+
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub struct TimeoutListener<L> {
+    #[pin]
+    inner: L,
+    timeout: Duration,
+}
+
+impl<L, U, E> Stream for TimeoutListener<L>
+where
+    L: Stream<Item = ListenerEvent<U, E>>,
+{
+    type Item = ListenerEvent<TimeoutFuture<U>, TimeoutError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let timeout = *this.timeout;
+        this.inner.poll_next(cx).map(|event| {
+            event.map(|event| {
+                event
+                    .map_upgrade(|upgrade| TimeoutFuture::new(upgrade, timeout))
+                    .map_err(TransportUpgradeError::Transport)
+            })
+        })
+    }
+}
+
+impl<L, U, E> Listener for TimeoutListener<L>
+where
+    L: Listener<Item = ListenerEvent<U, E>>,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+/// 在超时前完成内部 Future，否则返回 [`TransportUpgradeError::Timeout`]
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub struct TimeoutFuture<F> {
+    #[pin]
+    inner: F,
+    timer: Delay,
+}
+
+impl<F> TimeoutFuture<F> {
+    fn new(inner: F, timeout: Duration) -> Self {
+        TimeoutFuture {
+            inner,
+            timer: Delay::new(timeout),
+        }
+    }
+}
+
+impl<F> Future for TimeoutFuture<F>
+where
+    F: TryFuture,
+{
+    type Output = Result<F::Ok, TimeoutError<F::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.inner.try_poll(cx) {
+            return Poll::Ready(output.map_err(TransportUpgradeError::Transport));
+        }
+        match this.timer.poll_unpin(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(TransportUpgradeError::Timeout)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use futures::{StreamExt, executor::block_on, future};
+
+    use super::*;
+    use crate::transport::memory::{Channel, MemoryTransport};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn times_out_stalled_dial_and_upgrade() {
+        // 底层连接建立后一直不完成的 Transport
+        let transport = MemoryTransport::new()
+            .and_then(|_, _| future::pending::<io::Result<Channel>>())
+            .timeout(TIMEOUT);
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            let dialed = transport.connect(addr).unwrap().await;
+            assert!(matches!(dialed, Err(TransportUpgradeError::Timeout)));
+
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            assert!(matches!(upgrade.await, Err(TransportUpgradeError::Timeout)));
+        });
+    }
+
+    #[test]
+    fn completes_before_timeout() {
+        let transport = MemoryTransport::new()
+            .timeout(Duration::from_secs(60))
+            .outgoing_timeout(TIMEOUT);
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            transport.connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            upgrade.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn reports_inner_errors() {
+        let transport = MemoryTransport::new().timeout(TIMEOUT);
+        let addr = format!("/memory/{}", u64::MAX).parse().unwrap();
+        assert!(matches!(
+            transport.connect(addr),
+            Err(TransportError::Other(TransportUpgradeError::Transport(_)))
+        ));
+    }
+}
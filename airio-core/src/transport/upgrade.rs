@@ -1,24 +1,34 @@
 use std::{
     error,
     marker::PhantomData,
-    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use airio_stream_select::Negotiated;
-use futures::{AsyncRead, AsyncWrite, Stream, TryFuture, future, ready};
+use futures::{AsyncRead, AsyncWrite, FutureExt, Stream, TryFuture, future, ready};
+use futures_timer::Delay;
 
 use crate::{
-    ConnectedPoint, Endpoint, ListenerEvent, PeerId, StreamMuxer, Transport, Upgrade,
+    Endpoint, ListenerEvent, Multiaddr, PeerId, StreamMuxer, Transport, TransportError, Upgrade,
     muxing::StreamMuxerBox,
-    transport::{Boxed, and_then::AndThen, boxed::boxed},
+    transport::{Boxed, Listener, ListenerHandle, UpgradeGuard, boxed::boxed},
     upgrade::{UpgradeApply, UpgradeError},
 };
 
+/// 各升级阶段的超时时间，`None` 表示不限时
+#[derive(Debug, Copy, Clone, Default)]
+struct Timeouts {
+    authenticate: Option<Duration>,
+    apply: Option<Duration>,
+    multiplex: Option<Duration>,
+}
+
 #[derive(Clone)]
 pub struct Builder<T> {
     inner: T,
+    timeouts: Timeouts,
 }
 
 impl<T> Builder<T>
@@ -27,18 +37,22 @@ where
     T::Error: 'static,
 {
     pub fn new(inner: T) -> Builder<T> {
-        Builder { inner }
+        Builder {
+            inner,
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    /// 身份协商的超时时间，超时返回 [`TransportUpgradeError::AuthenticateTimeout`]
+    pub fn authenticate_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.authenticate = Some(timeout);
+        self
     }
 
     /// 使用一个 [`Upgrade`] 对 [`Transport::Output`] 进行身份协商
     /// * I/O upgrade: `C -> (PeerId, D)`.
     /// * 转化 Transport output: `C -> (PeerId, D)`
-    pub fn authenticate<C, D, U, E>(
-        self,
-        upgrade: U,
-    ) -> AuthenticatedBuilder<
-        AndThen<T, impl FnOnce(C, ConnectedPoint) -> Authenticate<C, U> + Clone>,
-    >
+    pub fn authenticate<C, D, U, E>(self, upgrade: U) -> AuthenticatedBuilder<Authenticated<T, U>>
     where
         T: Transport<Output = C>,
         C: AsyncRead + AsyncWrite + Unpin,
@@ -46,15 +60,14 @@ where
         U: Upgrade<Negotiated<C>, Output = (PeerId, D), Error = E> + Clone,
         E: error::Error + 'static,
     {
-        AuthenticatedBuilder(Builder::new(self.inner.and_then(move |io, endpoint| {
-            let inner = if endpoint.is_dialer() {
-                UpgradeApply::new_outbound(io, upgrade)
-            } else {
-                UpgradeApply::new_inbound(io, upgrade)
-            };
-
-            Authenticate { inner }
-        })))
+        AuthenticatedBuilder(Builder {
+            inner: Authenticated {
+                inner: self.inner,
+                upgrade,
+                timeout: self.timeouts.authenticate,
+            },
+            timeouts: self.timeouts,
+        })
     }
 }
 
@@ -67,6 +80,19 @@ where
     T: Transport,
     T::Error: 'static,
 {
+    /// 之后每次 [`AuthenticatedBuilder::apply`] 的超时时间，
+    /// 超时返回 [`TransportUpgradeError::ApplyTimeout`]
+    pub fn apply_timeout(mut self, timeout: Duration) -> Self {
+        self.0.timeouts.apply = Some(timeout);
+        self
+    }
+
+    /// 多路复用协商的超时时间，超时返回 [`TransportUpgradeError::MultiplexTimeout`]
+    pub fn multiplex_timeout(mut self, timeout: Duration) -> Self {
+        self.0.timeouts.multiplex = Some(timeout);
+        self
+    }
+
     /// 在一个 [`Transport`] 身份协商后的流应用一个 [`Upgrade`]。
     /// 这将返回一个新的 [`AuthenticatedBuilder`]，其中包含了升级后的流
     /// 使用 [`Upgrade`] 作用 -> `(PeerId, C) -> (PeerId, D)`.
@@ -78,17 +104,18 @@ where
         U: Upgrade<Negotiated<C>, Output = D, Error = E> + Clone,
         E: error::Error + 'static,
     {
-        AuthenticatedBuilder(Builder::new(WithUpgrade::new(self.0.inner, upgrade)))
+        let Builder { inner, timeouts } = self.0;
+        AuthenticatedBuilder(Builder {
+            inner: WithUpgrade::with_stage(inner, upgrade, Stage::Apply, timeouts.apply),
+            timeouts,
+        })
     }
 
     /// 在一个 [`Transport`] 身份协商后的流应用一个多路复用 [`Upgrade`]。
     /// 实现了一个多路复用的连接升级。
     /// 使用 [`Upgrade`] 作用 -> `(PeerId, C) -> (PeerId, M)`.
     /// M 必须实现了 [`StreamMuxer`]
-    pub fn multiplex<C, M, U, E>(
-        self,
-        upgrade: U,
-    ) -> Multiplexed<AndThen<T, impl FnOnce((PeerId, C), ConnectedPoint) -> Multiplex<C, U> + Clone>>
+    pub fn multiplex<C, M, U, E>(self, upgrade: U) -> Multiplexed<WithUpgrade<T, U>>
     where
         T: Transport<Output = (PeerId, C)>,
         M: StreamMuxer,
@@ -96,42 +123,235 @@ where
         U: Upgrade<Negotiated<C>, Output = M, Error = E> + Clone,
         E: error::Error + 'static,
     {
-        Multiplexed(self.0.inner.and_then(move |(id, io), endpoint| {
-            let upgrade = if endpoint.is_dialer() {
-                UpgradeApply::new_outbound(io, upgrade)
-            } else {
-                UpgradeApply::new_inbound(io, upgrade)
-            };
-            Multiplex {
-                peer_id: Some(id),
-                upgrade,
-            }
-        }))
+        let Builder { inner, timeouts } = self.0;
+        Multiplexed(WithUpgrade::with_stage(
+            inner,
+            upgrade,
+            Stage::Multiplex,
+            timeouts.multiplex,
+        ))
+    }
+}
+
+/// 升级所处的阶段，用于区分超时错误
+#[derive(Debug, Copy, Clone)]
+enum Stage {
+    Apply,
+    Multiplex,
+}
+
+impl Stage {
+    fn timeout_error<TE, UE>(self) -> TransportUpgradeError<TE, UE> {
+        match self {
+            Stage::Apply => TransportUpgradeError::ApplyTimeout,
+            Stage::Multiplex => TransportUpgradeError::MultiplexTimeout,
+        }
     }
 }
 
-/// 身份认证 Future
-/// 内部为 [`UpgradeApply`] 结构体
+/// 超时后返回 `true`，未设置超时时总是返回 `false`
+fn poll_timeout(delay: &mut Option<Delay>, cx: &mut Context<'_>) -> bool {
+    delay
+        .as_mut()
+        .is_some_and(|delay| delay.poll_unpin(cx).is_ready())
+}
+
+/// 监听器关闭超时、入站升级需要中止时返回 `true`
+fn poll_aborted(guard: &Option<UpgradeGuard>, cx: &mut Context<'_>) -> bool {
+    guard
+        .as_ref()
+        .is_some_and(|guard| guard.poll_aborted(cx).is_ready())
+}
+
+/// 对 Transport 的输出进行身份协商: `C -> (PeerId, D)`
+#[derive(Debug, Copy, Clone)]
 #[pin_project::pin_project]
-pub struct Authenticate<C, U>
+pub struct Authenticated<T, U> {
+    #[pin]
+    inner: T,
+    upgrade: U,
+    timeout: Option<Duration>,
+}
+
+impl<T, C, D, U, E> Transport for Authenticated<T, U>
 where
+    T: Transport<Output = C>,
+    T::Error: 'static,
     C: AsyncRead + AsyncWrite + Unpin,
-    U: Upgrade<Negotiated<C>>,
+    U: Upgrade<Negotiated<C>, Output = (PeerId, D), Error = E> + Clone,
+    E: error::Error + 'static,
+{
+    type Output = (PeerId, D);
+    type Error = TransportUpgradeError<T::Error, E>;
+    type Dialer = AuthenticateFuture<T::Dialer, U, C>;
+    type ListenerUpgrade = AuthenticateFuture<T::ListenerUpgrade, U, C>;
+    type Listener = AuthenticateListener<T, U>;
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let fut = self
+            .inner
+            .connect(addr)
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
+        Ok(AuthenticateFuture::new(
+            fut,
+            Endpoint::Dialer,
+            self.upgrade.clone(),
+            self.timeout,
+        ))
+    }
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .inner
+            .listen(addr)
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
+        Ok(AuthenticateListener {
+            inner: listener,
+            upgrade: self.upgrade.clone(),
+            timeout: self.timeout,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+#[pin_project::pin_project]
+#[derive(Clone, Debug)]
+pub struct AuthenticateListener<T, U>
+where
+    T: Transport,
 {
     #[pin]
-    inner: UpgradeApply<C, U>,
+    inner: T::Listener,
+    upgrade: U,
+    timeout: Option<Duration>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, C, D, U, E> Stream for AuthenticateListener<T, U>
+where
+    T: Transport<Output = C>,
+    T::Error: 'static,
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>, Output = (PeerId, D), Error = E> + Clone,
+{
+    type Item = ListenerEvent<
+        AuthenticateFuture<T::ListenerUpgrade, U, C>,
+        TransportUpgradeError<T::Error, E>,
+    >;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let event = match ready!(this.inner.as_mut().poll_next(cx)) {
+            Some(event) => event,
+            None => return Poll::Ready(None),
+        };
+        let upgrade = this.upgrade.clone();
+        let timeout = *this.timeout;
+        let event = event
+            .map_upgrade_with_guard(move |up, guard| {
+                AuthenticateFuture::new(up, Endpoint::Listener, upgrade, timeout)
+                    .with_guard(guard.clone())
+            })
+            .map_err(TransportUpgradeError::Transport);
+        Poll::Ready(Some(event))
+    }
+}
+
+impl<T, C, D, U, E> Listener for AuthenticateListener<T, U>
+where
+    T: Transport<Output = C>,
+    T::Error: 'static,
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>, Output = (PeerId, D), Error = E> + Clone,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+/// 身份认证 Future，先等待底层连接建立，再通过 [`UpgradeApply`] 完成身份协商
+pub struct AuthenticateFuture<Fut, U, C>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>>,
+{
+    inner_fut: Pin<Box<Fut>>,
+    role: Endpoint,
+    upgrade: future::Either<Option<U>, UpgradeApply<C, U>>,
+    timeout: Option<Duration>,
+    delay: Option<Delay>,
+    /// 入站升级完成前计入监听器的进行中升级，监听器关闭超时后中止升级
+    guard: Option<UpgradeGuard>,
+}
+
+impl<Fut, U, C> AuthenticateFuture<Fut, U, C>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>>,
+{
+    fn new(fut: Fut, role: Endpoint, upgrade: U, timeout: Option<Duration>) -> Self {
+        AuthenticateFuture {
+            inner_fut: Box::pin(fut),
+            role,
+            upgrade: future::Either::Left(Some(upgrade)),
+            timeout,
+            delay: None,
+            guard: None,
+        }
+    }
+
+    fn with_guard(mut self, guard: UpgradeGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
 }
 
-impl<C, U> Future for Authenticate<C, U>
+impl<Fut, U, C> Unpin for AuthenticateFuture<Fut, U, C>
 where
     C: AsyncRead + AsyncWrite + Unpin,
     U: Upgrade<Negotiated<C>>,
 {
-    type Output = <UpgradeApply<C, U> as Future>::Output;
+}
+
+impl<Fut, U, C, D> Future for AuthenticateFuture<Fut, U, C>
+where
+    Fut: TryFuture<Ok = C>,
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>, Output = (PeerId, D)>,
+    U::Error: error::Error,
+{
+    type Output = Result<(PeerId, D), TransportUpgradeError<Fut::Error, U::Error>>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-        this.inner.poll(cx)
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if poll_aborted(&this.guard, cx) {
+            return Poll::Ready(Err(TransportUpgradeError::Aborted));
+        }
+        loop {
+            this.upgrade = match this.upgrade {
+                future::Either::Left(ref mut up) => {
+                    let io = ready!(this.inner_fut.as_mut().try_poll(cx))
+                        .map_err(TransportUpgradeError::Transport)?;
+                    let upgrade = up.take().expect("upgrade should be set");
+                    // 超时从身份协商开始计时
+                    this.delay = this.timeout.map(Delay::new);
+                    let upgrade = match this.role {
+                        Endpoint::Dialer => UpgradeApply::new_outbound(io, upgrade),
+                        Endpoint::Listener => UpgradeApply::new_inbound(io, upgrade),
+                    };
+                    future::Either::Right(upgrade)
+                }
+                future::Either::Right(ref mut up) => {
+                    if let Poll::Ready(output) = Pin::new(up).try_poll(cx) {
+                        return Poll::Ready(output.map_err(TransportUpgradeError::Upgrade));
+                    }
+                    if poll_timeout(&mut this.delay, cx) {
+                        return Poll::Ready(Err(TransportUpgradeError::AuthenticateTimeout));
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
     }
 }
 
@@ -141,11 +361,22 @@ pub struct WithUpgrade<T, U> {
     #[pin]
     inner: T,
     upgrade: U,
+    stage: Stage,
+    timeout: Option<Duration>,
 }
 
 impl<T, U> WithUpgrade<T, U> {
     pub fn new(inner: T, upgrade: U) -> Self {
-        WithUpgrade { inner, upgrade }
+        Self::with_stage(inner, upgrade, Stage::Apply, None)
+    }
+
+    fn with_stage(inner: T, upgrade: U, stage: Stage, timeout: Option<Duration>) -> Self {
+        WithUpgrade {
+            inner,
+            upgrade,
+            stage,
+            timeout,
+        }
     }
 }
 
@@ -163,26 +394,30 @@ where
     type ListenerUpgrade = UpgradeFuture<T::ListenerUpgrade, U, C>;
     type Listener = MapListener<T, U>;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
         let fut = self
             .inner
             .connect(addr)
-            .map_err(TransportUpgradeError::Transport)?;
-        Ok(UpgradeFuture {
-            inner_fut: Box::pin(fut),
-            role: Endpoint::Dialer,
-            upgrade: future::Either::Left(Some(self.upgrade.clone())),
-        })
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
+        Ok(UpgradeFuture::new(
+            fut,
+            Endpoint::Dialer,
+            self.upgrade.clone(),
+            self.stage,
+            self.timeout,
+        ))
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
         let listener = self
             .inner
             .listen(addr)
-            .map_err(TransportUpgradeError::Transport)?;
+            .map_err(|e| e.map(TransportUpgradeError::Transport))?;
         Ok(MapListener {
             inner: listener,
             upgrade: self.upgrade.clone(),
+            stage: self.stage,
+            timeout: self.timeout,
             _phantom: PhantomData,
         })
     }
@@ -197,6 +432,8 @@ where
     #[pin]
     inner: T::Listener,
     upgrade: U,
+    stage: Stage,
+    timeout: Option<Duration>,
     _phantom: PhantomData<T>,
 }
 
@@ -214,11 +451,11 @@ where
         match Pin::new(&mut this.inner).poll_next(cx) {
             Poll::Ready(Some(event)) => {
                 let upgrade = self.upgrade.clone();
+                let (stage, timeout) = (self.stage, self.timeout);
                 let event = event
-                    .map_upgrade(move |up| UpgradeFuture {
-                        inner_fut: Box::pin(up),
-                        role: Endpoint::Listener,
-                        upgrade: future::Either::Left(Some(upgrade)),
+                    .map_upgrade_with_guard(move |up, guard| {
+                        UpgradeFuture::new(up, Endpoint::Listener, upgrade, stage, timeout)
+                            .with_guard(guard.clone())
                     })
                     .map_err(TransportUpgradeError::Transport);
 
@@ -230,6 +467,18 @@ where
     }
 }
 
+impl<T, C, D, U, E> Listener for MapListener<T, U>
+where
+    T: Transport<Output = (PeerId, C)>,
+    T::Error: 'static,
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>, Output = D, Error = E> + Clone,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
 pub struct UpgradeFuture<Fut, U, C>
 where
     C: AsyncRead + AsyncWrite + Unpin,
@@ -238,6 +487,34 @@ where
     inner_fut: Pin<Box<Fut>>,
     role: Endpoint,
     upgrade: future::Either<Option<U>, (PeerId, UpgradeApply<C, U>)>,
+    stage: Stage,
+    timeout: Option<Duration>,
+    delay: Option<Delay>,
+    /// 入站升级完成前计入监听器的进行中升级，监听器关闭超时后中止升级
+    guard: Option<UpgradeGuard>,
+}
+
+impl<Fut, U, C> UpgradeFuture<Fut, U, C>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: Upgrade<Negotiated<C>>,
+{
+    fn new(fut: Fut, role: Endpoint, upgrade: U, stage: Stage, timeout: Option<Duration>) -> Self {
+        UpgradeFuture {
+            inner_fut: Box::pin(fut),
+            role,
+            upgrade: future::Either::Left(Some(upgrade)),
+            stage,
+            timeout,
+            delay: None,
+            guard: None,
+        }
+    }
+
+    fn with_guard(mut self, guard: UpgradeGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
 }
 
 impl<Fut, U, C> Unpin for UpgradeFuture<Fut, U, C>
@@ -257,6 +534,9 @@ where
     type Output = Result<(PeerId, D), TransportUpgradeError<Fut::Error, U::Error>>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = &mut *self;
+        if poll_aborted(&this.guard, cx) {
+            return Poll::Ready(Err(TransportUpgradeError::Aborted));
+        }
         loop {
             this.upgrade = match this.upgrade {
                 future::Either::Left(ref mut up) => {
@@ -264,6 +544,8 @@ where
                     let (peer_id, io) = ready!(this.inner_fut.as_mut().try_poll(cx))
                         .map_err(TransportUpgradeError::Transport)?;
                     let upgrade = up.take().expect("upgrade should be set");
+                    // 超时从本阶段升级开始计时
+                    this.delay = this.timeout.map(Delay::new);
                     // 使用 `UpgradeApply` 来应用升级。
                     let upgrade = match this.role {
                         Endpoint::Dialer => UpgradeApply::new_outbound(io, upgrade),
@@ -272,8 +554,13 @@ where
                     future::Either::Right((peer_id, upgrade))
                 }
                 future::Either::Right((i, ref mut up)) => {
-                    let output = ready!(Pin::new(up).try_poll(cx))?;
-                    return Poll::Ready(Ok((i, output)));
+                    if let Poll::Ready(output) = Pin::new(up).try_poll(cx) {
+                        return Poll::Ready(Ok((i, output?)));
+                    }
+                    if poll_timeout(&mut this.delay, cx) {
+                        return Poll::Ready(Err(this.stage.timeout_error()));
+                    }
+                    return Poll::Pending;
                 }
             }
         }
@@ -286,23 +573,24 @@ pub enum TransportUpgradeError<TE, UE> {
     Transport(TE),
     #[error(transparent)]
     Upgrade(#[from] UpgradeError<UE>),
+    /// [`Transport::timeout`] 限定的整个连接建立过程超时
+    #[error("Connection establishment timed out")]
+    Timeout,
+    #[error("Authentication timed out")]
+    AuthenticateTimeout,
+    #[error("Upgrade timed out")]
+    ApplyTimeout,
+    #[error("Multiplexing timed out")]
+    MultiplexTimeout,
+    /// 监听器关闭时超过等待时长仍未完成
+    #[error("Upgrade aborted because the listener was closed")]
+    Aborted,
 }
 
 #[derive(Clone)]
 #[pin_project::pin_project]
 pub struct Multiplexed<T>(#[pin] T);
 
-#[pin_project::pin_project]
-pub struct Multiplex<C, U>
-where
-    C: AsyncRead + AsyncWrite + Unpin,
-    U: Upgrade<Negotiated<C>>,
-{
-    peer_id: Option<PeerId>,
-    #[pin]
-    upgrade: UpgradeApply<C, U>,
-}
-
 impl<T> Multiplexed<T> {
     pub fn boxed<M>(self) -> Boxed<(PeerId, StreamMuxerBox)>
     where
@@ -329,32 +617,183 @@ where
     type Dialer = T::Dialer;
     type Listener = T::Listener;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
         self.0.connect(addr)
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
         self.0.listen(addr)
     }
 }
 
-impl<C, U, M, E> Future for Multiplex<C, U>
-where
-    C: AsyncRead + AsyncWrite + Unpin,
-    U: Upgrade<Negotiated<C>, Output = M, Error = E>,
-{
-    type Output = Result<(PeerId, M), UpgradeError<E>>;
+#[cfg(test)]
+mod tests {
+    use std::{array, io};
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.project();
-        let m = match ready!(Pin::new(&mut this.upgrade).poll(cx)) {
-            Ok(m) => m,
-            Err(err) => return Poll::Ready(Err(err)),
-        };
-        let i = this
-            .peer_id
-            .take()
-            .expect("Multiplex future polled after completion.");
-        Poll::Ready(Ok((i, m)))
+    use futures::{executor::block_on, future::join};
+
+    use super::*;
+    use crate::{
+        ListenerEvent, UpgradeInfo,
+        identity::Keypair,
+        transport::memory::{Channel, MemoryTransport, tests::Plaintext},
+    };
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// 协商成功后永不完成的升级，输出类型为 `O`
+    struct Stall<O>(PhantomData<fn() -> O>);
+
+    impl<O> Stall<O> {
+        fn new() -> Self {
+            Stall(PhantomData)
+        }
+    }
+
+    impl<O> Clone for Stall<O> {
+        fn clone(&self) -> Self {
+            Stall::new()
+        }
+    }
+
+    impl<O> UpgradeInfo for Stall<O> {
+        type Info = &'static str;
+        type InfoIter = array::IntoIter<Self::Info, 2>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            // 多于一个协议时拨号端会等待对端确认，两端都进入升级
+            ["/stall/test", "/stall/other"].into_iter()
+        }
+    }
+
+    impl<C, O> Upgrade<C> for Stall<O> {
+        type Output = O;
+        type Error = io::Error;
+        type Future = future::Pending<io::Result<O>>;
+
+        fn upgrade_inbound(self, _: C, _: Self::Info) -> Self::Future {
+            future::pending()
+        }
+
+        fn upgrade_outbound(self, _: C, _: Self::Info) -> Self::Future {
+            future::pending()
+        }
+    }
+
+    type Outcome<T> = Result<<T as Transport>::Output, <T as Transport>::Error>;
+
+    /// 在 `transport` 上监听并拨号，返回拨号端与监听端的升级结果
+    fn connect<T>(transport: T) -> (Outcome<T>, Outcome<T>)
+    where
+        T: Transport,
+        T::Listener: Unpin,
+    {
+        use futures::StreamExt;
+
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            let dial = transport.connect(addr).unwrap();
+            let accept = async {
+                let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                    panic!("expected Incoming");
+                };
+                upgrade.await
+            };
+            join(dial, accept).await
+        })
+    }
+
+    #[test]
+    fn authenticate_timeout() {
+        let transport = MemoryTransport::new()
+            .upgrade()
+            .authenticate_timeout(TIMEOUT)
+            .authenticate(Stall::<(PeerId, Channel)>::new())
+            .multiplex(Stall::<StreamMuxerBox>::new());
+        let (dialed, accepted) = connect(transport);
+        assert!(matches!(
+            dialed,
+            Err(TransportUpgradeError::Transport(
+                TransportUpgradeError::AuthenticateTimeout
+            ))
+        ));
+        assert!(matches!(
+            accepted,
+            Err(TransportUpgradeError::Transport(
+                TransportUpgradeError::AuthenticateTimeout
+            ))
+        ));
+    }
+
+    #[test]
+    fn authenticate_timeout_on_silent_peer() {
+        use futures::StreamExt;
+
+        // 对端建立连接后不进行协商
+        let transport = MemoryTransport::new()
+            .upgrade()
+            .authenticate_timeout(TIMEOUT)
+            .authenticate(Plaintext(Keypair::generate_ed25519()))
+            .multiplex(Stall::<StreamMuxerBox>::new());
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        block_on(async {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            let _silent = MemoryTransport::new().connect(addr).unwrap().await.unwrap();
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            assert!(matches!(
+                upgrade.await,
+                Err(TransportUpgradeError::Transport(
+                    TransportUpgradeError::AuthenticateTimeout
+                ))
+            ));
+        });
+    }
+
+    #[test]
+    fn apply_timeout() {
+        let transport = MemoryTransport::new()
+            .upgrade()
+            .authenticate(Plaintext(Keypair::generate_ed25519()))
+            .apply_timeout(TIMEOUT)
+            .apply(Stall::<Channel>::new())
+            .multiplex(Stall::<StreamMuxerBox>::new());
+        let (dialed, accepted) = connect(transport);
+        assert!(matches!(
+            dialed,
+            Err(TransportUpgradeError::Transport(
+                TransportUpgradeError::ApplyTimeout
+            ))
+        ));
+        assert!(matches!(
+            accepted,
+            Err(TransportUpgradeError::Transport(
+                TransportUpgradeError::ApplyTimeout
+            ))
+        ));
+    }
+
+    #[test]
+    fn multiplex_timeout() {
+        let transport = MemoryTransport::new()
+            .upgrade()
+            .authenticate(Plaintext(Keypair::generate_ed25519()))
+            .multiplex_timeout(TIMEOUT)
+            .multiplex(Stall::<StreamMuxerBox>::new());
+        let (dialed, accepted) = connect(transport);
+        assert!(matches!(
+            dialed,
+            Err(TransportUpgradeError::MultiplexTimeout)
+        ));
+        assert!(matches!(
+            accepted,
+            Err(TransportUpgradeError::MultiplexTimeout)
+        ));
     }
 }
@@ -9,6 +9,7 @@ use futures::{AsyncRead, AsyncWrite};
 
 use crate::{Negotiated, Upgrade, upgrade::UpgradeError};
 
+#[allow(clippy::large_enum_variant)]
 enum UpgradeApplyState<C, U>
 where
     C: AsyncRead + AsyncWrite + Unpin,
@@ -36,10 +36,10 @@ where
 
         // Grab the item to copy from.
         let item_to_copy = loop {
-            if let Some(i) = this.current_item {
-                if i.position() < i.get_ref().as_ref().len() as u64 {
-                    break i;
-                }
+            if let Some(i) = this.current_item
+                && i.position() < i.get_ref().as_ref().len() as u64
+            {
+                break i;
             }
             *this.current_item = Some(match ready!(this.inner.as_mut().try_poll_next(cx)) {
                 Some(Ok(i)) => std::io::Cursor::new(i),
@@ -0,0 +1,309 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use airio_core::{
+    Extensions, Multiaddr, PeerId, StreamMuxer, Upgrade,
+    muxing::{StreamMuxerEvent, StreamMuxerExt},
+    upgrade::{ReadyUpgrade, UpgradeApply},
+};
+use futures::{
+    AsyncRead, AsyncWrite, FutureExt, StreamExt, channel::mpsc, future::BoxFuture,
+    task::AtomicWaker,
+};
+
+use crate::{
+    Error, Identify, Info,
+    protocol::{PROTOCOL_NAME, PUSH_PROTOCOL_NAME, Protocol},
+};
+
+/// 连接发起的出站 identify 或 push
+type Task = BoxFuture<'static, Result<(), Error>>;
+
+/// 运行 identify 的多路复用连接，由 [`Identify::connection`] 创建。
+///
+/// 连接建立后打开一个子流请求对端信息，本地信息变化时向对端 push；
+/// 收到的 [`Info`] 作为连接元数据保存在 [`Connection::extensions`] 中，后收到的覆盖先收到的。
+/// 这些工作在 [`StreamMuxer::poll`] 中进行，连接需要像多路复用器一样被持续 poll。
+/// 进站子流仍交给应用，应用用 [`Connection::protocol`] 处理其中的 identify 子流。
+pub struct Connection<M> {
+    muxer: M,
+    protocol: Protocol,
+    received: mpsc::UnboundedReceiver<Info>,
+    waker: Arc<AtomicWaker>,
+    extensions: Extensions,
+    task: Option<Task>,
+    requested: bool,
+    pushed_generation: u64,
+}
+
+impl<M> Connection<M> {
+    pub(crate) fn new(
+        identify: Identify,
+        peer_id: PeerId,
+        observed_addr: Multiaddr,
+        muxer: M,
+    ) -> Self {
+        let (sender, received) = mpsc::unbounded();
+        let waker = Arc::new(AtomicWaker::new());
+        identify.register(&waker);
+        let pushed_generation = identify.generation();
+        Self {
+            muxer,
+            protocol: Protocol::new(identify, peer_id, observed_addr, sender),
+            received,
+            waker,
+            extensions: Extensions::new(),
+            task: None,
+            requested: false,
+            pushed_generation,
+        }
+    }
+
+    /// 对端最近一次发来的信息
+    pub fn info(&self) -> Option<&Info> {
+        self.extensions.get()
+    }
+
+    /// 处理进站 identify 子流的协议
+    pub fn protocol(&self) -> Protocol {
+        self.protocol.clone()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    pub fn into_inner(self) -> M {
+        self.muxer
+    }
+}
+
+impl<M> Connection<M>
+where
+    M: StreamMuxer + Unpin,
+    M::Substream: Send + Unpin + 'static,
+{
+    /// 保存收到的信息，并在需要时打开子流请求或 push
+    fn poll_identify(&mut self, cx: &mut Context<'_>) -> Result<(), M::Error> {
+        self.waker.register(cx.waker());
+        loop {
+            while let Poll::Ready(Some(info)) = self.received.poll_next_unpin(cx) {
+                tracing::debug!(
+                    "Identified {} ({}), listening on {:?}",
+                    info.peer_id(),
+                    info.agent_version,
+                    info.listen_addrs
+                );
+                self.extensions.insert(info);
+            }
+            if let Some(task) = self.task.as_mut() {
+                match task.poll_unpin(cx) {
+                    Poll::Pending => return Ok(()),
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(e)) => tracing::debug!("Identify failed: {}", e),
+                }
+                self.task = None;
+                continue;
+            }
+
+            let generation = self.protocol.identify().generation();
+            let protocol_name = if !self.requested {
+                PROTOCOL_NAME
+            } else if generation != self.pushed_generation {
+                PUSH_PROTOCOL_NAME
+            } else {
+                return Ok(());
+            };
+            let substream = match self.muxer.poll_outbound_unpin(cx) {
+                Poll::Ready(substream) => substream?,
+                Poll::Pending => return Ok(()),
+            };
+            if protocol_name == PROTOCOL_NAME {
+                self.requested = true;
+            } else {
+                self.pushed_generation = generation;
+            }
+            let protocol = self.protocol.clone();
+            self.task = Some(Box::pin(async move {
+                let stream =
+                    UpgradeApply::new_outbound(substream, ReadyUpgrade::new(protocol_name)).await?;
+                protocol.upgrade_outbound(stream, protocol_name).await
+            }));
+        }
+    }
+}
+
+impl<M> StreamMuxer for Connection<M>
+where
+    M: StreamMuxer + Unpin,
+    M::Substream: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Substream = M::Substream;
+    type Error = M::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        self.muxer.poll_inbound_unpin(cx)
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        self.muxer.poll_outbound_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.muxer.poll_close_unpin(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.poll_identify(cx)?;
+        self.muxer.poll_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::{
+        ConnectedPoint, identity::Keypair, transport::memory::Channel, upgrade::UpgradeError,
+    };
+    use airio_yamux::{Config, Mode, Muxer};
+    use futures::{executor::block_on, future, stream::FuturesUnordered};
+
+    use super::*;
+
+    type Inbound = BoxFuture<'static, Result<(), UpgradeError<Error>>>;
+
+    /// 连接的一端，在同一任务中驱动多路复用器与进站的 identify 子流
+    struct Node {
+        identify: Identify,
+        connection: Connection<Muxer<Channel>>,
+        inbound: FuturesUnordered<Inbound>,
+    }
+
+    impl Node {
+        fn new(
+            key: &Keypair,
+            remote: PeerId,
+            socket: Channel,
+            mode: Mode,
+            point: ConnectedPoint,
+        ) -> Self {
+            let identify = Identify::new(key);
+            let muxer = Muxer::new(airio_yamux::Connection::new(
+                socket,
+                Config::default(),
+                mode,
+            ));
+            let connection = identify.connection(remote, muxer, &point);
+            Self {
+                identify,
+                connection,
+                inbound: FuturesUnordered::new(),
+            }
+        }
+
+        fn poll(&mut self, cx: &mut Context<'_>) {
+            if let Poll::Ready(Err(e)) = self.connection.poll_unpin(cx) {
+                panic!("connection failed: {e}");
+            }
+            while let Poll::Ready(substream) = self.connection.poll_inbound_unpin(cx) {
+                let substream = substream.expect("inbound substream");
+                let protocol = self.connection.protocol();
+                self.inbound
+                    .push(Box::pin(UpgradeApply::new_inbound(substream, protocol)));
+            }
+            while let Poll::Ready(Some(result)) = self.inbound.poll_next_unpin(cx) {
+                result.expect("inbound identify");
+            }
+        }
+    }
+
+    /// 同时驱动两端，直到 `done` 成立
+    fn run_until(a: &mut Node, b: &mut Node, done: impl Fn(&Node, &Node) -> bool) {
+        block_on(future::poll_fn(|cx| {
+            a.poll(cx);
+            b.poll(cx);
+            if done(a, b) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+    }
+
+    fn nodes() -> (Node, Node, Keypair, Keypair) {
+        let (dialer, listener) = Channel::pair();
+        let (a_key, b_key) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let a = Node::new(
+            &a_key,
+            b_key.to_peer_id(),
+            dialer,
+            Mode::Client,
+            ConnectedPoint::Dialer {
+                addr: "/memory/1".parse().unwrap(),
+            },
+        );
+        let b = Node::new(
+            &b_key,
+            a_key.to_peer_id(),
+            listener,
+            Mode::Server,
+            ConnectedPoint::Listener {
+                local_addr: "/memory/1".parse().unwrap(),
+                remote_addr: "/memory/2".parse().unwrap(),
+            },
+        );
+        (a, b, a_key, b_key)
+    }
+
+    #[test]
+    fn identifies_remote_on_connect() {
+        let (mut a, mut b, a_key, b_key) = nodes();
+        run_until(&mut a, &mut b, |a, b| {
+            a.connection.info().is_some() && b.connection.info().is_some()
+        });
+
+        let a_info = a.connection.extensions().get::<Info>().unwrap();
+        assert_eq!(a_info.peer_id(), b_key.to_peer_id());
+        assert_eq!(a_info.observed_addr, Some("/memory/2".parse().unwrap()));
+        let b_info = b.connection.info().unwrap();
+        assert_eq!(b_info.peer_id(), a_key.to_peer_id());
+        assert_eq!(b_info.observed_addr, Some("/memory/1".parse().unwrap()));
+    }
+
+    #[test]
+    fn pushes_local_changes() {
+        let (mut a, mut b, _, _) = nodes();
+        run_until(&mut a, &mut b, |a, b| {
+            a.connection.info().is_some() && b.connection.info().is_some()
+        });
+
+        let addrs: Vec<Multiaddr> = vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()];
+        a.identify.set_listen_addrs(addrs.clone());
+        a.identify.set_protocols(vec!["/echo/1.0.0".to_owned()]);
+        run_until(&mut a, &mut b, |_, b| {
+            b.connection
+                .info()
+                .is_some_and(|info| info.protocols == ["/echo/1.0.0"])
+        });
+
+        let info = b.connection.info().unwrap();
+        assert_eq!(info.listen_addrs, addrs);
+        let record = info.signed_peer_record.as_ref().unwrap();
+        assert_eq!(record.addresses(), addrs.as_slice());
+    }
+}
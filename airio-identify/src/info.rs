@@ -0,0 +1,246 @@
+use airio_core::{
+    Multiaddr, PeerId, PeerRecord,
+    identity::{
+        PublicKey,
+        proto::{self, Value},
+    },
+};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Error;
+
+/// 单条 identify 消息的最大长度
+const MAX_MESSAGE_LENGTH: usize = 8192;
+
+const PUBLIC_KEY_FIELD: u64 = 1;
+const LISTEN_ADDRS_FIELD: u64 = 2;
+const PROTOCOLS_FIELD: u64 = 3;
+const OBSERVED_ADDR_FIELD: u64 = 4;
+const PROTOCOL_VERSION_FIELD: u64 = 5;
+const AGENT_VERSION_FIELD: u64 = 6;
+const SIGNED_PEER_RECORD_FIELD: u64 = 8;
+
+/// 通过 identify 交换的节点信息，编码与 libp2p 的 `Identify` 消息相同
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Info {
+    pub public_key: PublicKey,
+    pub protocol_version: String,
+    pub agent_version: String,
+    /// 节点的监听地址，带有效的签名记录时以记录中的地址为准
+    pub listen_addrs: Vec<Multiaddr>,
+    /// 节点支持的子流协议
+    pub protocols: Vec<String>,
+    /// 节点观察到的对端地址，即接收方在对方眼中的地址
+    pub observed_addr: Option<Multiaddr>,
+    /// 节点签名的地址记录，解码时已验证
+    pub signed_peer_record: Option<PeerRecord>,
+}
+
+impl Info {
+    pub fn peer_id(&self) -> PeerId {
+        self.public_key.to_peer_id()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        proto::write_bytes_field(
+            PUBLIC_KEY_FIELD,
+            &self.public_key.to_protobuf_encoding(),
+            &mut out,
+        );
+        for addr in &self.listen_addrs {
+            proto::write_bytes_field(LISTEN_ADDRS_FIELD, &addr.to_vec(), &mut out);
+        }
+        for protocol in &self.protocols {
+            proto::write_bytes_field(PROTOCOLS_FIELD, protocol.as_bytes(), &mut out);
+        }
+        if let Some(addr) = &self.observed_addr {
+            proto::write_bytes_field(OBSERVED_ADDR_FIELD, &addr.to_vec(), &mut out);
+        }
+        proto::write_bytes_field(
+            PROTOCOL_VERSION_FIELD,
+            self.protocol_version.as_bytes(),
+            &mut out,
+        );
+        proto::write_bytes_field(AGENT_VERSION_FIELD, self.agent_version.as_bytes(), &mut out);
+        if let Some(record) = &self.signed_peer_record {
+            proto::write_bytes_field(
+                SIGNED_PEER_RECORD_FIELD,
+                &record.to_protobuf_encoding(),
+                &mut out,
+            );
+        }
+        out
+    }
+
+    fn decode(mut input: &[u8]) -> Result<Self, Error> {
+        let mut public_key = None;
+        let (mut protocol_version, mut agent_version) = (String::new(), String::new());
+        let (mut listen_addrs, mut protocols) = (Vec::new(), Vec::new());
+        let (mut observed_addr, mut signed_peer_record) = (None, None);
+        while !input.is_empty() {
+            let (field, value, rest) = proto::read_field(input)
+                .map_err(|_| Error::InvalidMessage("invalid protobuf encoding"))?;
+            input = rest;
+            let Value::Bytes(bytes) = value else {
+                continue;
+            };
+            match field {
+                PUBLIC_KEY_FIELD => public_key = Some(PublicKey::from_protobuf_encoding(bytes)?),
+                LISTEN_ADDRS_FIELD => listen_addrs.push(Multiaddr::try_from(bytes)?),
+                PROTOCOLS_FIELD => protocols.push(string(bytes)?),
+                OBSERVED_ADDR_FIELD => observed_addr = Some(Multiaddr::try_from(bytes)?),
+                PROTOCOL_VERSION_FIELD => protocol_version = string(bytes)?,
+                AGENT_VERSION_FIELD => agent_version = string(bytes)?,
+                SIGNED_PEER_RECORD_FIELD => match PeerRecord::from_protobuf_encoding(bytes) {
+                    Ok(record) => signed_peer_record = Some(record),
+                    Err(e) => tracing::debug!("Ignoring invalid signed peer record: {}", e),
+                },
+                _ => {}
+            }
+        }
+        let public_key = public_key.ok_or(Error::InvalidMessage("missing public key"))?;
+        // 只接受由消息中的公钥签名的记录
+        let signed_peer_record =
+            signed_peer_record.filter(|record| record.peer_id() == public_key.to_peer_id());
+        if let Some(record) = &signed_peer_record {
+            listen_addrs = record.addresses().to_vec();
+        }
+        Ok(Info {
+            public_key,
+            protocol_version,
+            agent_version,
+            listen_addrs,
+            protocols,
+            observed_addr,
+            signed_peer_record,
+        })
+    }
+}
+
+fn string(bytes: &[u8]) -> Result<String, Error> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidMessage("invalid UTF-8 string"))
+}
+
+/// 写入一条带 varint 长度前缀的消息并关闭写端
+pub(crate) async fn write_info<S>(stream: &mut S, info: &Info) -> Result<(), Error>
+where
+    S: AsyncWrite + Unpin,
+{
+    let message = info.encode();
+    if message.len() > MAX_MESSAGE_LENGTH {
+        return Err(Error::MessageTooLong(message.len()));
+    }
+    let mut out = Vec::with_capacity(message.len() + 2);
+    proto::write_varint(message.len() as u64, &mut out);
+    out.extend_from_slice(&message);
+    stream.write_all(&out).await?;
+    stream.close().await?;
+    Ok(())
+}
+
+pub(crate) async fn read_info<S>(stream: &mut S) -> Result<Info, Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut len: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).await?;
+        len |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if len > MAX_MESSAGE_LENGTH as u64 {
+            return Err(Error::MessageTooLong(len as usize));
+        }
+        if byte[0] & 0x80 == 0 {
+            let mut message = vec![0u8; len as usize];
+            stream.read_exact(&mut message).await?;
+            return Info::decode(&message);
+        }
+    }
+    Err(Error::InvalidMessage("invalid length prefix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::identity::Keypair;
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::PROTOCOL_NAME;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    fn info(key: &Keypair, signed_peer_record: Option<PeerRecord>) -> Info {
+        Info {
+            public_key: key.public(),
+            protocol_version: "/airio/1.0.0".to_owned(),
+            agent_version: "airio/test".to_owned(),
+            listen_addrs: vec![addr("/ip4/127.0.0.1/tcp/4001")],
+            protocols: vec![PROTOCOL_NAME.to_owned(), "/echo/1.0.0".to_owned()],
+            observed_addr: Some(addr("/ip4/10.0.0.1/tcp/53000")),
+            signed_peer_record,
+        }
+    }
+
+    /// 经过一次编码与解码
+    fn transfer(info: &Info) -> Result<Info, Error> {
+        block_on(async {
+            let mut encoded = Vec::new();
+            write_info(&mut encoded, info).await?;
+            read_info(&mut encoded.as_slice()).await
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = Keypair::generate_ed25519();
+        let unsigned = info(&key, None);
+        assert_eq!(transfer(&unsigned).unwrap(), unsigned);
+
+        let record = PeerRecord::new(&key, unsigned.listen_addrs.clone());
+        let signed = info(&key, Some(record));
+        assert_eq!(transfer(&signed).unwrap(), signed);
+    }
+
+    #[test]
+    fn signed_record_overrides_listen_addrs() {
+        let key = Keypair::generate_ed25519();
+        let record = PeerRecord::new(&key, vec![addr("/ip4/192.0.2.1/tcp/4001")]);
+        let received = transfer(&info(&key, Some(record.clone()))).unwrap();
+        assert_eq!(received.listen_addrs, record.addresses());
+        assert_eq!(received.signed_peer_record, Some(record));
+    }
+
+    #[test]
+    fn drops_record_from_other_key() {
+        let key = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let record = PeerRecord::new(&other, vec![addr("/ip4/192.0.2.1/tcp/4001")]);
+        let received = transfer(&info(&key, Some(record))).unwrap();
+        assert_eq!(received.signed_peer_record, None);
+        assert_eq!(received.listen_addrs, [addr("/ip4/127.0.0.1/tcp/4001")]);
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let mut message = Vec::new();
+        proto::write_bytes_field(AGENT_VERSION_FIELD, b"airio/test", &mut message);
+        assert!(matches!(
+            Info::decode(&message),
+            Err(Error::InvalidMessage("missing public key"))
+        ));
+        assert!(matches!(
+            Info::decode(&[0x0a, 0x05, 0x01]),
+            Err(Error::InvalidMessage("invalid protobuf encoding"))
+        ));
+
+        let mut oversized = Vec::new();
+        proto::write_varint(MAX_MESSAGE_LENGTH as u64 + 1, &mut oversized);
+        assert!(matches!(
+            block_on(read_info(&mut oversized.as_slice())),
+            Err(Error::MessageTooLong(_))
+        ));
+    }
+}
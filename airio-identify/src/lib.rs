@@ -1,46 +1,127 @@
+mod connection;
+mod info;
+mod protocol;
+
 use std::{
+    convert::Infallible,
     io, iter,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use airio_core::{PeerId, Upgrade, UpgradeInfo};
-use ed25519_dalek::PUBLIC_KEY_LENGTH;
-use ed25519_dalek::{SignatureError, VerifyingKey};
+use airio_core::{
+    PeerId, Upgrade, UpgradeInfo,
+    identity::{DecodingError, Keypair, PublicKey},
+    multiaddr,
+    upgrade::UpgradeError,
+};
 use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, future::BoxFuture};
 
-pub use ed25519_dalek as ed25519;
+pub use connection::Connection;
+pub use info::Info;
+pub use protocol::{Identify, PROTOCOL_NAME, PUSH_PROTOCOL_NAME, Protocol};
+
+const NONCE_LENGTH: usize = 32;
+
+/// 公钥编码与签名的最大长度
+const MAX_FIELD_LENGTH: usize = 4096;
+
+/// 签名内容的前缀，避免签名被挪作他用
+const TRANSCRIPT_DOMAIN: &[u8] = b"airio-identify-transcript:";
 
 #[derive(Clone)]
 pub struct Config {
-    local_pubkey: VerifyingKey,
+    local_key: Keypair,
 }
 
 impl Config {
-    pub fn new(local_pubkey: VerifyingKey) -> Self {
-        Self { local_pubkey }
+    pub fn new(local_key: Keypair) -> Self {
+        Self { local_key }
     }
 
+    /// 双方先交换公钥与随机数，再各自对握手记录签名，以证明持有公钥对应的私钥
     async fn handshake<T>(self, mut socket: T) -> Result<(PeerId, IdentifyConnection<T>), Error>
     where
         T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
     {
-        socket.write_all(self.local_pubkey.as_bytes()).await?;
+        let local_pubkey = self.local_key.public().to_protobuf_encoding();
+        let local_nonce: [u8; NONCE_LENGTH] = rand::random();
+        write_field(&mut socket, &local_pubkey).await?;
+        socket.write_all(&local_nonce).await?;
         socket.flush().await?;
-        let mut key_buf = [0; PUBLIC_KEY_LENGTH];
-        socket.read_exact(&mut key_buf).await?;
-        let remote_key = VerifyingKey::from_bytes(&key_buf)?;
-        let peer_id = PeerId::from_bytes(remote_key.as_bytes().clone());
+
+        let remote_pubkey = read_field(&mut socket).await?;
+        let remote_key = PublicKey::from_protobuf_encoding(&remote_pubkey)?;
+        let mut remote_nonce = [0; NONCE_LENGTH];
+        socket.read_exact(&mut remote_nonce).await?;
+        if remote_nonce == local_nonce {
+            return Err(Error::ReflectedHandshake);
+        }
+
+        let signature = self.local_key.sign(&transcript(
+            &local_pubkey,
+            &remote_pubkey,
+            &local_nonce,
+            &remote_nonce,
+        ));
+        write_field(&mut socket, &signature).await?;
+        socket.flush().await?;
+
+        let remote_signature = read_field(&mut socket).await?;
+        let remote_transcript =
+            transcript(&remote_pubkey, &local_pubkey, &remote_nonce, &local_nonce);
+        if !remote_key.verify(&remote_transcript, &remote_signature) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let peer_id = remote_key.to_peer_id();
         Ok((peer_id, IdentifyConnection { socket, remote_key }))
     }
 }
 
+/// 两字节大端长度加内容
+async fn write_field<T>(socket: &mut T, field: &[u8]) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    socket
+        .write_all(&(field.len() as u16).to_be_bytes())
+        .await?;
+    socket.write_all(field).await
+}
+
+async fn read_field<T>(socket: &mut T) -> Result<Vec<u8>, Error>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut len = [0; 2];
+    socket.read_exact(&mut len).await?;
+    let len = u16::from_be_bytes(len) as usize;
+    if len > MAX_FIELD_LENGTH {
+        return Err(Error::FieldTooLong(len));
+    }
+    let mut field = vec![0; len];
+    socket.read_exact(&mut field).await?;
+    Ok(field)
+}
+
+/// 签名方视角的握手记录：签名方公钥、对方公钥、签名方随机数、对方随机数，
+/// 公钥为 protobuf 编码，本身带有长度
+fn transcript(
+    signer: &[u8],
+    peer: &[u8],
+    signer_nonce: &[u8; NONCE_LENGTH],
+    peer_nonce: &[u8; NONCE_LENGTH],
+) -> Vec<u8> {
+    [TRANSCRIPT_DOMAIN, signer, peer, signer_nonce, peer_nonce].concat()
+}
+
 impl UpgradeInfo for Config {
     type Info = &'static str;
     type InfoIter = iter::Once<Self::Info>;
 
     fn protocol_info(&self) -> Self::InfoIter {
-        iter::once("/v1/identify")
+        iter::once("/v3/identify")
     }
 }
 
@@ -66,15 +147,31 @@ where
     S: AsyncRead + AsyncWrite + Unpin,
 {
     pub socket: S,
-    pub remote_key: VerifyingKey,
+    pub remote_key: PublicKey,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
-    #[error(transparent)]
-    SignatureError(#[from] SignatureError),
+    #[error("Invalid remote public key: {0}")]
+    InvalidKey(#[from] DecodingError),
+    #[error("Handshake field too long: {0}")]
+    FieldTooLong(usize),
+    #[error("Remote handshake signature verification failed")]
+    InvalidSignature,
+    #[error("Remote echoed the local handshake nonce")]
+    ReflectedHandshake,
+    #[error("Identify message too long: {0}")]
+    MessageTooLong(usize),
+    #[error("Invalid identify message: {0}")]
+    InvalidMessage(&'static str),
+    #[error("Invalid address in identify message: {0}")]
+    InvalidAddress(#[from] multiaddr::Error),
+    #[error("Identify info is for {actual}, expected {expected}")]
+    UnexpectedPeer { expected: PeerId, actual: PeerId },
+    #[error("Identify protocol negotiation failed: {0}")]
+    Negotiation(#[from] UpgradeError<Infallible>),
 }
 
 impl<T> AsyncRead for IdentifyConnection<T>
@@ -118,3 +215,106 @@ where
         Pin::new(&mut self.get_mut().socket).poll_close(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use airio_core::transport::memory::Channel;
+    use futures::{executor::block_on, future::join};
+
+    use super::*;
+
+    /// 手动执行握手的对端，`sign` 根据双方的公钥与随机数给出发送的签名。
+    /// 被测的一方发现错误后会直接关闭连接，因此之后的写入错误被忽略
+    async fn stand_in<F>(
+        mut socket: Channel,
+        key: &Keypair,
+        nonce: Option<[u8; NONCE_LENGTH]>,
+        sign: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&[u8], &[u8; NONCE_LENGTH], &[u8; NONCE_LENGTH]) -> Vec<u8>,
+    {
+        let remote_pubkey = read_field(&mut socket).await?;
+        let mut remote_nonce = [0; NONCE_LENGTH];
+        socket.read_exact(&mut remote_nonce).await?;
+        // 未指定随机数时回显对端的随机数
+        let local_nonce = nonce.unwrap_or(remote_nonce);
+        let local_pubkey = key.public().to_protobuf_encoding();
+        write_field(&mut socket, &local_pubkey).await?;
+        socket.write_all(&local_nonce).await?;
+        let signature = sign(&remote_pubkey, &local_nonce, &remote_nonce);
+        write_field(&mut socket, &signature).await?;
+        socket.flush().await?;
+        // 等待对端读完后关闭
+        let _ = socket.read(&mut [0u8; 1]).await;
+        Ok(())
+    }
+
+    #[test]
+    fn handshake_proves_key_possession() {
+        let (dialer_key, listener_key) = (Keypair::generate_ed25519(), Keypair::generate_ecdsa());
+        let (outbound, inbound) = block_on(Channel::upgrade_pair(
+            Config::new(dialer_key.clone()),
+            Config::new(listener_key.clone()),
+            "/v3/identify",
+        ));
+        let (remote, mut outbound) = outbound.unwrap();
+        assert_eq!(remote, listener_key.to_peer_id());
+        assert_eq!(outbound.remote_key, listener_key.public());
+        let (remote, mut inbound) = inbound.unwrap();
+        assert_eq!(remote, dialer_key.to_peer_id());
+
+        // 握手之后的数据原样传递
+        block_on(async {
+            outbound.write_all(b"ping").await.unwrap();
+            outbound.flush().await.unwrap();
+            let mut buf = [0u8; 4];
+            inbound.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn rejects_forged_signature() {
+        let (local_key, claimed_key) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let forger = Keypair::generate_ed25519();
+        let (outbound, inbound) = Channel::pair();
+        let claimed = claimed_key.public().to_protobuf_encoding();
+        // 声称持有 `claimed_key`，签名却来自另一个私钥
+        let sign = |remote: &[u8], local_nonce: &[u8; NONCE_LENGTH], remote_nonce: &_| {
+            forger.sign(&transcript(&claimed, remote, local_nonce, remote_nonce))
+        };
+        let (result, _) = block_on(join(
+            Config::new(local_key.clone()).upgrade_outbound(outbound, "/v3/identify"),
+            stand_in(inbound, &claimed_key, Some(rand::random()), sign),
+        ));
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+
+        // 签名的握手记录与本次握手不符
+        let (outbound, inbound) = Channel::pair();
+        let sign = |remote: &[u8], local_nonce: &[u8; NONCE_LENGTH], _: &_| {
+            claimed_key.sign(&transcript(
+                &claimed,
+                remote,
+                local_nonce,
+                &[0; NONCE_LENGTH],
+            ))
+        };
+        let (result, _) = block_on(join(
+            Config::new(local_key).upgrade_outbound(outbound, "/v3/identify"),
+            stand_in(inbound, &claimed_key, Some(rand::random()), sign),
+        ));
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+    }
+
+    #[test]
+    fn rejects_reflected_nonce() {
+        let key = Keypair::generate_ed25519();
+        let (outbound, inbound) = Channel::pair();
+        let (result, _) = block_on(join(
+            Config::new(key.clone()).upgrade_outbound(outbound, "/v3/identify"),
+            stand_in(inbound, &key, None, |_, _, _| Vec::new()),
+        ));
+        assert!(matches!(result, Err(Error::ReflectedHandshake)));
+    }
+}
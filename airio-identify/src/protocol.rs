@@ -0,0 +1,309 @@
+use std::{
+    array,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+use airio_core::{
+    ConnectedPoint, Multiaddr, PeerId, PeerRecord, Upgrade, UpgradeInfo, identity::Keypair,
+};
+use futures::{AsyncRead, AsyncWrite, channel::mpsc, future::BoxFuture, task::AtomicWaker};
+
+use crate::{
+    Connection, Error,
+    info::{self, Info},
+};
+
+/// 请求对端信息的子流协议，由接收方回复
+pub const PROTOCOL_NAME: &str = "/ipfs/id/1.0.0";
+/// 主动推送本地信息的子流协议，由发起方发送
+pub const PUSH_PROTOCOL_NAME: &str = "/ipfs/id/push/1.0.0";
+
+const DEFAULT_PROTOCOL_VERSION: &str = "/airio/1.0.0";
+const DEFAULT_AGENT_VERSION: &str = concat!("airio/", env!("CARGO_PKG_VERSION"));
+
+/// 本地节点在 identify 中公布的信息，在所有连接间共享。
+///
+/// 通过 [`Identify::set_listen_addrs`] 或 [`Identify::set_protocols`] 修改后，
+/// 每个 [`Connection`] 都会向对端 push 新的信息。监听地址同时以
+/// [`PeerRecord`] 的形式签名发送，对端可将其转发给其他节点。
+#[derive(Clone)]
+pub struct Identify {
+    inner: Arc<Shared>,
+}
+
+struct Shared {
+    local_key: Keypair,
+    state: Mutex<LocalState>,
+}
+
+struct LocalState {
+    protocol_version: String,
+    agent_version: String,
+    listen_addrs: Vec<Multiaddr>,
+    protocols: Vec<String>,
+    peer_record: PeerRecord,
+    /// 每次修改加一，连接据此判断是否需要 push
+    generation: u64,
+    connections: Vec<Weak<AtomicWaker>>,
+}
+
+impl Identify {
+    pub fn new(local_key: &Keypair) -> Self {
+        let state = LocalState {
+            protocol_version: DEFAULT_PROTOCOL_VERSION.to_owned(),
+            agent_version: DEFAULT_AGENT_VERSION.to_owned(),
+            listen_addrs: Vec::new(),
+            protocols: Vec::new(),
+            peer_record: PeerRecord::new(local_key, Vec::new()),
+            generation: 0,
+            connections: Vec::new(),
+        };
+        Self {
+            inner: Arc::new(Shared {
+                local_key: local_key.clone(),
+                state: Mutex::new(state),
+            }),
+        }
+    }
+
+    /// 节点遵循的协议族版本，默认为 `/airio/1.0.0`
+    pub fn protocol_version(self, protocol_version: impl Into<String>) -> Self {
+        self.state().protocol_version = protocol_version.into();
+        self
+    }
+
+    /// 节点的实现名称与版本，默认为 `airio/<版本号>`
+    pub fn agent_version(self, agent_version: impl Into<String>) -> Self {
+        self.state().agent_version = agent_version.into();
+        self
+    }
+
+    /// 更新监听地址并重新签名地址记录，之后向所有连接的对端 push
+    pub fn set_listen_addrs(&self, listen_addrs: Vec<Multiaddr>) {
+        let peer_record = PeerRecord::new(&self.inner.local_key, listen_addrs.clone());
+        let mut state = self.state();
+        state.listen_addrs = listen_addrs;
+        state.peer_record = peer_record;
+        state.changed();
+    }
+
+    /// 更新支持的子流协议，之后向所有连接的对端 push
+    pub fn set_protocols(&self, protocols: Vec<String>) {
+        let mut state = self.state();
+        state.protocols = protocols;
+        state.changed();
+    }
+
+    /// 当前公布的本地信息，不含观察地址
+    pub fn local_info(&self) -> Info {
+        let state = self.state();
+        Info {
+            public_key: self.inner.local_key.public(),
+            protocol_version: state.protocol_version.clone(),
+            agent_version: state.agent_version.clone(),
+            listen_addrs: state.listen_addrs.clone(),
+            protocols: state.protocols.clone(),
+            observed_addr: None,
+            signed_peer_record: Some(state.peer_record.clone()),
+        }
+    }
+
+    /// 在多路复用后的连接上运行 identify，通常在 [`Transport::map`](airio_core::Transport::map)
+    /// 中调用：`.map(move |(peer_id, muxer), point| (peer_id, identify.connection(peer_id, muxer, &point)))`
+    pub fn connection<M>(
+        &self,
+        peer_id: PeerId,
+        muxer: M,
+        point: &ConnectedPoint,
+    ) -> Connection<M> {
+        let observed_addr = match point {
+            ConnectedPoint::Dialer { addr } => addr.clone(),
+            ConnectedPoint::Listener { remote_addr, .. } => remote_addr.clone(),
+        };
+        Connection::new(self.clone(), peer_id, observed_addr, muxer)
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.state().generation
+    }
+
+    /// 本地信息变化时唤醒连接
+    pub(crate) fn register(&self, waker: &Arc<AtomicWaker>) {
+        self.state().connections.push(Arc::downgrade(waker));
+    }
+
+    fn state(&self) -> MutexGuard<'_, LocalState> {
+        self.inner.state.lock().expect("identify state poisoned")
+    }
+}
+
+impl LocalState {
+    fn changed(&mut self) {
+        self.generation += 1;
+        self.connections.retain(|waker| match waker.upgrade() {
+            Some(waker) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// 连接上的 identify 子流协议。
+///
+/// 进站方向对 [`PROTOCOL_NAME`] 回复本地信息，对 [`PUSH_PROTOCOL_NAME`] 接收对端推送的信息；
+/// 出站方向相反。收到的信息交给所属的 [`Connection`]，保存到连接的元数据中。
+/// 应用需将其与自身的协议组合（如 [`SelectUpgrade`](airio_core::upgrade::SelectUpgrade)）
+/// 来处理进站子流。
+#[derive(Clone)]
+pub struct Protocol {
+    identify: Identify,
+    remote_peer_id: PeerId,
+    /// 对端的地址，作为观察地址发给对端
+    observed_addr: Multiaddr,
+    received: mpsc::UnboundedSender<Info>,
+}
+
+impl Protocol {
+    pub(crate) fn new(
+        identify: Identify,
+        remote_peer_id: PeerId,
+        observed_addr: Multiaddr,
+        received: mpsc::UnboundedSender<Info>,
+    ) -> Self {
+        Self {
+            identify,
+            remote_peer_id,
+            observed_addr,
+            received,
+        }
+    }
+
+    pub(crate) fn identify(&self) -> &Identify {
+        &self.identify
+    }
+
+    async fn send<S>(self, mut stream: S) -> Result<(), Error>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let mut info = self.identify.local_info();
+        info.observed_addr = Some(self.observed_addr);
+        info::write_info(&mut stream, &info).await
+    }
+
+    /// 接收对端的信息，公钥必须与连接的 `PeerId` 一致
+    async fn receive<S>(self, mut stream: S) -> Result<(), Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let info = info::read_info(&mut stream).await?;
+        if info.peer_id() != self.remote_peer_id {
+            return Err(Error::UnexpectedPeer {
+                expected: self.remote_peer_id,
+                actual: info.peer_id(),
+            });
+        }
+        // 连接已关闭时丢弃
+        let _ = self.received.unbounded_send(info);
+        Ok(())
+    }
+}
+
+impl UpgradeInfo for Protocol {
+    type Info = &'static str;
+    type InfoIter = array::IntoIter<Self::Info, 2>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        [PROTOCOL_NAME, PUSH_PROTOCOL_NAME].into_iter()
+    }
+}
+
+impl<C> Upgrade<C> for Protocol
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = ();
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        if info == PROTOCOL_NAME {
+            Box::pin(self.send(socket))
+        } else {
+            Box::pin(self.receive(socket))
+        }
+    }
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        if info == PROTOCOL_NAME {
+            Box::pin(self.receive(socket))
+        } else {
+            Box::pin(self.send(socket))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, executor::block_on, io::Cursor};
+
+    use super::*;
+
+    /// 期望 `expected` 的协议接收 `sender` 发出的信息
+    fn receive(expected: PeerId, sender: &Identify) -> (Result<(), Error>, Option<Info>) {
+        let (tx, mut rx) = mpsc::unbounded();
+        let local = Identify::new(&Keypair::generate_ed25519());
+        let protocol = Protocol::new(local, expected, "/memory/1".parse().unwrap(), tx);
+        block_on(async {
+            let mut encoded = Vec::new();
+            info::write_info(&mut encoded, &sender.local_info())
+                .await
+                .unwrap();
+            let result = protocol
+                .upgrade_inbound(Cursor::new(encoded), PUSH_PROTOCOL_NAME)
+                .await;
+            (result, rx.next().await)
+        })
+    }
+
+    #[test]
+    fn accepts_info_from_expected_peer() {
+        let key = Keypair::generate_ed25519();
+        let identify = Identify::new(&key).agent_version("airio/test");
+        let (result, info) = receive(key.to_peer_id(), &identify);
+        result.unwrap();
+        let info = info.expect("info forwarded to the connection");
+        assert_eq!(info.peer_id(), key.to_peer_id());
+        assert_eq!(info.agent_version, "airio/test");
+    }
+
+    #[test]
+    fn rejects_unexpected_peer() {
+        let expected = Keypair::generate_ed25519().to_peer_id();
+        let key = Keypair::generate_ed25519();
+        let (result, info) = receive(expected, &Identify::new(&key));
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedPeer { expected: e, actual })
+                if e == expected && actual == key.to_peer_id()
+        ));
+        assert!(info.is_none());
+    }
+
+    #[test]
+    fn set_listen_addrs_signs_new_record() {
+        let key = Keypair::generate_ed25519();
+        let identify = Identify::new(&key);
+        let before = identify.local_info().signed_peer_record.unwrap();
+        let addrs: Vec<Multiaddr> = vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()];
+        identify.set_listen_addrs(addrs.clone());
+
+        let info = identify.local_info();
+        assert_eq!(info.listen_addrs, addrs);
+        let record = info.signed_peer_record.unwrap();
+        assert_eq!(record.addresses(), addrs.as_slice());
+        assert!(record.seq() >= before.seq());
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use airio_core::identity::PublicKey;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use snow::TransportState;
+
+/// Noise 消息的最大长度
+pub(crate) const MAX_FRAME_LEN: usize = 65535;
+
+/// ChaCha20-Poly1305 认证标签的长度
+const TAG_LEN: usize = 16;
+
+/// 帧头为两字节大端长度
+const LENGTH_PREFIX: usize = 2;
+
+/// 单帧可承载的最大明文长度
+const MAX_PLAINTEXT: usize = MAX_FRAME_LEN - TAG_LEN;
+
+pub(crate) async fn write_frame<C>(socket: &mut C, message: &[u8]) -> io::Result<()>
+where
+    C: AsyncWrite + Unpin,
+{
+    socket
+        .write_all(&(message.len() as u16).to_be_bytes())
+        .await?;
+    socket.write_all(message).await?;
+    socket.flush().await
+}
+
+pub(crate) async fn read_frame<C>(socket: &mut C) -> io::Result<Vec<u8>>
+where
+    C: AsyncRead + Unpin,
+{
+    let mut len = [0u8; LENGTH_PREFIX];
+    socket.read_exact(&mut len).await?;
+    let mut message = vec![0u8; u16::from_be_bytes(len) as usize];
+    socket.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+/// Noise 握手完成后的加密连接，数据按帧加密，每帧最多 65535 字节
+pub struct NoiseOutput<C> {
+    io: C,
+    state: TransportState,
+    remote_key: PublicKey,
+    /// 正在接收的密文帧，包含帧头
+    recv: Vec<u8>,
+    received: usize,
+    /// 已解密尚未读取的明文
+    plaintext: Vec<u8>,
+    plaintext_offset: usize,
+    /// 等待加密的明文
+    pending: Vec<u8>,
+    /// 正在发送的密文帧，包含帧头
+    send: Vec<u8>,
+    sent: usize,
+}
+
+impl<C> NoiseOutput<C> {
+    pub(crate) fn new(io: C, state: TransportState, remote_key: PublicKey) -> Self {
+        NoiseOutput {
+            io,
+            state,
+            remote_key,
+            recv: vec![0u8; LENGTH_PREFIX + MAX_FRAME_LEN],
+            received: 0,
+            plaintext: Vec::new(),
+            plaintext_offset: 0,
+            pending: Vec::new(),
+            send: Vec::new(),
+            sent: 0,
+        }
+    }
+
+    /// 对端经过验证的身份公钥
+    pub fn remote_key(&self) -> &PublicKey {
+        &self.remote_key
+    }
+
+    pub fn get_ref(&self) -> &C {
+        &self.io
+    }
+}
+
+impl<C> fmt::Debug for NoiseOutput<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NoiseOutput")
+            .field("remote_key", &self.remote_key)
+            .finish()
+    }
+}
+
+fn invalid_data(e: snow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl<C: AsyncWrite + Unpin> NoiseOutput<C> {
+    /// 加密并发出缓冲的明文，直到没有待发送的数据
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.sent < self.send.len() {
+                let n = futures::ready!(
+                    Pin::new(&mut self.io).poll_write(cx, &self.send[self.sent..])
+                )?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                self.sent += n;
+                continue;
+            }
+            self.send.clear();
+            self.sent = 0;
+            if self.pending.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            self.send
+                .resize(LENGTH_PREFIX + self.pending.len() + TAG_LEN, 0);
+            let len = self
+                .state
+                .write_message(&self.pending, &mut self.send[LENGTH_PREFIX..])
+                .map_err(invalid_data)?;
+            self.send[..LENGTH_PREFIX].copy_from_slice(&(len as u16).to_be_bytes());
+            self.send.truncate(LENGTH_PREFIX + len);
+            self.pending.clear();
+        }
+    }
+}
+
+impl<C: AsyncRead + Unpin> AsyncRead for NoiseOutput<C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.plaintext_offset < this.plaintext.len() {
+                let available = &this.plaintext[this.plaintext_offset..];
+                let n = available.len().min(buf.len());
+                buf[..n].copy_from_slice(&available[..n]);
+                this.plaintext_offset += n;
+                return Poll::Ready(Ok(n));
+            }
+            let frame_len = if this.received < LENGTH_PREFIX {
+                LENGTH_PREFIX
+            } else {
+                LENGTH_PREFIX + u16::from_be_bytes([this.recv[0], this.recv[1]]) as usize
+            };
+            if this.received < frame_len {
+                let n = futures::ready!(
+                    Pin::new(&mut this.io).poll_read(cx, &mut this.recv[this.received..frame_len])
+                )?;
+                if n == 0 {
+                    if this.received == 0 {
+                        return Poll::Ready(Ok(0));
+                    }
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+                this.received += n;
+                continue;
+            }
+            this.plaintext.resize(frame_len - LENGTH_PREFIX, 0);
+            let len = this
+                .state
+                .read_message(&this.recv[LENGTH_PREFIX..frame_len], &mut this.plaintext)
+                .map_err(invalid_data)?;
+            this.plaintext.truncate(len);
+            this.plaintext_offset = 0;
+            this.received = 0;
+        }
+    }
+}
+
+impl<C: AsyncWrite + Unpin> AsyncWrite for NoiseOutput<C> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.pending.len() >= MAX_PLAINTEXT {
+            futures::ready!(this.poll_send(cx))?;
+        }
+        let n = buf.len().min(MAX_PLAINTEXT - this.pending.len());
+        this.pending.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.io).poll_close(cx)
+    }
+}
@@ -0,0 +1,283 @@
+mod io;
+
+use std::{iter, sync::Arc};
+
+use airio_core::{
+    PeerId, Upgrade, UpgradeInfo,
+    identity::{DecodingError, Keypair, PublicKey},
+};
+use futures::{AsyncRead, AsyncWrite, future::BoxFuture};
+use snow::{HandshakeState, params::NoiseParams};
+
+pub use io::NoiseOutput;
+
+const PATTERN: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+
+/// 签名静态 DH 公钥时的前缀，避免签名被挪作他用
+const STATIC_KEY_DOMAIN: &[u8] = b"airio-noise-static-key:";
+
+/// 载荷中公钥编码前的两字节大端长度
+const KEY_LENGTH_PREFIX: usize = 2;
+
+/// Noise XX 握手的认证升级。
+///
+/// 每个 `Config` 生成一个 X25519 静态密钥，并用身份私钥对其签名。
+/// 握手时双方在加密的载荷中交换身份公钥与签名，验证通过后由身份公钥得到 `PeerId`，
+/// 之后的数据都使用 ChaCha20-Poly1305 加密。
+#[derive(Clone)]
+pub struct Config {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    params: NoiseParams,
+    static_key: snow::Keypair,
+    /// 带长度的身份公钥 protobuf 编码，以及其对静态公钥的签名
+    payload: Vec<u8>,
+}
+
+impl Config {
+    pub fn new(identity: &Keypair) -> Self {
+        let params: NoiseParams = PATTERN.parse().expect("valid noise pattern");
+        let static_key = snow::Builder::new(params.clone())
+            .generate_keypair()
+            .expect("default resolver supports X25519");
+        let key = identity.public().to_protobuf_encoding();
+        let signature = identity.sign(&[STATIC_KEY_DOMAIN, &static_key.public].concat());
+        let mut payload = Vec::with_capacity(KEY_LENGTH_PREFIX + key.len() + signature.len());
+        payload.extend_from_slice(&(key.len() as u16).to_be_bytes());
+        payload.extend_from_slice(&key);
+        payload.extend_from_slice(&signature);
+        Self {
+            inner: Arc::new(Inner {
+                params,
+                static_key,
+                payload,
+            }),
+        }
+    }
+
+    fn builder(&self) -> snow::Builder<'_> {
+        snow::Builder::new(self.inner.params.clone())
+            .local_private_key(&self.inner.static_key.private)
+    }
+
+    /// -> e
+    /// <- e, ee, s, es
+    /// -> s, se
+    async fn handshake_outbound<C>(self, mut socket: C) -> Result<(PeerId, NoiseOutput<C>), Error>
+    where
+        C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut state = self.builder().build_initiator()?;
+        let mut buf = vec![0u8; io::MAX_FRAME_LEN];
+        send(&mut state, &mut socket, &[], &mut buf).await?;
+        let payload = recv(&mut state, &mut socket, &mut buf).await?;
+        let remote_key = verify(&state, payload)?;
+        send(&mut state, &mut socket, &self.inner.payload, &mut buf).await?;
+        finish(state, socket, remote_key)
+    }
+
+    async fn handshake_inbound<C>(self, mut socket: C) -> Result<(PeerId, NoiseOutput<C>), Error>
+    where
+        C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut state = self.builder().build_responder()?;
+        let mut buf = vec![0u8; io::MAX_FRAME_LEN];
+        recv(&mut state, &mut socket, &mut buf).await?;
+        send(&mut state, &mut socket, &self.inner.payload, &mut buf).await?;
+        let payload = recv(&mut state, &mut socket, &mut buf).await?;
+        let remote_key = verify(&state, payload)?;
+        finish(state, socket, remote_key)
+    }
+}
+
+async fn send<C>(
+    state: &mut HandshakeState,
+    socket: &mut C,
+    payload: &[u8],
+    buf: &mut [u8],
+) -> Result<(), Error>
+where
+    C: AsyncWrite + Unpin,
+{
+    let len = state.write_message(payload, buf)?;
+    io::write_frame(socket, &buf[..len]).await?;
+    Ok(())
+}
+
+async fn recv<'a, C>(
+    state: &mut HandshakeState,
+    socket: &mut C,
+    buf: &'a mut [u8],
+) -> Result<&'a [u8], Error>
+where
+    C: AsyncRead + Unpin,
+{
+    let message = io::read_frame(socket).await?;
+    let len = state.read_message(&message, buf)?;
+    Ok(&buf[..len])
+}
+
+/// 验证对端身份公钥对其静态 DH 公钥的签名
+fn verify(state: &HandshakeState, payload: &[u8]) -> Result<PublicKey, Error> {
+    let remote_static = state.get_remote_static().ok_or(Error::InvalidPayload)?;
+    let (len, rest) = payload
+        .split_first_chunk::<KEY_LENGTH_PREFIX>()
+        .ok_or(Error::InvalidPayload)?;
+    let (key, signature) = rest
+        .split_at_checked(u16::from_be_bytes(*len) as usize)
+        .ok_or(Error::InvalidPayload)?;
+    let key = PublicKey::from_protobuf_encoding(key)?;
+    if !key.verify(&[STATIC_KEY_DOMAIN, remote_static].concat(), signature) {
+        return Err(Error::BadSignature);
+    }
+    Ok(key)
+}
+
+fn finish<C>(
+    state: HandshakeState,
+    socket: C,
+    remote_key: PublicKey,
+) -> Result<(PeerId, NoiseOutput<C>), Error> {
+    let peer_id = remote_key.to_peer_id();
+    let transport = state.into_transport_mode()?;
+    tracing::trace!("Noise handshake with {} completed", peer_id);
+    Ok((peer_id, NoiseOutput::new(socket, transport, remote_key)))
+}
+
+impl UpgradeInfo for Config {
+    type Info = &'static str;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once("/noise")
+    }
+}
+
+impl<C> Upgrade<C> for Config
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, NoiseOutput<C>);
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, _: Self::Info) -> Self::Future {
+        Box::pin(self.handshake_inbound(socket))
+    }
+
+    fn upgrade_outbound(self, socket: C, _: Self::Info) -> Self::Future {
+        Box::pin(self.handshake_outbound(socket))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Noise error: {0}")]
+    Noise(#[from] snow::Error),
+    #[error("Invalid handshake payload")]
+    InvalidPayload,
+    #[error("Invalid identity key: {0}")]
+    InvalidKey(#[from] DecodingError),
+    #[error("Static key signature verification failed")]
+    BadSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::transport::memory::Channel;
+    use futures::{AsyncReadExt, AsyncWriteExt, executor::block_on, future::join};
+
+    use super::*;
+
+    #[test]
+    fn authenticates_both_sides() {
+        let (dialer_key, listener_key) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let (outbound, inbound) = block_on(Channel::upgrade_pair(
+            Config::new(&dialer_key),
+            Config::new(&listener_key),
+            "/noise",
+        ));
+        let (remote, outbound) = outbound.unwrap();
+        assert_eq!(remote, listener_key.to_peer_id());
+        assert_eq!(outbound.remote_key(), &listener_key.public());
+        let (remote, inbound) = inbound.unwrap();
+        assert_eq!(remote, dialer_key.to_peer_id());
+        assert_eq!(inbound.remote_key(), &dialer_key.public());
+    }
+
+    /// 签名对象换成另一个静态公钥的配置
+    fn forged(identity: &Keypair) -> Config {
+        let config = Config::new(identity);
+        let other = Config::new(identity);
+        let key = identity.public().to_protobuf_encoding();
+        let signature =
+            identity.sign(&[STATIC_KEY_DOMAIN, &other.inner.static_key.public].concat());
+        let mut payload = (key.len() as u16).to_be_bytes().to_vec();
+        payload.extend_from_slice(&key);
+        payload.extend_from_slice(&signature);
+        Config {
+            inner: Arc::new(Inner {
+                params: config.inner.params.clone(),
+                static_key: snow::Keypair {
+                    private: config.inner.static_key.private.clone(),
+                    public: config.inner.static_key.public.clone(),
+                },
+                payload,
+            }),
+        }
+    }
+
+    #[test]
+    fn rejects_signature_over_other_static_key() {
+        let (honest, forger) = (Keypair::generate_ed25519(), Keypair::generate_ed25519());
+        let (outbound, _) = block_on(Channel::upgrade_pair(
+            Config::new(&honest),
+            forged(&forger),
+            "/noise",
+        ));
+        assert!(matches!(outbound, Err(Error::BadSignature)));
+        let (_, inbound) = block_on(Channel::upgrade_pair(
+            forged(&forger),
+            Config::new(&honest),
+            "/noise",
+        ));
+        assert!(matches!(inbound, Err(Error::BadSignature)));
+    }
+
+    #[test]
+    fn transfers_data_larger_than_a_frame() {
+        let (outbound, inbound) = block_on(Channel::upgrade_pair(
+            Config::new(&Keypair::generate_ed25519()),
+            Config::new(&Keypair::generate_ed25519()),
+            "/noise",
+        ));
+        let ((_, mut outbound), (_, mut inbound)) = (outbound.unwrap(), inbound.unwrap());
+        let data: Vec<u8> = (0..3 * io::MAX_FRAME_LEN + 123).map(|i| i as u8).collect();
+        let received = block_on(async {
+            let send = async {
+                outbound.write_all(&data).await.unwrap();
+                outbound.flush().await.unwrap();
+                // 对端回显后读取
+                let mut echo = vec![0u8; data.len()];
+                outbound.read_exact(&mut echo).await.unwrap();
+                echo
+            };
+            let echo = async {
+                let mut buf = vec![0u8; data.len()];
+                inbound.read_exact(&mut buf).await.unwrap();
+                inbound.write_all(&buf).await.unwrap();
+                inbound.close().await.unwrap();
+            };
+            join(send, echo).await.0
+        });
+        assert!(received == data);
+
+        // 对端关闭后读到 EOF
+        let mut rest = Vec::new();
+        assert_eq!(block_on(outbound.read_to_end(&mut rest)).unwrap(), 0);
+    }
+}
@@ -72,37 +72,35 @@ impl<R> Negotiated<R> {
         if let StateProj::Completed { .. } = this.state.as_mut().project() {
             return Poll::Ready(Ok(()));
         }
-        loop {
-            match mem::replace(&mut *this.state, State::Invalid) {
-                State::Expecting { mut io, protocol } => {
-                    let msg = match Pin::new(&mut io).poll_next(cx)? {
-                        Poll::Ready(Some(msg)) => msg,
-                        Poll::Ready(None) => {
-                            return Poll::Ready(Err(io::Error::new(
-                                io::ErrorKind::UnexpectedEof,
-                                "unexpected end of stream",
-                            )
-                            .into()));
-                        }
-                        Poll::Pending => {
-                            *this.state = State::Expecting { io, protocol };
-                            return Poll::Pending;
-                        }
-                    };
-                    tracing::trace!("Received message: {:?}", msg);
-                    if let Message::Protocol(p) = &msg {
-                        if p.as_ref() == protocol.as_ref() {
-                            tracing::debug!("Negotiated protocol completed: {}", p.as_ref());
-                            *this.state = State::Completed {
-                                io: io.into_inner(),
-                            };
-                            return Poll::Ready(Ok(()));
-                        }
+        match mem::replace(&mut *this.state, State::Invalid) {
+            State::Expecting { mut io, protocol } => {
+                let msg = match Pin::new(&mut io).poll_next(cx)? {
+                    Poll::Ready(Some(msg)) => msg,
+                    Poll::Ready(None) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "unexpected end of stream",
+                        )
+                        .into()));
                     }
-                    return Poll::Ready(Err(NegotiationError::Failed));
+                    Poll::Pending => {
+                        *this.state = State::Expecting { io, protocol };
+                        return Poll::Pending;
+                    }
+                };
+                tracing::trace!("Received message: {:?}", msg);
+                if let Message::Protocol(p) = &msg
+                    && p.as_ref() == protocol.as_ref()
+                {
+                    tracing::debug!("Negotiated protocol completed: {}", p.as_ref());
+                    *this.state = State::Completed {
+                        io: io.into_inner(),
+                    };
+                    return Poll::Ready(Ok(()));
                 }
-                _ => panic!("Negotiated state should not be in Invalid state"),
+                Poll::Ready(Err(NegotiationError::Failed))
             }
+            _ => panic!("Negotiated state should not be in Invalid state"),
         }
     }
 }
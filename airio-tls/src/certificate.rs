@@ -0,0 +1,238 @@
+//! 携带节点身份公钥的证书
+
+use airio_core::{
+    PeerId,
+    identity::{KeyType, Keypair, PublicKey},
+};
+use rustls::{
+    CertificateError,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, UnixTime, alg_id},
+};
+use x509_parser::{
+    der_parser::oid::Oid,
+    oid_registry::{OID_PKCS1_SHA256WITHRSA, OID_SIG_ECDSA_WITH_SHA256, OID_SIG_ED25519},
+    prelude::{ASN1Time, FromDer, X509Certificate},
+};
+
+use crate::Error;
+
+/// 自签名证书的主体名称
+pub(crate) const SUBJECT_NAME: &str = "airio";
+
+pub(crate) fn private_key_der(identity: &Keypair) -> PrivateKeyDer<'static> {
+    PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(identity.to_pkcs8_der()))
+}
+
+/// 身份私钥签发证书时使用的算法，TLS 不支持 secp256k1
+pub(crate) fn signature_algorithm(
+    key_type: KeyType,
+) -> Result<&'static rcgen::SignatureAlgorithm, Error> {
+    match key_type {
+        KeyType::Ed25519 => Ok(&rcgen::PKCS_ED25519),
+        KeyType::Ecdsa => Ok(&rcgen::PKCS_ECDSA_P256_SHA256),
+        KeyType::Rsa => Ok(&rcgen::PKCS_RSA_SHA256),
+        KeyType::Secp256k1 => Err(Error::UnsupportedKey(key_type)),
+    }
+}
+
+/// 与 [`signature_algorithm`] 对应的证书签名算法 OID
+fn signature_oid(key_type: KeyType) -> Option<Oid<'static>> {
+    match key_type {
+        KeyType::Ed25519 => Some(OID_SIG_ED25519),
+        KeyType::Ecdsa => Some(OID_SIG_ECDSA_WITH_SHA256),
+        KeyType::Rsa => Some(OID_PKCS1_SHA256WITHRSA),
+        KeyType::Secp256k1 => None,
+    }
+}
+
+/// 用身份私钥生成自签名证书
+pub(crate) fn generate(identity: &Keypair) -> Result<CertificateDer<'static>, Error> {
+    let algorithm = signature_algorithm(identity.key_type())?;
+    let der = PrivatePkcs8KeyDer::from(identity.to_pkcs8_der());
+    let key_pair = rcgen::KeyPair::from_pkcs8_der_and_sign_algo(&der, algorithm)?;
+    let mut params = rcgen::CertificateParams::new(vec![SUBJECT_NAME.to_string()])?;
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, SUBJECT_NAME);
+    let certificate = params.self_signed(&key_pair)?;
+    Ok(certificate.der().clone())
+}
+
+/// 由证书中的身份公钥得到 `PeerId`，与其他认证升级的推导方式相同
+pub fn peer_id(certificate: &CertificateDer<'_>) -> Result<PeerId, Error> {
+    let (_, cert) =
+        X509Certificate::from_der(certificate).map_err(|_| Error::InvalidCertificate)?;
+    let key = public_key(&cert).ok_or(Error::InvalidCertificate)?;
+    Ok(key.to_peer_id())
+}
+
+/// 证书中的身份公钥
+fn public_key(cert: &X509Certificate<'_>) -> Option<PublicKey> {
+    PublicKey::from_spki_der(cert.public_key().raw).ok()
+}
+
+/// 检查证书链的首个证书是否携带 `identity` 的公钥
+pub(crate) fn check_identity(
+    certificate: &CertificateDer<'_>,
+    identity: &Keypair,
+) -> Result<(), Error> {
+    let (_, cert) =
+        X509Certificate::from_der(certificate).map_err(|_| Error::InvalidCertificate)?;
+    match public_key(&cert) {
+        Some(key) if key == identity.public() => Ok(()),
+        _ => Err(Error::KeyMismatch),
+    }
+}
+
+/// 验证对端的自签名证书：只有一个证书、在有效期内、携带受支持的身份公钥且签名由该公钥签发
+pub(crate) fn verify_self_signed(
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    now: UnixTime,
+) -> Result<(), rustls::Error> {
+    if !intermediates.is_empty() {
+        return Err(rustls::Error::General(
+            "expected a single self-signed certificate".to_string(),
+        ));
+    }
+    let (_, cert) = X509Certificate::from_der(end_entity)
+        .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+    let now = ASN1Time::from_timestamp(now.as_secs() as i64)
+        .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+    let validity = cert.validity();
+    if now < validity.not_before {
+        return Err(rustls::Error::InvalidCertificate(
+            CertificateError::NotValidYet,
+        ));
+    }
+    if now > validity.not_after {
+        return Err(rustls::Error::InvalidCertificate(CertificateError::Expired));
+    }
+    let Some(key) = public_key(&cert).filter(|key| {
+        signature_oid(key.key_type()).is_some_and(|oid| oid == cert.signature_algorithm.algorithm)
+    }) else {
+        return Err(rustls::Error::InvalidCertificate(
+            CertificateError::UnsupportedSignatureAlgorithmContext {
+                signature_algorithm_id: cert.signature_algorithm.algorithm.as_bytes().to_vec(),
+                supported_algorithms: vec![
+                    alg_id::ED25519,
+                    alg_id::ECDSA_SHA256,
+                    alg_id::RSA_PKCS1_SHA256,
+                ],
+            },
+        ));
+    };
+    if !key.verify(cert.tbs_certificate.as_ref(), &cert.signature_value.data) {
+        return Err(rustls::Error::InvalidCertificate(
+            CertificateError::BadSignature,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const RSA_KEY: &str = include_str!("testdata/rsa-2048.pem");
+
+    fn keys() -> [Keypair; 3] {
+        [
+            Keypair::generate_ed25519(),
+            Keypair::generate_ecdsa(),
+            Keypair::from_pkcs8_pem(RSA_KEY).unwrap(),
+        ]
+    }
+
+    fn certificate_error(result: Result<(), rustls::Error>) -> CertificateError {
+        match result {
+            Err(rustls::Error::InvalidCertificate(e)) => e,
+            other => panic!("expected invalid certificate, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn generated_certificate_carries_identity() {
+        for identity in keys() {
+            let certificate = generate(&identity).unwrap();
+            verify_self_signed(&certificate, &[], UnixTime::now()).unwrap();
+            assert_eq!(peer_id(&certificate).unwrap(), identity.to_peer_id());
+            check_identity(&certificate, &identity).unwrap();
+            assert!(matches!(
+                check_identity(&certificate, &Keypair::generate_ed25519()),
+                Err(Error::KeyMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    fn secp256k1_is_unsupported() {
+        let identity = Keypair::generate_secp256k1();
+        assert!(matches!(
+            generate(&identity),
+            Err(Error::UnsupportedKey(KeyType::Secp256k1))
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_certificate() {
+        for identity in keys() {
+            let mut certificate = generate(&identity).unwrap().to_vec();
+            // 改动主体名称，证书仍可解析但签名不再匹配
+            let at = certificate
+                .windows(SUBJECT_NAME.len())
+                .position(|w| w == SUBJECT_NAME.as_bytes())
+                .unwrap();
+            certificate[at] ^= 0x20;
+            let certificate = CertificateDer::from(certificate);
+            assert_eq!(
+                certificate_error(verify_self_signed(&certificate, &[], UnixTime::now())),
+                CertificateError::BadSignature
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_signature_algorithm() {
+        let mut certificate = generate(&Keypair::generate_ecdsa()).unwrap().to_vec();
+        // 把外层的 ecdsa-with-SHA256 改为 ecdsa-with-SHA384，与身份公钥不再对应
+        let oid = OID_SIG_ECDSA_WITH_SHA256;
+        let oid = oid.as_bytes();
+        let at = certificate
+            .windows(oid.len())
+            .rposition(|w| w == oid)
+            .unwrap();
+        certificate[at + oid.len() - 1] = 0x03;
+        let certificate = CertificateDer::from(certificate);
+        assert!(matches!(
+            certificate_error(verify_self_signed(&certificate, &[], UnixTime::now())),
+            CertificateError::UnsupportedSignatureAlgorithmContext { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_certificates() {
+        let certificate = generate(&Keypair::generate_ed25519()).unwrap();
+        assert!(matches!(
+            verify_self_signed(
+                &certificate,
+                std::slice::from_ref(&certificate),
+                UnixTime::now()
+            ),
+            Err(rustls::Error::General(_))
+        ));
+        let epoch = UnixTime::since_unix_epoch(Duration::ZERO);
+        assert_eq!(
+            certificate_error(verify_self_signed(&certificate, &[], epoch)),
+            CertificateError::NotValidYet
+        );
+        let garbage = CertificateDer::from(vec![0x30, 0x03, 0x02, 0x01, 0x00]);
+        assert_eq!(
+            certificate_error(verify_self_signed(&garbage, &[], UnixTime::now())),
+            CertificateError::BadEncoding
+        );
+        assert!(matches!(peer_id(&garbage), Err(Error::InvalidCertificate)));
+    }
+}
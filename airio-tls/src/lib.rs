@@ -0,0 +1,277 @@
+mod certificate;
+mod verifier;
+
+use std::{io, iter, sync::Arc};
+
+use airio_core::{
+    PeerId, Upgrade, UpgradeInfo,
+    identity::{KeyType, Keypair},
+};
+use futures::{AsyncRead, AsyncWrite, future::BoxFuture};
+use futures_rustls::{TlsAcceptor, TlsConnector};
+use rustls::{
+    RootCertStore,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    server::{VerifierBuilderError, WebPkiClientVerifier},
+};
+
+pub use certificate::peer_id;
+pub use futures_rustls::TlsStream;
+pub use rustls;
+
+use crate::verifier::Verifier;
+
+/// 基于 rustls 的 TLS 1.3 认证升级。
+///
+/// 默认使用身份私钥生成的自签名证书（不支持 secp256k1 身份），双方都必须出示证书（双向认证），
+/// 对端的 `PeerId` 由证书中的身份公钥得到，与其他认证升级一致。
+/// 设置 [`Config::root_certificates`] 后改为要求对端证书由指定 CA 签发，
+/// 此时本地证书通常也应换成 CA 签发的证书（[`Config::with_certificate`]）。
+#[derive(Clone)]
+pub struct Config {
+    identity: Arc<Identity>,
+    alpn: Vec<Vec<u8>>,
+    roots: Option<Arc<RootCertStore>>,
+    server_name: Option<ServerName<'static>>,
+}
+
+struct Identity {
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    peer_id: PeerId,
+}
+
+impl Config {
+    /// 使用由 `identity` 生成的自签名证书
+    pub fn new(identity: &Keypair) -> Result<Self, Error> {
+        let certificate = certificate::generate(identity)?;
+        Self::with_certificate(identity, vec![certificate])
+    }
+
+    /// 使用外部签发的证书链，首个证书必须携带 `identity` 的公钥
+    pub fn with_certificate(
+        identity: &Keypair,
+        chain: Vec<CertificateDer<'static>>,
+    ) -> Result<Self, Error> {
+        certificate::signature_algorithm(identity.key_type())?;
+        let end_entity = chain.first().ok_or(Error::InvalidCertificate)?;
+        certificate::check_identity(end_entity, identity)?;
+        let peer_id = certificate::peer_id(end_entity)?;
+        Ok(Self {
+            identity: Arc::new(Identity {
+                chain,
+                key: certificate::private_key_der(identity),
+                peer_id,
+            }),
+            alpn: Vec::new(),
+            roots: None,
+            server_name: None,
+        })
+    }
+
+    /// 按优先级排列的 ALPN 协议，双方都设置时必须至少有一个相同
+    pub fn alpn<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.alpn = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 只接受由这些 CA 签发的对端证书
+    pub fn root_certificates(mut self, roots: RootCertStore) -> Self {
+        self.roots = Some(Arc::new(roots));
+        self
+    }
+
+    /// 出站连接发送的 SNI，设置了 CA 时还要求服务端证书与该名称匹配
+    pub fn server_name(mut self, server_name: ServerName<'static>) -> Self {
+        self.server_name = Some(server_name);
+        self
+    }
+
+    /// 本地证书对应的 `PeerId`
+    pub fn local_peer_id(&self) -> PeerId {
+        self.identity.peer_id
+    }
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(rustls::crypto::ring::default_provider())
+    }
+
+    fn verifier(&self, provider: &CryptoProvider) -> Verifier {
+        Verifier::new(
+            self.roots.clone(),
+            self.server_name.clone(),
+            provider.signature_verification_algorithms,
+        )
+    }
+
+    fn client_config(&self) -> Result<rustls::ClientConfig, Error> {
+        let provider = Self::provider();
+        let verifier = self.verifier(&provider);
+        let mut config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_client_auth_cert(self.identity.chain.clone(), self.identity.key.clone_key())?;
+        config.alpn_protocols = self.alpn.clone();
+        Ok(config)
+    }
+
+    fn server_config(&self) -> Result<rustls::ServerConfig, Error> {
+        let provider = Self::provider();
+        let verifier: Arc<dyn rustls::server::danger::ClientCertVerifier> = match &self.roots {
+            None => Arc::new(self.verifier(&provider)),
+            Some(roots) => {
+                WebPkiClientVerifier::builder_with_provider(roots.clone(), provider.clone())
+                    .build()?
+            }
+        };
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(self.identity.chain.clone(), self.identity.key.clone_key())?;
+        config.alpn_protocols = self.alpn.clone();
+        Ok(config)
+    }
+
+    async fn handshake_outbound<C>(self, socket: C) -> Result<(PeerId, TlsStream<C>), Error>
+    where
+        C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let connector = TlsConnector::from(Arc::new(self.client_config()?));
+        let server_name = self.server_name.clone().unwrap_or_else(|| {
+            ServerName::try_from(certificate::SUBJECT_NAME).expect("valid name")
+        });
+        let stream = connector.connect(server_name, socket).await?;
+        let peer_id = remote_peer_id(stream.get_ref().1.peer_certificates())?;
+        Ok((peer_id, TlsStream::Client(stream)))
+    }
+
+    async fn handshake_inbound<C>(self, socket: C) -> Result<(PeerId, TlsStream<C>), Error>
+    where
+        C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let acceptor = TlsAcceptor::from(Arc::new(self.server_config()?));
+        let stream = acceptor.accept(socket).await?;
+        let peer_id = remote_peer_id(stream.get_ref().1.peer_certificates())?;
+        Ok((peer_id, TlsStream::Server(stream)))
+    }
+}
+
+fn remote_peer_id(certificates: Option<&[CertificateDer<'static>]>) -> Result<PeerId, Error> {
+    let end_entity = certificates
+        .and_then(|certificates| certificates.first())
+        .ok_or(Error::MissingCertificate)?;
+    peer_id(end_entity)
+}
+
+impl UpgradeInfo for Config {
+    type Info = &'static str;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once("/tls")
+    }
+}
+
+impl<C> Upgrade<C> for Config
+where
+    C: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = (PeerId, TlsStream<C>);
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, _: Self::Info) -> Self::Future {
+        Box::pin(self.handshake_inbound(socket))
+    }
+
+    fn upgrade_outbound(self, socket: C, _: Self::Info) -> Self::Future {
+        Box::pin(self.handshake_outbound(socket))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("TLS error: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("Failed to generate certificate: {0}")]
+    Certificate(#[from] rcgen::Error),
+    #[error("Failed to build certificate verifier: {0}")]
+    Verifier(#[from] VerifierBuilderError),
+    #[error("Invalid certificate")]
+    InvalidCertificate,
+    #[error("Certificate does not carry the identity key")]
+    KeyMismatch,
+    #[error("Unsupported identity key type: {0}")]
+    UnsupportedKey(KeyType),
+    #[error("Remote did not present a certificate")]
+    MissingCertificate,
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::transport::memory::Channel;
+    use futures::{AsyncReadExt, AsyncWriteExt, executor::block_on, future::join};
+
+    use super::*;
+
+    #[test]
+    fn mutual_authentication_with_alpn() {
+        let (dialer_key, listener_key) = (Keypair::generate_ed25519(), Keypair::generate_ecdsa());
+        let dialer = Config::new(&dialer_key).unwrap().alpn(["/b", "/a"]);
+        let listener = Config::new(&listener_key).unwrap().alpn(["/a", "/c"]);
+        assert_eq!(dialer.local_peer_id(), dialer_key.to_peer_id());
+        let (outbound, inbound) = block_on(Channel::upgrade_pair(dialer, listener, "/tls"));
+        let (remote, mut outbound) = outbound.unwrap();
+        assert_eq!(remote, listener_key.to_peer_id());
+        let (remote, mut inbound) = inbound.unwrap();
+        assert_eq!(remote, dialer_key.to_peer_id());
+        assert_eq!(outbound.get_ref().1.alpn_protocol(), Some(&b"/a"[..]));
+        assert_eq!(inbound.get_ref().1.alpn_protocol(), Some(&b"/a"[..]));
+
+        let received = block_on(async {
+            let send = async {
+                outbound.write_all(b"hello").await.unwrap();
+                outbound.flush().await.unwrap();
+            };
+            let receive = async {
+                let mut buf = [0u8; 5];
+                inbound.read_exact(&mut buf).await.unwrap();
+                buf
+            };
+            join(send, receive).await.1
+        });
+        assert_eq!(&received, b"hello");
+    }
+
+    #[test]
+    fn rejects_alpn_mismatch() {
+        let dialer = Config::new(&Keypair::generate_ed25519())
+            .unwrap()
+            .alpn(["/a"]);
+        let listener = Config::new(&Keypair::generate_ed25519())
+            .unwrap()
+            .alpn(["/b"]);
+        let (outbound, inbound) = block_on(Channel::upgrade_pair(dialer, listener, "/tls"));
+        assert!(outbound.is_err());
+        assert!(matches!(
+            inbound,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_identity() {
+        assert!(matches!(
+            Config::new(&Keypair::generate_secp256k1()),
+            Err(Error::UnsupportedKey(KeyType::Secp256k1))
+        ));
+    }
+}
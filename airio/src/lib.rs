@@ -3,6 +3,12 @@ pub use airio_core as core;
 #[cfg(feature = "identify")]
 pub use airio_identify as identify;
 
+#[cfg(feature = "noise")]
+pub use airio_noise as noise;
+
+#[cfg(feature = "tls")]
+pub use airio_tls as tls;
+
 #[cfg(feature = "muxing")]
 pub use airio_muxing as muxing;
 
@@ -14,3 +20,12 @@ pub use airio_tcp as tcp;
 
 #[cfg(feature = "ws")]
 pub use airio_ws as ws;
+
+#[cfg(feature = "uds")]
+pub use airio_uds as uds;
+
+#[cfg(feature = "dns")]
+pub use airio_dns as dns;
+
+#[cfg(feature = "proxy")]
+pub use airio_proxy as proxy;
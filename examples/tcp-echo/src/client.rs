@@ -1,9 +1,8 @@
-use std::net::SocketAddr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use airio::core::Transport;
+use airio::core::identity::KeyStore;
 use airio::core::muxing::StreamMuxerExt;
-use airio::identify::ed25519::SigningKey;
+use airio::core::{Multiaddr, Transport};
 use airio::{identify, muxing, tcp};
 use futures::{AsyncReadExt, AsyncWriteExt, future};
 
@@ -19,12 +18,15 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Starting TCP Echo Example");
 
-    let addr = "0.0.0.0:8088".parse::<SocketAddr>()?;
+    let addr = "/ip4/0.0.0.0/tcp/8088".parse::<Multiaddr>()?;
 
-    let key: [u8; 32] = rand::random();
-    let local_key = SigningKey::from_bytes(&key);
+    let mut key_store = KeyStore::new("tcp-echo-client.key");
+    if let Ok(passphrase) = std::env::var("AIRIO_KEY_PASSPHRASE") {
+        key_store = key_store.passphrase(passphrase);
+    }
+    let local_key = key_store.load_or_generate()?;
 
-    let identify_upgrade = identify::Config::new(local_key.verifying_key());
+    let identify_upgrade = identify::Config::new(local_key);
     let muxing_upgrade = muxing::Config::new();
 
     let tcp = tcp::Config::new()
@@ -33,7 +35,7 @@ async fn main() -> anyhow::Result<()> {
         .multiplex(muxing_upgrade);
 
     tracing::info!("Listener is ready and listening on {}", addr);
-    let (peer, mut muxer) = tcp.connect(addr).unwrap().await.unwrap();
+    let (peer, mut muxer) = tcp.connect(addr.clone()).unwrap().await.unwrap();
     tracing::info!("Client connected to {}, Peer:{}", addr, peer);
 
     let stream = future::poll_fn(|cx| muxer.poll_outbound_unpin(cx))
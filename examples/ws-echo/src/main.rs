@@ -1,10 +1,9 @@
+use airio::core::identity::KeyStore;
 use airio::core::muxing::StreamMuxerExt;
-use airio::core::{ListenerEvent, Transport};
-use airio::identify::ed25519::SigningKey;
+use airio::core::{ListenerEvent, Multiaddr, Transport};
 use airio::{identify, muxing, ws};
 use futures::channel::mpsc;
 use futures::{AsyncReadExt, AsyncWriteExt, StreamExt, future};
-use std::net::SocketAddr;
 use std::task::Poll;
 
 #[tokio::main]
@@ -19,12 +18,15 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Starting TCP Echo Example");
 
-    let addr = "0.0.0.0:8088".parse::<SocketAddr>()?;
+    let addr = "/ip4/0.0.0.0/tcp/8088/ws".parse::<Multiaddr>()?;
 
-    let key: [u8; 32] = rand::random();
-    let local_key = SigningKey::from_bytes(&key);
+    let mut key_store = KeyStore::new("ws-echo-server.key");
+    if let Ok(passphrase) = std::env::var("AIRIO_KEY_PASSPHRASE") {
+        key_store = key_store.passphrase(passphrase);
+    }
+    let local_key = key_store.load_or_generate()?;
 
-    let identify_upgrade = identify::Config::new(local_key.verifying_key());
+    let identify_upgrade = identify::Config::new(local_key);
     let muxing_upgrade = muxing::Config::new();
 
     let tcp = ws::Config::new()
@@ -37,81 +39,76 @@ async fn main() -> anyhow::Result<()> {
     let server_fut = tokio::spawn(async move {
         while let Some(event) = listener.next().await {
             tracing::info!("Listener event: {:?}", event);
-            match event {
-                ListenerEvent::Incoming {
-                    local_addr,
-                    remote_addr,
-                    upgrade,
-                } => {
-                    tokio::spawn(async move {
-                        tracing::debug!(
-                            "Incoming connection from {} to {}",
-                            remote_addr,
-                            local_addr
-                        );
-                        let (peer, mut muxer) = upgrade.await.unwrap();
-                        tracing::debug!(
-                            "Peer({}), Upgraded stream from {} to {}",
-                            peer,
-                            remote_addr,
-                            local_addr
-                        );
-                        let (mut sender, mut receiver) = mpsc::channel(10);
+            if let ListenerEvent::Incoming {
+                local_addr,
+                remote_addr,
+                upgrade,
+                ..
+            } = event
+            {
+                tokio::spawn(async move {
+                    tracing::debug!("Incoming connection from {} to {}", remote_addr, local_addr);
+                    let (peer, mut muxer) = upgrade.await.unwrap();
+                    tracing::debug!(
+                        "Peer({}), Upgraded stream from {} to {}",
+                        peer,
+                        remote_addr,
+                        local_addr
+                    );
+                    let (mut sender, mut receiver) = mpsc::channel(10);
 
-                        let muxer_fut = future::poll_fn(move |cx| {
-                            match muxer.poll_inbound_unpin(cx) {
-                                Poll::Ready(Ok(stream)) => {
-                                    sender.try_send(stream).unwrap();
-                                    cx.waker().wake_by_ref();
-                                    return Poll::Pending;
-                                }
-                                Poll::Ready(Err(e)) => {
-                                    tracing::error!("Error polling inbound stream: {:?}", e);
-                                    return Poll::Ready(());
-                                }
-                                Poll::Pending => {}
+                    let muxer_fut = future::poll_fn(move |cx| {
+                        match muxer.poll_inbound_unpin(cx) {
+                            Poll::Ready(Ok(stream)) => {
+                                sender.try_send(stream).unwrap();
+                                cx.waker().wake_by_ref();
+                                return Poll::Pending;
                             }
-                            match muxer.poll_unpin(cx) {
-                                Poll::Ready(Ok(stream)) => {
-                                    tracing::info!(
-                                        "Accepted stream from: {} to: {}, stream: {:?}",
-                                        remote_addr,
-                                        local_addr,
-                                        stream
-                                    );
-                                    cx.waker().wake_by_ref();
-                                    Poll::Pending
-                                }
-                                Poll::Ready(Err(e)) => {
-                                    tracing::error!("Error polling inbound stream: {:?}", e);
-                                    return Poll::Ready(());
-                                }
-                                Poll::Pending => Poll::Pending,
+                            Poll::Ready(Err(e)) => {
+                                tracing::error!("Error polling inbound stream: {:?}", e);
+                                return Poll::Ready(());
+                            }
+                            Poll::Pending => {}
+                        }
+                        match muxer.poll_unpin(cx) {
+                            Poll::Ready(Ok(stream)) => {
+                                tracing::info!(
+                                    "Accepted stream from: {} to: {}, stream: {:?}",
+                                    remote_addr,
+                                    local_addr,
+                                    stream
+                                );
+                                cx.waker().wake_by_ref();
+                                Poll::Pending
                             }
-                        });
-                        tokio::spawn(muxer_fut);
+                            Poll::Ready(Err(e)) => {
+                                tracing::error!("Error polling inbound stream: {:?}", e);
+                                Poll::Ready(())
+                            }
+                            Poll::Pending => Poll::Pending,
+                        }
+                    });
+                    tokio::spawn(muxer_fut);
+                    loop {
+                        let stream = receiver.next().await.unwrap();
+                        let (mut reader, mut writer) = stream.split();
+                        let mut buf = vec![0; 1024];
                         loop {
-                            let stream = receiver.next().await.unwrap();
-                            let (mut reader, mut writer) = stream.split();
-                            let mut buf = vec![0; 1024];
-                            loop {
-                                match reader.read(&mut buf).await {
-                                    Ok(0) => break, // EOF
-                                    Ok(n) => {
-                                        if writer.write_all(&buf[..n]).await.is_err() {
-                                            break; // Write error
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Read error: {}", e);
-                                        break;
+                            match reader.read(&mut buf).await {
+                                Ok(0) => break, // EOF
+                                Ok(n) => {
+                                    if writer.write_all(&buf[..n]).await.is_err() {
+                                        break; // Write error
                                     }
                                 }
+                                Err(e) => {
+                                    tracing::error!("Read error: {}", e);
+                                    break;
+                                }
                             }
                         }
-                    });
-                }
-                _ => {}
+                    }
+                });
             }
         }
     });
@@ -87,7 +87,7 @@ where
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UpgradeConfig(Config);
 
 impl From<Config> for UpgradeConfig {
@@ -96,12 +96,6 @@ impl From<Config> for UpgradeConfig {
     }
 }
 
-impl Default for UpgradeConfig {
-    fn default() -> Self {
-        UpgradeConfig(Config::default())
-    }
-}
-
 impl UpgradeInfo for UpgradeConfig {
     type Info = &'static str;
     type InfoIter = iter::Once<Self::Info>;
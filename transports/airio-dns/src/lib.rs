@@ -0,0 +1,388 @@
+mod resolver;
+
+use std::{
+    error, io,
+    net::IpAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use airio_core::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    multiaddr::Protocol,
+    transport::{Listener, ListenerHandle},
+};
+use futures::{
+    FutureExt, Stream,
+    future::{self, BoxFuture},
+};
+
+pub use resolver::{CachingResolver, Resolver, StaticResolver, SystemResolver};
+
+/// 解析 `/dns`、`/dns4`、`/dns6` 地址的 Transport 包装。
+///
+/// 主机名被解析为 IP 后交给内部 Transport 逐个尝试连接，原始主机名以 `/sni/<host>`
+/// 的形式插入到端口之后，供 WebSocket 的 `Host` 头以及 TLS/QUIC 的 SNI 使用，
+/// 例如 `/dns4/example.com/tcp/443/ws` 会被改写为 `/ip4/1.2.3.4/tcp/443/sni/example.com/ws`。
+/// 其他地址直接交给内部 Transport。
+#[derive(Debug)]
+pub struct Config<T, R = SystemResolver> {
+    inner: Arc<T>,
+    resolver: Arc<R>,
+}
+
+impl<T, R> Clone for Config<T, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl<T> Config<T> {
+    /// 使用系统解析器包装 `inner`
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+}
+
+impl<T, R> Config<T, R> {
+    /// 替换解析器
+    pub fn resolver<R2>(self, resolver: R2) -> Config<T, R2> {
+        Config {
+            inner: self.inner,
+            resolver: Arc::new(resolver),
+        }
+    }
+}
+
+impl<T, R> Transport for Config<T, R>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Error: Send + 'static,
+    T::Dialer: Send + 'static,
+    T::Output: Send + 'static,
+    R: Resolver,
+{
+    type Output = T::Output;
+    type Error = Error<T::Error>;
+    type Dialer = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type ListenerUpgrade = future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Self::Error>;
+    type Listener = ListenStream<T>;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .inner
+            .listen(addr)
+            .map_err(|e| e.map(Error::Transport))?;
+        Ok(ListenStream { inner: listener })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let Some((host, family)) = dns_host(&addr) else {
+            let dialer = self
+                .inner
+                .connect(addr)
+                .map_err(|e| e.map(Error::Transport))?;
+            return Ok(dialer.map(|r| r.map_err(Error::Transport)).boxed());
+        };
+        let host = host.to_owned();
+        let lookup = self.resolver.lookup(&host);
+        let inner = self.inner.clone();
+        Ok(async move {
+            let ips = lookup
+                .await
+                .map_err(|e| Error::Resolve(host.clone(), e))?
+                .into_iter()
+                .filter(|ip| family.matches(ip))
+                .collect::<Vec<_>>();
+            tracing::debug!("Resolved {} to {:?}", host, ips);
+            let mut errors = Vec::new();
+            for ip in ips {
+                let resolved = resolved_addr(&addr, ip, &host);
+                let result = match inner.connect(resolved.clone()) {
+                    Ok(dialer) => dialer.await.map_err(TransportError::Other),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(output) => return Ok(output),
+                    Err(e) => {
+                        tracing::debug!("Dialing {} via {} failed: {}", host, resolved, e);
+                        errors.push((resolved, e));
+                    }
+                }
+            }
+            if errors.is_empty() {
+                return Err(Error::NoAddresses(host));
+            }
+            Err(Error::AllFailed(host, errors))
+        }
+        .boxed())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Family {
+    Any,
+    V4,
+    V6,
+}
+
+impl Family {
+    fn matches(&self, ip: &IpAddr) -> bool {
+        match self {
+            Family::Any => true,
+            Family::V4 => ip.is_ipv4(),
+            Family::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// 地址以 `/dns`、`/dns4` 或 `/dns6` 开头时返回主机名以及地址族
+fn dns_host(addr: &Multiaddr) -> Option<(&str, Family)> {
+    match addr.protocols().first()? {
+        Protocol::Dns(host) => Some((host, Family::Any)),
+        Protocol::Dns4(host) => Some((host, Family::V4)),
+        Protocol::Dns6(host) => Some((host, Family::V6)),
+        _ => None,
+    }
+}
+
+/// 将首段替换为解析出的 IP，并在端口之后插入 `/sni/<host>`（已存在时保持不变）
+fn resolved_addr(addr: &Multiaddr, ip: IpAddr, host: &str) -> Multiaddr {
+    let mut resolved = Multiaddr::from(ip);
+    let mut rest = addr.iter().skip(1).peekable();
+    let has_sni = addr.iter().any(|p| matches!(p, Protocol::Sni(_)));
+    if let Some(port @ (Protocol::Tcp(_) | Protocol::Udp(_))) = rest.peek() {
+        resolved.push((*port).clone());
+        rest.next();
+        if !has_sni {
+            resolved.push(Protocol::Sni(host.to_owned()));
+        }
+    }
+    resolved.extend(rest.cloned());
+    resolved
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<E> {
+    #[error("Failed to resolve {0}: {1}")]
+    Resolve(String, io::Error),
+    #[error("No addresses found for {0}")]
+    NoAddresses(String),
+    /// 解析出的地址全部连接失败，按尝试的先后顺序列出
+    #[error("All {count} addresses resolved for {0} failed", count = .1.len())]
+    AllFailed(String, Vec<(Multiaddr, TransportError<E>)>),
+    #[error(transparent)]
+    Transport(E),
+}
+
+/// [`Config`] 的监听流，监听直接交给内部 Transport
+#[pin_project::pin_project]
+pub struct ListenStream<T: Transport> {
+    #[pin]
+    inner: T::Listener,
+}
+
+impl<T> Stream for ListenStream<T>
+where
+    T: Transport,
+    T::Error: error::Error,
+{
+    type Item = ListenerEvent<
+        future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Error<T::Error>>,
+        Error<T::Error>,
+    >;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx).map(|event| {
+            event.map(|event| {
+                event
+                    .map_upgrade_err(Error::Transport as fn(_) -> _)
+                    .map_err(Error::Transport)
+            })
+        })
+    }
+}
+
+impl<T> Listener for ListenStream<T>
+where
+    T: Transport,
+    T::Error: error::Error,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        sync::Mutex,
+    };
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// 记录拨号地址的 Transport：不支持 IPv6，只有 `reachable` 可以连通，连接的输出即拨号地址
+    #[derive(Default)]
+    struct MockTransport {
+        reachable: Option<IpAddr>,
+        dialed: Arc<Mutex<Vec<Multiaddr>>>,
+    }
+
+    struct MockListener(ListenerHandle);
+
+    impl Stream for MockListener {
+        type Item = ListenerEvent<future::Ready<io::Result<Multiaddr>>, io::Error>;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(None)
+        }
+    }
+
+    impl Listener for MockListener {
+        fn handle(&self) -> &ListenerHandle {
+            &self.0
+        }
+    }
+
+    impl Transport for MockTransport {
+        type Output = Multiaddr;
+        type Error = io::Error;
+        type Dialer = future::Ready<io::Result<Multiaddr>>;
+        type ListenerUpgrade = future::Ready<io::Result<Multiaddr>>;
+        type Listener = MockListener;
+
+        fn listen(&self, _: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+            Ok(MockListener(ListenerHandle::new()))
+        }
+
+        fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+            self.dialed.lock().unwrap().push(addr.clone());
+            let ip = match addr.protocols().first() {
+                Some(Protocol::Ip4(ip)) => IpAddr::V4(*ip),
+                _ => return Err(TransportError::Unsupported(addr)),
+            };
+            if self.reachable == Some(ip) {
+                Ok(future::ok(addr))
+            } else {
+                Ok(future::err(io::ErrorKind::ConnectionRefused.into()))
+            }
+        }
+    }
+
+    const V4: IpAddr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+    const V4_OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(5, 6, 7, 8));
+    const V6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+    fn transport(
+        reachable: Option<IpAddr>,
+    ) -> (
+        Config<MockTransport, StaticResolver>,
+        Arc<Mutex<Vec<Multiaddr>>>,
+    ) {
+        let inner = MockTransport {
+            reachable,
+            ..Default::default()
+        };
+        let dialed = inner.dialed.clone();
+        let resolver = StaticResolver::new()
+            .insert("example.com", V6)
+            .insert("example.com", V4_OTHER)
+            .insert("example.com", V4);
+        (Config::new(inner).resolver(resolver), dialed)
+    }
+
+    fn dial(
+        transport: &Config<MockTransport, StaticResolver>,
+        addr: &str,
+    ) -> Result<Multiaddr, Error<io::Error>> {
+        block_on(transport.connect(addr.parse().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn rewrites_dns_address_with_sni() {
+        let (transport, dialed) = transport(Some(V4));
+        let output = dial(&transport, "/dns4/example.com/tcp/443/ws").unwrap();
+        assert_eq!(
+            output,
+            "/ip4/1.2.3.4/tcp/443/sni/example.com/ws".parse().unwrap()
+        );
+        // `/dns4` 只尝试 IPv4 地址
+        assert_eq!(
+            *dialed.lock().unwrap(),
+            vec![
+                "/ip4/5.6.7.8/tcp/443/sni/example.com/ws".parse().unwrap(),
+                output
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_existing_sni() {
+        let (transport, _) = transport(Some(V4));
+        let output = dial(&transport, "/dns/example.com/tcp/443/sni/other.org/wss").unwrap();
+        assert_eq!(
+            output,
+            "/ip4/1.2.3.4/tcp/443/sni/other.org/wss".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn passes_through_ip_address() {
+        let (transport, dialed) = transport(Some(V4));
+        let output = dial(&transport, "/ip4/1.2.3.4/tcp/80").unwrap();
+        assert_eq!(output, "/ip4/1.2.3.4/tcp/80".parse().unwrap());
+        assert_eq!(dialed.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reports_every_failed_address() {
+        let (transport, _) = transport(None);
+        let Err(Error::AllFailed(host, errors)) = dial(&transport, "/dns/example.com/tcp/443")
+        else {
+            panic!("expected AllFailed");
+        };
+        assert_eq!(host, "example.com");
+        let addrs = errors
+            .iter()
+            .map(|(addr, _)| addr.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addrs,
+            [
+                "/ip6/::1/tcp/443/sni/example.com",
+                "/ip4/5.6.7.8/tcp/443/sni/example.com",
+                "/ip4/1.2.3.4/tcp/443/sni/example.com",
+            ]
+        );
+        assert!(matches!(errors[0].1, TransportError::Unsupported(_)));
+        assert!(matches!(
+            &errors[1].1,
+            TransportError::Other(e) if e.kind() == io::ErrorKind::ConnectionRefused
+        ));
+    }
+
+    #[test]
+    fn no_addresses() {
+        let (transport, dialed) = transport(Some(V4));
+        let transport = transport.resolver(StaticResolver::new().insert("v4.example.com", V4));
+        assert!(matches!(
+            dial(&transport, "/dns6/v4.example.com/tcp/443"),
+            Err(Error::NoAddresses(host)) if host == "v4.example.com"
+        ));
+        assert!(matches!(
+            dial(&transport, "/dns/unknown.example.com/tcp/443"),
+            Err(Error::Resolve(host, _)) if host == "unknown.example.com"
+        ));
+        assert!(dialed.lock().unwrap().is_empty());
+    }
+}
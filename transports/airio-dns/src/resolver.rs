@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    FutureExt, TryFutureExt,
+    future::{self, BoxFuture},
+};
+
+/// 主机名解析器，返回主机名对应的全部 IP 地址
+pub trait Resolver: Send + Sync + 'static {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>>;
+}
+
+impl<R: Resolver + ?Sized> Resolver for Arc<R> {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>> {
+        (**self).lookup(host)
+    }
+}
+
+/// 使用操作系统解析器（`getaddrinfo`）
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>> {
+        tokio::net::lookup_host((host.to_owned(), 0))
+            .map_ok(|addrs| addrs.map(|addr| addr.ip()).collect())
+            .boxed()
+    }
+}
+
+/// 基于静态表的解析器，适用于测试或固定的主机映射
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为主机名添加一个地址，可多次调用添加多个地址
+    pub fn insert(mut self, host: impl Into<String>, addr: IpAddr) -> Self {
+        self.hosts.entry(host.into()).or_default().push(addr);
+        self
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>> {
+        let result =
+            self.hosts.get(host).cloned().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("Unknown host {host}"))
+            });
+        future::ready(result).boxed()
+    }
+}
+
+/// 缓存的解析结果以及过期时间
+type Cache = Arc<Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>>;
+
+/// 缓存解析结果的解析器，结果在 `ttl` 内有效，解析失败不会被缓存
+#[derive(Debug, Clone)]
+pub struct CachingResolver<R> {
+    inner: R,
+    ttl: Duration,
+    cache: Cache,
+}
+
+impl<R> CachingResolver<R> {
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        self.cache.lock().expect("dns cache poisoned").clear();
+    }
+}
+
+impl<R: Resolver> Resolver for CachingResolver<R> {
+    fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>> {
+        {
+            let mut cache = self.cache.lock().expect("dns cache poisoned");
+            match cache.get(host) {
+                Some((expires, addrs)) if *expires > Instant::now() => {
+                    tracing::trace!("DNS cache hit for {}", host);
+                    return future::ok(addrs.clone()).boxed();
+                }
+                Some(_) => {
+                    cache.remove(host);
+                }
+                None => {}
+            }
+        }
+        let cache = self.cache.clone();
+        let host = host.to_owned();
+        let ttl = self.ttl;
+        self.inner
+            .lookup(&host)
+            .inspect_ok(move |addrs| {
+                cache
+                    .lock()
+                    .expect("dns cache poisoned")
+                    .insert(host, (Instant::now() + ttl, addrs.clone()));
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// 统计查询次数的解析器
+    #[derive(Clone, Default)]
+    struct Counting {
+        inner: StaticResolver,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl Resolver for Counting {
+        fn lookup(&self, host: &str) -> BoxFuture<'static, io::Result<Vec<IpAddr>>> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            self.inner.lookup(host)
+        }
+    }
+
+    const ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+    #[test]
+    fn static_resolver() {
+        let resolver = StaticResolver::new().insert("example.com", ADDR);
+        assert_eq!(block_on(resolver.lookup("example.com")).unwrap(), [ADDR]);
+        let error = block_on(resolver.lookup("unknown")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn caches_until_ttl_expires() {
+        let counting = Counting {
+            inner: StaticResolver::new().insert("example.com", ADDR),
+            ..Default::default()
+        };
+        let lookups = counting.lookups.clone();
+        let resolver = CachingResolver::new(counting, Duration::from_millis(50));
+
+        assert_eq!(block_on(resolver.lookup("example.com")).unwrap(), [ADDR]);
+        assert_eq!(block_on(resolver.lookup("example.com")).unwrap(), [ADDR]);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(block_on(resolver.lookup("example.com")).unwrap(), [ADDR]);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        resolver.clear();
+        block_on(resolver.lookup("example.com")).unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn does_not_cache_failures() {
+        let counting = Counting::default();
+        let lookups = counting.lookups.clone();
+        let resolver = CachingResolver::new(counting, Duration::from_secs(60));
+        assert!(block_on(resolver.lookup("unknown")).is_err());
+        assert!(block_on(resolver.lookup("unknown")).is_err());
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,55 @@
+//! HTTP CONNECT 客户端握手（RFC 9110 第 9.3.6 节）
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Credentials, Error, Target};
+
+/// 响应头的最大长度
+const MAX_RESPONSE_LEN: usize = 8192;
+const MAX_HEADERS: usize = 64;
+
+/// 发送 CONNECT 请求，代理返回 2xx 后 `stream` 即为到目标的隧道
+pub(crate) async fn connect<S, E>(
+    stream: &mut S,
+    target: &Target,
+    credentials: Option<&Credentials>,
+) -> Result<(), Error<E>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let authority = match target {
+        Target::Ip(addr) => addr.to_string(),
+        Target::Domain(host, port) => format!("{host}:{port}"),
+    };
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some(credentials) = credentials {
+        let token = STANDARD.encode(format!("{}:{}", credentials.username, credentials.password));
+        request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+
+    // 逐字节读取，避免读走隧道中属于目标的数据
+    let mut response = Vec::with_capacity(128);
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_LEN {
+            return Err(Error::InvalidResponse("HTTP response header too long"));
+        }
+        stream.read_exact(&mut byte).await?;
+        response.push(byte[0]);
+    }
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut headers);
+    match parsed.parse(&response) {
+        Ok(httparse::Status::Complete(_)) => {}
+        _ => return Err(Error::InvalidResponse("malformed HTTP response")),
+    }
+    match parsed.code {
+        Some(code) if (200..300).contains(&code) => Ok(()),
+        Some(code) => Err(Error::HttpStatus(code)),
+        None => Err(Error::InvalidResponse("malformed HTTP response")),
+    }
+}
@@ -0,0 +1,640 @@
+mod http;
+mod socks5;
+
+use std::{
+    error, fmt, io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use airio_core::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    multiaddr::Protocol,
+    transport::{Listener, ListenerHandle},
+};
+use airio_dns::{Resolver, SystemResolver};
+use futures::{
+    AsyncRead, AsyncWrite, FutureExt, Stream,
+    future::{self, BoxFuture},
+};
+
+/// 通过代理服务器拨号的 Transport 包装。
+///
+/// 内部 Transport 连接到代理服务器的地址，随后通过 SOCKS5 或 HTTP CONNECT 建立到目标地址的隧道，
+/// 隧道建立后内部 Transport 的输出原样返回，可以继续交给 `upgrade::Builder`。
+/// 目标地址只支持 `/ip4`、`/ip6`、`/dns`、`/dns4`、`/dns6` 加 `/tcp` 的形式。
+/// 监听直接交给内部 Transport。
+///
+/// SOCKS5 关闭 [`Config::remote_dns`] 时由 `R` 在本地解析主机名，
+/// 默认的 [`SystemResolver`] 需要在 Tokio 运行时中使用，可通过 [`Config::resolver`] 替换。
+pub struct Config<T, R = SystemResolver> {
+    inner: Arc<T>,
+    proxy: Arc<Proxy>,
+    resolver: Arc<R>,
+}
+
+struct Proxy {
+    addr: Multiaddr,
+    kind: Kind,
+    credentials: Option<Credentials>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Socks5 { remote_dns: bool },
+    HttpConnect,
+}
+
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+impl<T, R> Clone for Config<T, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            proxy: self.proxy.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl<T, R> fmt::Debug for Config<T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("proxy", &self.proxy.addr)
+            .field("kind", &self.proxy.kind)
+            .field("credentials", &self.proxy.credentials.is_some())
+            .finish()
+    }
+}
+
+impl<T> Config<T> {
+    /// 通过 `proxy_addr` 上的 SOCKS5 服务器拨号，默认由代理服务器解析主机名
+    pub fn socks5(inner: T, proxy_addr: Multiaddr) -> Self {
+        Self::new(inner, proxy_addr, Kind::Socks5 { remote_dns: true })
+    }
+
+    /// 通过 `proxy_addr` 上的 HTTP 代理以 CONNECT 方法拨号，主机名总是由代理服务器解析
+    pub fn http_connect(inner: T, proxy_addr: Multiaddr) -> Self {
+        Self::new(inner, proxy_addr, Kind::HttpConnect)
+    }
+
+    fn new(inner: T, addr: Multiaddr, kind: Kind) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            proxy: Arc::new(Proxy {
+                addr,
+                kind,
+                credentials: None,
+            }),
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+}
+
+impl<T, R> Config<T, R> {
+    /// 替换本地解析主机名时使用的解析器
+    pub fn resolver<R2>(self, resolver: R2) -> Config<T, R2> {
+        Config {
+            inner: self.inner,
+            proxy: self.proxy,
+            resolver: Arc::new(resolver),
+        }
+    }
+
+    /// 代理服务器的认证信息，SOCKS5 使用用户名/密码认证，HTTP 使用 `Proxy-Authorization: Basic`
+    pub fn credentials(self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        let credentials = Credentials {
+            username: username.into(),
+            password: password.into(),
+        };
+        self.map_proxy(|proxy| proxy.credentials = Some(credentials))
+    }
+
+    /// SOCKS5 是否把主机名交给代理服务器解析，为 `false` 时在本地解析后以 IP 请求连接
+    pub fn remote_dns(self, remote_dns: bool) -> Self {
+        self.map_proxy(|proxy| {
+            if let Kind::Socks5 { remote_dns: r } = &mut proxy.kind {
+                *r = remote_dns;
+            }
+        })
+    }
+
+    fn map_proxy(self, f: impl FnOnce(&mut Proxy)) -> Self {
+        let mut proxy = Proxy {
+            addr: self.proxy.addr.clone(),
+            kind: self.proxy.kind,
+            credentials: self.proxy.credentials.clone(),
+        };
+        f(&mut proxy);
+        Self {
+            inner: self.inner,
+            proxy: Arc::new(proxy),
+            resolver: self.resolver,
+        }
+    }
+}
+
+impl<T, R> Transport for Config<T, R>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Error: Send + 'static,
+    T::Dialer: Send + 'static,
+    T::Output: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    R: Resolver,
+{
+    type Output = T::Output;
+    type Error = Error<T::Error>;
+    type Dialer = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type ListenerUpgrade = future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Self::Error>;
+    type Listener = ListenStream<T>;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let listener = self
+            .inner
+            .listen(addr)
+            .map_err(|e| e.map(Error::Transport))?;
+        Ok(ListenStream { inner: listener })
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let Some(target) = Target::from_multiaddr(&addr) else {
+            return Err(TransportError::Unsupported(addr));
+        };
+        let ipv4 = match addr.protocols().first() {
+            Some(Protocol::Dns4(_)) => Some(true),
+            Some(Protocol::Dns6(_)) => Some(false),
+            _ => None,
+        };
+        let lookup = match (self.proxy.kind, &target) {
+            (Kind::Socks5 { remote_dns: false }, Target::Domain(host, _)) => {
+                Some(self.resolver.lookup(host))
+            }
+            _ => None,
+        };
+        let dialer = self
+            .inner
+            .connect(self.proxy.addr.clone())
+            .map_err(|e| e.map(Error::Transport))?;
+        let proxy = self.proxy.clone();
+        Ok(async move {
+            let target = match (lookup, target) {
+                (Some(lookup), Target::Domain(host, port)) => {
+                    Target::Ip(resolve(lookup, host, port, ipv4).await?)
+                }
+                (_, target) => target,
+            };
+            let mut stream = dialer.await.map_err(Error::Transport)?;
+            tracing::debug!("Opening tunnel to {} through {}", target, proxy.addr);
+            match proxy.kind {
+                Kind::Socks5 { .. } => {
+                    socks5::connect(&mut stream, &target, proxy.credentials.as_ref()).await?
+                }
+                Kind::HttpConnect => {
+                    http::connect(&mut stream, &target, proxy.credentials.as_ref()).await?
+                }
+            }
+            Ok(stream)
+        }
+        .boxed())
+    }
+}
+
+/// 隧道的目标地址
+#[derive(Debug, Clone)]
+pub(crate) enum Target {
+    Ip(SocketAddr),
+    Domain(String, u16),
+}
+
+impl Target {
+    fn from_multiaddr(addr: &Multiaddr) -> Option<Self> {
+        let [host, Protocol::Tcp(port)] = addr.protocols() else {
+            return None;
+        };
+        match host {
+            Protocol::Ip4(ip) => Some(Target::Ip(SocketAddr::new((*ip).into(), *port))),
+            Protocol::Ip6(ip) => Some(Target::Ip(SocketAddr::new((*ip).into(), *port))),
+            // SOCKS5 的域名长度字段只有一个字节
+            Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host)
+                if host.len() <= 255 =>
+            {
+                Some(Target::Domain(host.clone(), *port))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Ip(addr) => addr.fmt(f),
+            Target::Domain(host, port) => write!(f, "{host}:{port}"),
+        }
+    }
+}
+
+/// 取本地解析结果中的第一个地址，`ipv4` 为 `Some` 时只接受对应地址族（`/dns4`、`/dns6`）
+async fn resolve<E>(
+    lookup: BoxFuture<'static, io::Result<Vec<IpAddr>>>,
+    host: String,
+    port: u16,
+    ipv4: Option<bool>,
+) -> Result<SocketAddr, Error<E>> {
+    let ips = lookup.await.map_err(|e| Error::Resolve(host.clone(), e))?;
+    let ip = ips
+        .into_iter()
+        .find(|ip| ipv4.is_none_or(|ipv4| ip.is_ipv4() == ipv4));
+    ip.map(|ip| SocketAddr::new(ip, port))
+        .ok_or(Error::NoAddresses(host))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<E> {
+    #[error(transparent)]
+    Transport(E),
+    #[error("Failed to resolve {0}: {1}")]
+    Resolve(String, io::Error),
+    #[error("No addresses found for {0}")]
+    NoAddresses(String),
+    #[error("Proxy I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid proxy response: {0}")]
+    InvalidResponse(&'static str),
+    #[error("Username and password must be at most 255 bytes")]
+    InvalidCredentials,
+    #[error("SOCKS5 proxy accepts none of the offered authentication methods")]
+    NoAcceptableAuthMethod,
+    #[error("SOCKS5 proxy rejected the credentials")]
+    AuthenticationFailed,
+    #[error("SOCKS5 proxy failed to connect: {}", socks5::reply_message(*.0))]
+    Socks5(u8),
+    #[error("HTTP proxy responded with status {0}")]
+    HttpStatus(u16),
+}
+
+/// [`Config`] 的监听流，监听直接交给内部 Transport
+#[pin_project::pin_project]
+pub struct ListenStream<T: Transport> {
+    #[pin]
+    inner: T::Listener,
+}
+
+impl<T> Stream for ListenStream<T>
+where
+    T: Transport,
+    T::Error: error::Error,
+{
+    type Item = ListenerEvent<
+        future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Error<T::Error>>,
+        Error<T::Error>,
+    >;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx).map(|event| {
+            event.map(|event| {
+                event
+                    .map_upgrade_err(Error::Transport as fn(_) -> _)
+                    .map_err(Error::Transport)
+            })
+        })
+    }
+}
+
+impl<T> Listener for ListenStream<T>
+where
+    T: Transport,
+    T::Error: error::Error,
+{
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::transport::memory::{Channel, MemoryTransport, MemoryTransportError};
+    use airio_dns::StaticResolver;
+    use futures::{AsyncReadExt, AsyncWriteExt, StreamExt, executor::block_on, future::join};
+
+    use super::*;
+
+    type Check = fn(&Error<MemoryTransportError>) -> bool;
+
+    /// 在内存地址上运行一次性的代理服务器，经 `config` 拨号 `target` 并在隧道中收发一次数据
+    fn connect_through<C, R, S, F>(
+        config: C,
+        target: &str,
+        serve: S,
+    ) -> Result<(), Error<MemoryTransportError>>
+    where
+        C: FnOnce(MemoryTransport, Multiaddr) -> Config<MemoryTransport, R>,
+        R: Resolver,
+        S: FnOnce(Channel) -> F,
+        F: Future<Output = ()>,
+    {
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        let Some(ListenerEvent::Listened(proxy_addr)) = block_on(listener.next()) else {
+            panic!("expected Listened");
+        };
+        let config = config(transport, proxy_addr);
+        let target = target.parse().unwrap();
+        let server = async {
+            let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+                panic!("expected Incoming");
+            };
+            serve(upgrade.await.unwrap()).await;
+        };
+        let client = async {
+            let mut stream = config.connect(target).unwrap().await?;
+            stream.write_all(b"ping").await?;
+            stream.flush().await?;
+            let mut echo = [0u8; 4];
+            stream.read_exact(&mut echo).await?;
+            assert_eq!(&echo, b"ping");
+            Ok(())
+        };
+        block_on(join(client, server)).0
+    }
+
+    async fn expect(conn: &mut Channel, expected: &[u8]) {
+        let mut buf = vec![0u8; expected.len()];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    async fn reply(conn: &mut Channel, data: &[u8]) {
+        conn.write_all(data).await.unwrap();
+        conn.flush().await.unwrap();
+    }
+
+    /// 握手完成后回显隧道中的数据
+    async fn echo(mut conn: Channel) {
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        reply(&mut conn, &buf).await;
+    }
+
+    /// 握手失败后等待客户端关闭连接
+    async fn wait_closed(mut conn: Channel) {
+        let _ = conn.read(&mut [0u8; 1]).await;
+    }
+
+    async fn read_http_request(conn: &mut Channel) -> String {
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            conn.read_exact(&mut byte).await.unwrap();
+            request.push(byte[0]);
+        }
+        String::from_utf8(request).unwrap()
+    }
+
+    #[test]
+    fn socks5_without_auth() {
+        connect_through(Config::socks5, "/ip4/192.0.2.1/tcp/443", |mut conn| async {
+            expect(&mut conn, &[5, 1, 0]).await;
+            reply(&mut conn, &[5, 0]).await;
+            expect(&mut conn, &[5, 1, 0, 1, 192, 0, 2, 1, 1, 187]).await;
+            reply(&mut conn, &[5, 0, 0, 1, 10, 0, 0, 1, 0x1f, 0x90]).await;
+            echo(conn).await;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn socks5_domain_target() {
+        connect_through(
+            Config::socks5,
+            "/dns/example.com/tcp/80",
+            |mut conn| async {
+                expect(&mut conn, &[5, 1, 0]).await;
+                reply(&mut conn, &[5, 0]).await;
+                expect(
+                    &mut conn,
+                    &[[5, 1, 0, 3, 11].as_slice(), b"example.com", &[0, 80]].concat(),
+                )
+                .await;
+                // 以域名形式返回绑定地址
+                reply(
+                    &mut conn,
+                    &[[5, 0, 0, 3, 5].as_slice(), b"proxy", &[0, 80]].concat(),
+                )
+                .await;
+                echo(conn).await;
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn socks5_local_dns() {
+        let local_dns = |inner, addr| {
+            Config::socks5(inner, addr)
+                .remote_dns(false)
+                .resolver(StaticResolver::new().insert("example.com", [192, 0, 2, 7].into()))
+        };
+        connect_through(local_dns, "/dns/example.com/tcp/80", |mut conn| async {
+            expect(&mut conn, &[5, 1, 0]).await;
+            reply(&mut conn, &[5, 0]).await;
+            expect(&mut conn, &[5, 1, 0, 1, 192, 0, 2, 7, 0, 80]).await;
+            reply(&mut conn, &[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await;
+            echo(conn).await;
+        })
+        .unwrap();
+
+        // 解析失败或没有对应地址族的地址时不经过代理握手
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen("/memory/0".parse().unwrap()).unwrap();
+        let Some(ListenerEvent::Listened(proxy_addr)) = block_on(listener.next()) else {
+            panic!("expected Listened");
+        };
+        let config = local_dns(transport, proxy_addr);
+        let result = block_on(
+            config
+                .connect("/dns/example.org/tcp/80".parse().unwrap())
+                .unwrap(),
+        );
+        assert!(matches!(result, Err(Error::Resolve(..))));
+        let result = block_on(
+            config
+                .connect("/dns6/example.com/tcp/80".parse().unwrap())
+                .unwrap(),
+        );
+        assert!(matches!(result, Err(Error::NoAddresses(_))));
+    }
+
+    #[test]
+    fn socks5_username_password() {
+        let with_credentials =
+            |inner, addr| Config::socks5(inner, addr).credentials("user", "pass");
+        connect_through(
+            with_credentials,
+            "/ip4/192.0.2.1/tcp/443",
+            |mut conn| async {
+                expect(&mut conn, &[5, 2, 0, 2]).await;
+                reply(&mut conn, &[5, 2]).await;
+                expect(
+                    &mut conn,
+                    &[[1, 4].as_slice(), b"user", &[4], b"pass"].concat(),
+                )
+                .await;
+                reply(&mut conn, &[1, 0]).await;
+                expect(&mut conn, &[5, 1, 0, 1, 192, 0, 2, 1, 1, 187]).await;
+                reply(&mut conn, &[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await;
+                echo(conn).await;
+            },
+        )
+        .unwrap();
+
+        let result = connect_through(
+            with_credentials,
+            "/ip4/192.0.2.1/tcp/443",
+            |mut conn| async {
+                expect(&mut conn, &[5, 2, 0, 2]).await;
+                reply(&mut conn, &[5, 2]).await;
+                expect(
+                    &mut conn,
+                    &[[1, 4].as_slice(), b"user", &[4], b"pass"].concat(),
+                )
+                .await;
+                reply(&mut conn, &[1, 1]).await;
+                wait_closed(conn).await;
+            },
+        );
+        assert!(matches!(result, Err(Error::AuthenticationFailed)));
+
+        // 认证回复的版本必须是 1
+        let result = connect_through(
+            with_credentials,
+            "/ip4/192.0.2.1/tcp/443",
+            |mut conn| async {
+                expect(&mut conn, &[5, 2, 0, 2]).await;
+                reply(&mut conn, &[5, 2]).await;
+                expect(
+                    &mut conn,
+                    &[[1, 4].as_slice(), b"user", &[4], b"pass"].concat(),
+                )
+                .await;
+                reply(&mut conn, &[5, 0]).await;
+                wait_closed(conn).await;
+            },
+        );
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn socks5_errors() {
+        let cases: [(&[u8], Check); 4] = [
+            (&[5, 0xff], |e| matches!(e, Error::NoAcceptableAuthMethod)),
+            (&[4, 0], |e| matches!(e, Error::InvalidResponse(_))),
+            // 未提供认证信息时不能选择用户名/密码认证
+            (&[5, 2], |e| matches!(e, Error::InvalidResponse(_))),
+            (&[5, 0, 5, 5, 0, 0], |e| matches!(e, Error::Socks5(5))),
+        ];
+        for (response, check) in cases {
+            let result = connect_through(
+                Config::socks5,
+                "/ip4/192.0.2.1/tcp/443",
+                |mut conn| async move {
+                    expect(&mut conn, &[5, 1, 0]).await;
+                    reply(&mut conn, response).await;
+                    wait_closed(conn).await;
+                },
+            );
+            assert!(check(&result.unwrap_err()), "{response:?}");
+        }
+
+        // 连接请求的回复格式错误
+        for response in [&[4, 0, 0, 1][..], &[5, 0, 0, 9]] {
+            let result = connect_through(
+                Config::socks5,
+                "/ip4/192.0.2.1/tcp/443",
+                |mut conn| async move {
+                    expect(&mut conn, &[5, 1, 0]).await;
+                    reply(&mut conn, &[5, 0]).await;
+                    expect(&mut conn, &[5, 1, 0, 1, 192, 0, 2, 1, 1, 187]).await;
+                    reply(&mut conn, response).await;
+                    wait_closed(conn).await;
+                },
+            );
+            assert!(matches!(result, Err(Error::InvalidResponse(_))));
+        }
+    }
+
+    #[test]
+    fn http_connect() {
+        let with_credentials =
+            |inner, addr| Config::http_connect(inner, addr).credentials("user", "pass");
+        connect_through(
+            with_credentials,
+            "/dns/example.com/tcp/443",
+            |mut conn| async {
+                let request = read_http_request(&mut conn).await;
+                assert!(request.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+                assert!(request.contains("Host: example.com:443\r\n"));
+                assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+                reply(&mut conn, b"HTTP/1.1 200 Connection established\r\n\r\n").await;
+                echo(conn).await;
+            },
+        )
+        .unwrap();
+
+        connect_through(Config::http_connect, "/ip6/::1/tcp/80", |mut conn| async {
+            let request = read_http_request(&mut conn).await;
+            assert_eq!(
+                request,
+                "CONNECT [::1]:80 HTTP/1.1\r\nHost: [::1]:80\r\n\r\n"
+            );
+            reply(&mut conn, b"HTTP/1.0 200 OK\r\nVia: proxy\r\n\r\n").await;
+            echo(conn).await;
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn http_connect_errors() {
+        let cases: [(&[u8], Check); 2] = [
+            (b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n", |e| {
+                matches!(e, Error::HttpStatus(407))
+            }),
+            (b"SSH-2.0-OpenSSH\r\n\r\n", |e| {
+                matches!(e, Error::InvalidResponse(_))
+            }),
+        ];
+        for (response, check) in cases {
+            let result = connect_through(
+                Config::http_connect,
+                "/ip4/192.0.2.1/tcp/443",
+                |mut conn| async move {
+                    read_http_request(&mut conn).await;
+                    reply(&mut conn, response).await;
+                    wait_closed(conn).await;
+                },
+            );
+            assert!(check(&result.unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn unsupported_targets() {
+        let config = Config::socks5(MemoryTransport::new(), "/memory/1".parse().unwrap());
+        for target in [
+            "/ip4/192.0.2.1/udp/443",
+            "/memory/2",
+            "/ip4/192.0.2.1/tcp/1/ws",
+        ] {
+            assert!(matches!(
+                config.connect(target.parse().unwrap()),
+                Err(TransportError::Unsupported(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! SOCKS5 客户端握手（RFC 1928、RFC 1929）
+
+use std::net::IpAddr;
+
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Credentials, Error, Target};
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// 完成协商并请求连接 `target`，成功后 `stream` 即为到目标的隧道
+pub(crate) async fn connect<S, E>(
+    stream: &mut S,
+    target: &Target,
+    credentials: Option<&Credentials>,
+) -> Result<(), Error<E>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let greeting: &[u8] = match credentials {
+        Some(_) => &[VERSION, 2, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD],
+        None => &[VERSION, 1, METHOD_NO_AUTH],
+    };
+    stream.write_all(greeting).await?;
+    stream.flush().await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice[0] != VERSION {
+        return Err(Error::InvalidResponse("unexpected SOCKS version"));
+    }
+    match (choice[1], credentials) {
+        (METHOD_NO_AUTH, _) => {}
+        (METHOD_USERNAME_PASSWORD, Some(credentials)) => authenticate(stream, credentials).await?,
+        (METHOD_NO_ACCEPTABLE, _) => return Err(Error::NoAcceptableAuthMethod),
+        _ => return Err(Error::InvalidResponse("unexpected SOCKS5 method")),
+    }
+
+    let mut request = vec![VERSION, CMD_CONNECT, 0x00];
+    let port = match target {
+        Target::Ip(addr) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    request.push(ATYP_IPV4);
+                    request.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    request.push(ATYP_IPV6);
+                    request.extend_from_slice(&ip.octets());
+                }
+            }
+            addr.port()
+        }
+        Target::Domain(host, port) => {
+            request.push(ATYP_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+            *port
+        }
+    };
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err(Error::InvalidResponse("unexpected SOCKS version"));
+    }
+    if reply[1] != 0x00 {
+        return Err(Error::Socks5(reply[1]));
+    }
+    // 读掉 BND.ADDR 与 BND.PORT，之后的数据都属于隧道
+    let addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        _ => return Err(Error::InvalidResponse("unknown SOCKS5 address type")),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+async fn authenticate<S, E>(stream: &mut S, credentials: &Credentials) -> Result<(), Error<E>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (username, password) = (
+        credentials.username.as_bytes(),
+        credentials.password.as_bytes(),
+    );
+    if username.len() > 255 || password.len() > 255 {
+        return Err(Error::InvalidCredentials);
+    }
+    let mut request = Vec::with_capacity(3 + username.len() + password.len());
+    request.push(AUTH_VERSION);
+    request.push(username.len() as u8);
+    request.extend_from_slice(username);
+    request.push(password.len() as u8);
+    request.extend_from_slice(password);
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != AUTH_VERSION {
+        return Err(Error::InvalidResponse(
+            "unexpected SOCKS5 authentication version",
+        ));
+    }
+    if reply[1] != 0x00 {
+        return Err(Error::AuthenticationFailed);
+    }
+    Ok(())
+}
+
+/// RFC 1928 第 6 节定义的 REP 字段
+pub(crate) fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
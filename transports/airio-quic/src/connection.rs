@@ -1,10 +1,13 @@
 use std::{
+    io,
     pin::Pin,
     task::{Context, Poll},
     time::Duration,
 };
 
-use airio_core::{PeerId, StreamMuxer, muxing::StreamMuxerEvent};
+use airio_core::{
+    PeerId, StreamMuxer, identity::PublicKey, muxing::StreamMuxerEvent, transport::UpgradeGuard,
+};
 use futures::{
     FutureExt,
     future::{BoxFuture, Either, Select, select},
@@ -12,7 +15,6 @@ use futures::{
 };
 use futures_timer::Delay;
 use quinn::rustls::pki_types::CertificateDer;
-use sha2::Digest;
 use x509_parser::prelude::{FromDer, X509Certificate};
 
 use crate::{Error, Stream};
@@ -20,47 +22,50 @@ use crate::{Error, Stream};
 #[derive(Debug)]
 pub struct Connecting {
     connecting: Select<quinn::Connecting, Delay>,
+    /// 入站握手完成前计入监听器的进行中升级，监听器关闭超时后中止握手
+    guard: Option<UpgradeGuard>,
 }
 
 impl Connecting {
     pub(crate) fn new(connecting: quinn::Connecting, timeout: Duration) -> Self {
         Connecting {
             connecting: select(connecting, Delay::new(timeout)),
+            guard: None,
         }
     }
+
+    pub(crate) fn with_guard(mut self, guard: UpgradeGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
 }
 
-fn remote_peer_id(connecting: &quinn::Connection) -> PeerId {
-    let identity = connecting
+/// 由对端证书中的身份公钥得到 `PeerId`
+fn remote_peer_id(connecting: &quinn::Connection) -> Result<PeerId, Error> {
+    let certificates = connecting
         .peer_identity()
-        .expect("peer identity should be set by the remote peer");
-
-    let certificates: Box<Vec<CertificateDer>> = identity
-        .downcast()
-        .expect("peer identity should be set by the remote peer");
-
-    let end_entity = certificates
-        .first()
-        .expect("peer identity should have at least one certificate");
-
-    let (_, cert) = X509Certificate::from_der(&end_entity)
-        .expect("peer identity should have a valid X509 certificate");
-    let digest = sha2::Sha256::digest(cert.public_key().raw);
-    let mut bytes: [u8; 32] = [0_u8; 32];
-    bytes.copy_from_slice(&digest);
-
-    PeerId::from_bytes(bytes)
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer>>().ok())
+        .ok_or(Error::MissingCertificate)?;
+    let end_entity = certificates.first().ok_or(Error::MissingCertificate)?;
+    let (_, cert) = X509Certificate::from_der(end_entity).map_err(|_| Error::InvalidCertificate)?;
+    let key = PublicKey::from_spki_der(cert.public_key().raw)?;
+    Ok(key.to_peer_id())
 }
 
 impl Future for Connecting {
     type Output = Result<(PeerId, Connection), Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(guard) = &self.guard
+            && guard.poll_aborted(cx).is_ready()
+        {
+            return Poll::Ready(Err(Error::Io(io::ErrorKind::ConnectionAborted.into())));
+        }
         let connection = match futures::ready!(self.connecting.poll_unpin(cx)) {
             Either::Right(_) => return Poll::Ready(Err(Error::HandshakeTimedOut)),
             Either::Left((connection, _)) => connection.map_err(Error::from)?,
         };
-        let peer_id = remote_peer_id(&connection);
+        let peer_id = remote_peer_id(&connection)?;
         let muxer = Connection::new(connection);
         Poll::Ready(Ok((peer_id, muxer)))
     }
@@ -139,7 +144,7 @@ impl StreamMuxer for Connection {
             quinn::ConnectionError::LocallyClosed => {}
             err => return Poll::Ready(Err(err.into())),
         }
-        return Poll::Ready(Ok(()));
+        Poll::Ready(Ok(()))
     }
 
     fn poll(
@@ -1,3 +1,4 @@
+use airio_core::identity::DecodingError;
 use quinn::{ConnectError, ConnectionError};
 
 #[derive(Debug, thiserror::Error)]
@@ -13,4 +14,13 @@ pub enum Error {
 
     #[error("Handshake with the remote timed out.")]
     HandshakeTimedOut,
+
+    #[error("Unsupported remote identity key: {0}")]
+    UnsupportedKey(#[from] DecodingError),
+
+    #[error("Remote did not present a certificate")]
+    MissingCertificate,
+
+    #[error("Invalid remote certificate")]
+    InvalidCertificate,
 }
@@ -10,7 +10,10 @@ use std::{
     time::Duration,
 };
 
-use airio_core::{ListenerEvent, PeerId, Transport};
+use airio_core::{
+    ListenerEvent, Multiaddr, PeerId, Transport, TransportError, multiaddr,
+    transport::{Listener, ListenerHandle},
+};
 use futures::{FutureExt, future::BoxFuture};
 use socket2::{Domain, Socket, Type};
 
@@ -26,6 +29,8 @@ pub struct Config {
 }
 
 impl Config {
+    // 尚未实现，不提供会 panic 的 `Default`
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         todo!()
     }
@@ -34,15 +39,8 @@ impl Config {
         self.handshake_timeout = timeout;
         self
     }
-}
-impl Transport for Config {
-    type Output = (PeerId, Connection);
-    type Error = Error;
-    type Dialer = Connecting;
-    type ListenerUpgrade = Connecting;
-    type Listener = ListenerStream;
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
+    fn do_listen(&self, addr: SocketAddr) -> Result<ListenerStream, Error> {
         let socket = create_socket(addr)?;
         let local_addr = socket.local_addr()?;
         let runtime = Arc::new(quinn::TokioRuntime);
@@ -54,12 +52,15 @@ impl Transport for Config {
         Ok(ListenerStream {
             local_addr,
             endpoint,
-            accept,
+            accept: Some(accept),
+            handle: ListenerHandle::new(),
             handshake_timeout: self.handshake_timeout,
+            tell_listen_addr: true,
+            closed: false,
         })
     }
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
+    fn do_connect(&self, addr: SocketAddr, server_name: &str) -> Result<Connecting, Error> {
         let local_listen_addr = match addr {
             SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
             SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
@@ -69,48 +70,141 @@ impl Transport for Config {
         let endpoint_config = self.endpoint_config.clone();
         let client_config = self.client_config.clone();
         let endpoint = quinn::Endpoint::new(endpoint_config, None, socket, runtime)?;
-        let connecting = endpoint.connect_with(client_config, addr, "l")?;
+        let connecting = endpoint.connect_with(client_config, addr, server_name)?;
         Ok(Connecting::new(connecting, self.handshake_timeout))
     }
 }
 
+impl Transport for Config {
+    type Output = (PeerId, Connection);
+    type Error = Error;
+    type Dialer = Connecting;
+    type ListenerUpgrade = Connecting;
+    type Listener = ListenerStream;
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let Some((socket_addr, _)) = multiaddr_to_socketaddr(&addr) else {
+            return Err(TransportError::Unsupported(addr));
+        };
+        self.do_listen(socket_addr).map_err(TransportError::Other)
+    }
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let Some((socket_addr, server_name)) = multiaddr_to_socketaddr(&addr) else {
+            return Err(TransportError::Unsupported(addr));
+        };
+        // 未指定 SNI 时使用占位名称，对端身份由证书中的公钥确定
+        self.do_connect(socket_addr, server_name.unwrap_or("l"))
+            .map_err(TransportError::Other)
+    }
+}
+
 pub struct ListenerStream {
     local_addr: SocketAddr,
     endpoint: quinn::Endpoint,
-    accept: BoxFuture<'static, Option<quinn::Incoming>>,
+    /// 开始关闭后置为 `None`，不再接受新连接
+    accept: Option<BoxFuture<'static, Option<quinn::Incoming>>>,
+    handle: ListenerHandle,
     handshake_timeout: Duration,
+    tell_listen_addr: bool,
+    closed: bool,
+}
+
+impl ListenerStream {
+    /// 停止接受新连接，已建立的连接不受影响
+    fn stop_accepting(&mut self) {
+        if self.accept.take().is_some() {
+            self.endpoint.set_server_config(None);
+        }
+    }
 }
 
 impl futures::Stream for ListenerStream {
     type Item = ListenerEvent<Connecting, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        loop {
-            match self.accept.poll_unpin(cx) {
-                Poll::Ready(Some(incoming)) => {
-                    let endpoint = self.endpoint.clone();
-                    self.accept = async move { endpoint.accept().await }.boxed();
-                    let connecting = match incoming.accept() {
-                        Ok(connecting) => connecting,
-                        Err(e) => return Poll::Ready(Some(ListenerEvent::Error(e.into()))),
-                    };
-                    let event: ListenerEvent<Connecting, Error> = ListenerEvent::Incoming {
-                        local_addr: self.local_addr,
-                        remote_addr: connecting.remote_address(),
-                        upgrade: Connecting::new(connecting, self.handshake_timeout),
-                    };
-                    return Poll::Ready(Some(event));
-                }
-                Poll::Ready(None) => {
-                    continue;
-                }
-                Poll::Pending => {}
-            }
+        if self.tell_listen_addr {
+            self.tell_listen_addr = false;
+            let addr = socketaddr_to_multiaddr(self.local_addr);
+            return Poll::Ready(Some(ListenerEvent::Listened(addr)));
+        }
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        if self.handle.poll_closed(cx).is_ready() {
+            self.stop_accepting();
+            self.closed = true;
+            return Poll::Ready(Some(ListenerEvent::Closed(Ok(()))));
+        }
+        if self.handle.is_closing() {
+            self.stop_accepting();
+        }
+        let Some(accept) = self.accept.as_mut() else {
             return Poll::Pending;
+        };
+        match accept.poll_unpin(cx) {
+            Poll::Ready(Some(incoming)) => {
+                let endpoint = self.endpoint.clone();
+                self.accept = Some(async move { endpoint.accept().await }.boxed());
+                let connecting = match incoming.accept() {
+                    Ok(connecting) => connecting,
+                    Err(e) => return Poll::Ready(Some(ListenerEvent::Error(e.into()))),
+                };
+                let guard = self.handle.begin_incoming();
+                let event: ListenerEvent<Connecting, Error> = ListenerEvent::Incoming {
+                    local_addr: socketaddr_to_multiaddr(self.local_addr),
+                    remote_addr: socketaddr_to_multiaddr(connecting.remote_address()),
+                    upgrade: Connecting::new(connecting, self.handshake_timeout)
+                        .with_guard(guard.clone()),
+                    guard,
+                };
+                Poll::Ready(Some(event))
+            }
+            // Endpoint 已关闭
+            Poll::Ready(None) => {
+                self.accept = None;
+                self.closed = true;
+                Poll::Ready(Some(ListenerEvent::Closed(Ok(()))))
+            }
+            Poll::Pending => Poll::Pending,
         }
     }
 }
 
+impl Listener for ListenerStream {
+    fn handle(&self) -> &ListenerHandle {
+        &self.handle
+    }
+}
+
+/// 将 `/ip4/../udp/..[/sni/..]/quic-v1` 或 `/ip6/../udp/..[/sni/..]/quic-v1` 形式的地址
+/// 转换为 [`SocketAddr`] 与可选的 SNI 主机名
+fn multiaddr_to_socketaddr(addr: &Multiaddr) -> Option<(SocketAddr, Option<&str>)> {
+    use multiaddr::Protocol;
+    let (ip, port, server_name) = match addr.protocols() {
+        [ip, Protocol::Udp(port), Protocol::QuicV1] => (ip, *port, None),
+        [
+            ip,
+            Protocol::Udp(port),
+            Protocol::Sni(host),
+            Protocol::QuicV1,
+        ] => (ip, *port, Some(host.as_str())),
+        _ => return None,
+    };
+    let ip = match ip {
+        Protocol::Ip4(ip) => (*ip).into(),
+        Protocol::Ip6(ip) => (*ip).into(),
+        _ => return None,
+    };
+    Some((SocketAddr::new(ip, port), server_name))
+}
+
+fn socketaddr_to_multiaddr(addr: SocketAddr) -> Multiaddr {
+    Multiaddr::from(addr.ip())
+        .with(multiaddr::Protocol::Udp(addr.port()))
+        .with(multiaddr::Protocol::QuicV1)
+}
+
 fn create_socket(socket_addr: SocketAddr) -> std::io::Result<UdpSocket> {
     let socket = Socket::new(
         Domain::for_address(socket_addr),
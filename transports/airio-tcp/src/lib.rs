@@ -1,24 +1,45 @@
 mod listener;
+mod port_reuse;
+mod proxy_protocol;
 mod stream;
 
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, time::Duration};
 
-use airio_core::Transport;
+use airio_core::{Multiaddr, Transport, TransportError, multiaddr};
 use futures::{
     FutureExt, TryFutureExt,
     future::{BoxFuture, Ready},
 };
 
 pub use listener::ListenStream;
-use socket2::{Domain, Protocol, Socket, Type};
+use port_reuse::PortReuse;
+pub use proxy_protocol::{ProxyHeader, ProxyProtocol};
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
 pub use stream::TcpStream;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpSocket};
 
 #[derive(Clone, Debug)]
 pub struct Config {
     ttl: Option<u32>,
     nodelay: bool,
     backlog: u32,
+    accept_backoff_min: Duration,
+    accept_backoff_max: Duration,
+    max_pending_upgrades: Option<usize>,
+    keepalive: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    keepalive_retries: Option<u32>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+    linger: Option<Duration>,
+    user_timeout: Option<Duration>,
+    tos: Option<u32>,
+    mark: Option<u32>,
+    bind_device: Option<String>,
+    port_reuse: Option<PortReuse>,
+    dial_local_addr: Option<SocketAddr>,
+    dial_interface: Option<String>,
+    proxy_protocol: Option<ProxyProtocol>,
 }
 
 impl Config {
@@ -27,6 +48,23 @@ impl Config {
             ttl: None,
             nodelay: true,
             backlog: 1024,
+            accept_backoff_min: Duration::from_millis(5),
+            accept_backoff_max: Duration::from_secs(1),
+            max_pending_upgrades: None,
+            keepalive: None,
+            keepalive_interval: None,
+            keepalive_retries: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            linger: None,
+            user_timeout: None,
+            tos: None,
+            mark: None,
+            bind_device: None,
+            port_reuse: None,
+            dial_local_addr: None,
+            dial_interface: None,
+            proxy_protocol: None,
         }
     }
 
@@ -45,7 +83,123 @@ impl Config {
         self
     }
 
+    /// 文件描述符或内存耗尽导致 accept 失败时的退避时间，从 `min` 开始每次翻倍，最多到 `max`。
+    ///
+    /// 其他 accept 错误中，对端提前断开等瞬时错误会直接继续 accept，
+    /// 无法恢复的错误会以 `ListenerEvent::Closed(Err(..))` 结束监听流。
+    pub fn accept_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.accept_backoff_min = min;
+        self.accept_backoff_max = max.max(min);
+        self
+    }
+
+    /// 进行中的入站升级达到上限时暂停 accept，新连接留在内核的 backlog 中
+    pub fn max_pending_upgrades(mut self, max: usize) -> Self {
+        self.max_pending_upgrades = Some(max);
+        self
+    }
+
+    /// 开启 TCP keepalive，连接空闲 `idle` 后开始探测
+    pub fn keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// keepalive 探测的间隔，需同时开启 [`Config::keepalive`]
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// keepalive 探测失败多少次后断开连接，需同时开启 [`Config::keepalive`]
+    pub fn keepalive_retries(mut self, retries: u32) -> Self {
+        self.keepalive_retries = Some(retries);
+        self
+    }
+
+    /// SO_SNDBUF
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// SO_RCVBUF，监听套接字上在 listen 前设置，以便影响窗口缩放
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// SO_LINGER，关闭连接时最多等待 `linger` 发送剩余数据，`Duration::ZERO` 直接发送 RST
+    pub fn linger(mut self, linger: Duration) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// TCP_USER_TIMEOUT，仅 Linux、Android 与 Fuchsia 支持
+    pub fn user_timeout(mut self, timeout: Duration) -> Self {
+        self.user_timeout = Some(timeout);
+        self
+    }
+
+    /// IPv4 的 IP_TOS 或 IPv6 的 IPV6_TCLASS，DSCP 占高 6 位
+    pub fn tos(mut self, tos: u32) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// 以 DSCP 值设置 [`Config::tos`]
+    pub fn dscp(self, dscp: u8) -> Self {
+        self.tos(u32::from(dscp & 0x3f) << 2)
+    }
+
+    /// SO_MARK，仅 Linux、Android 与 Fuchsia 支持
+    pub fn mark(mut self, mark: u32) -> Self {
+        self.mark = Some(mark);
+        self
+    }
+
+    /// SO_BINDTODEVICE，将套接字绑定到网络接口，仅 Linux、Android 与 Fuchsia 支持
+    pub fn bind_device(mut self, interface: impl Into<String>) -> Self {
+        self.bind_device = Some(interface.into());
+        self
+    }
+
+    /// 端口复用：监听与拨号的套接字都设置 SO_REUSEPORT，拨号时绑定到同一地址族的监听地址，
+    /// 使出站连接的源端口与监听端口一致，用于 NAT 穿透。
+    ///
+    /// 监听地址在克隆出的 `Config` 之间共享。
+    pub fn port_reuse(mut self, enable: bool) -> Self {
+        self.port_reuse = enable.then(PortReuse::default);
+        self
+    }
+
+    /// 拨号时绑定的本地地址，优先于端口复用
+    pub fn dial_local_addr(mut self, addr: SocketAddr) -> Self {
+        self.dial_local_addr = Some(addr);
+        self
+    }
+
+    /// 拨号时绑定的网络接口（SO_BINDTODEVICE），优先于 [`Config::bind_device`]
+    pub fn dial_interface(mut self, interface: impl Into<String>) -> Self {
+        self.dial_interface = Some(interface.into());
+        self
+    }
+
+    /// 在监听器上解析 PROXY 协议头，参见 [`ProxyProtocol`]
+    pub fn proxy_protocol(mut self, proxy_protocol: ProxyProtocol) -> Self {
+        self.proxy_protocol = Some(proxy_protocol);
+        self
+    }
+
     fn create_socket(&self, socket_addr: SocketAddr) -> io::Result<Socket> {
+        self.create_socket_with_device(socket_addr, self.bind_device.as_deref())
+    }
+
+    fn create_socket_with_device(
+        &self,
+        socket_addr: SocketAddr,
+        device: Option<&str>,
+    ) -> io::Result<Socket> {
         let socket = Socket::new(
             Domain::for_address(socket_addr),
             Type::STREAM,
@@ -54,13 +208,156 @@ impl Config {
         if socket_addr.is_ipv6() {
             socket.set_only_v6(true)?;
         }
+        socket.set_reuse_address(true)?;
+        if self.port_reuse.is_some() {
+            set_reuse_port(&socket)?;
+        }
+        socket.set_nonblocking(true)?;
+        if let Some(interface) = device {
+            bind_device(&socket, interface)?;
+        }
+        self.apply(&SockRef::from(&socket), socket_addr.is_ipv6())?;
+        Ok(socket)
+    }
+
+    /// 应用连接相关的选项，拨号、监听与 accept 得到的套接字使用相同的设置
+    pub(crate) fn apply(&self, socket: &SockRef<'_>, is_ipv6: bool) -> io::Result<()> {
         if let Some(ttl) = self.ttl {
-            socket.set_ttl(ttl)?;
+            if is_ipv6 {
+                socket.set_unicast_hops_v6(ttl)?;
+            } else {
+                socket.set_ttl(ttl)?;
+            }
         }
         socket.set_nodelay(self.nodelay)?;
-        socket.set_reuse_address(true)?;
+        if let Some(keepalive) = self.tcp_keepalive()? {
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(linger) = self.linger {
+            socket.set_linger(Some(linger))?;
+        }
+        if let Some(timeout) = self.user_timeout {
+            set_user_timeout(socket, timeout)?;
+        }
+        if let Some(tos) = self.tos {
+            set_tos(socket, tos, is_ipv6)?;
+        }
+        if let Some(mark) = self.mark {
+            set_mark(socket, mark)?;
+        }
+        Ok(())
+    }
+
+    fn tcp_keepalive(&self) -> io::Result<Option<TcpKeepalive>> {
+        let Some(idle) = self.keepalive else {
+            return Ok(None);
+        };
+        #[allow(unused_mut)]
+        let mut keepalive = TcpKeepalive::new().with_time(idle);
+        if let Some(interval) = self.keepalive_interval {
+            #[cfg(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+                target_os = "windows",
+            ))]
+            {
+                keepalive = keepalive.with_interval(interval);
+            }
+            #[cfg(not(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+                target_os = "windows",
+            )))]
+            {
+                let _ = interval;
+                return Err(unsupported("TCP_KEEPINTVL"));
+            }
+        }
+        if let Some(retries) = self.keepalive_retries {
+            #[cfg(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+            ))]
+            {
+                keepalive = keepalive.with_retries(retries);
+            }
+            #[cfg(not(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+            )))]
+            {
+                let _ = retries;
+                return Err(unsupported("TCP_KEEPCNT"));
+            }
+        }
+        Ok(Some(keepalive))
+    }
+
+    fn do_listen(&self, addr: SocketAddr) -> io::Result<ListenStream> {
+        let socket = self.create_socket(addr)?;
+        socket.bind(&addr.into())?;
+        socket.listen(self.backlog as _)?;
         socket.set_nonblocking(true)?;
-        Ok(socket)
+        let listener = TcpListener::from_std(socket.into())?;
+        ListenStream::new(listener, self)
+    }
+
+    fn do_connect(&self, addr: SocketAddr) -> io::Result<TcpSocket> {
+        let device = self
+            .dial_interface
+            .as_deref()
+            .or(self.bind_device.as_deref());
+        let socket = self.create_socket_with_device(addr, device)?;
+        if let Some(local_addr) = self.dial_local_addr {
+            socket.bind(&local_addr.into())?;
+        } else if let Some(local_addr) = self
+            .port_reuse
+            .as_ref()
+            .and_then(|port_reuse| port_reuse.local_dial_addr(addr.ip()))
+        {
+            tracing::trace!("Binding dial socket to {} for port reuse", local_addr);
+            if let Err(e) = socket.bind(&local_addr.into()) {
+                // 绑定失败时退回到系统分配的端口
+                tracing::debug!("Port reuse bind to {} failed: {}", local_addr, e);
+                let socket = self.create_socket_with_device(addr, device)?;
+                return Ok(TcpSocket::from_std_stream(socket.into()));
+            }
+        }
+        Ok(TcpSocket::from_std_stream(socket.into()))
     }
 }
 
@@ -77,19 +374,260 @@ impl Transport for Config {
     type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
     type Listener = ListenStream;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
-        let fut = tokio::net::TcpStream::connect(addr)
-            .map_ok(TcpStream::from)
-            .boxed();
-        Ok(fut)
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let addr = multiaddr_to_socketaddr(&addr).ok_or(TransportError::Unsupported(addr))?;
+        let socket = self.do_connect(addr).map_err(TransportError::Other)?;
+        Ok(socket.connect(addr).map_ok(TcpStream::from).boxed())
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
-        let socket = self.create_socket(addr)?;
-        socket.bind(&addr.into())?;
-        socket.listen(self.backlog as _)?;
-        socket.set_nonblocking(true)?;
-        let listener = TcpListener::from_std(socket.into())?;
-        Ok(ListenStream::new(listener, addr))
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let addr = multiaddr_to_socketaddr(&addr).ok_or(TransportError::Unsupported(addr))?;
+        self.do_listen(addr).map_err(TransportError::Other)
+    }
+}
+
+/// 将 `/ip4/../tcp/..` 或 `/ip6/../tcp/..` 形式的地址转换为 [`SocketAddr`]，
+/// 末尾可带有 `/sni/..`，TCP 层会忽略它。
+fn multiaddr_to_socketaddr(addr: &Multiaddr) -> Option<SocketAddr> {
+    use multiaddr::Protocol;
+    let (ip, port) = match addr.protocols() {
+        [ip, Protocol::Tcp(port)] | [ip, Protocol::Tcp(port), Protocol::Sni(_)] => (ip, *port),
+        _ => return None,
+    };
+    match ip {
+        Protocol::Ip4(ip) => Some(SocketAddr::new((*ip).into(), port)),
+        Protocol::Ip6(ip) => Some(SocketAddr::new((*ip).into(), port)),
+        _ => None,
+    }
+}
+
+/// 部分平台上所有选项都受支持，此时不会用到
+#[allow(dead_code)]
+fn unsupported(option: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{option} is not supported on this platform"),
+    )
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+)))]
+fn set_reuse_port(_: &Socket) -> io::Result<()> {
+    Err(unsupported("SO_REUSEPORT"))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_: &Socket, _: &str) -> io::Result<()> {
+    Err(unsupported("SO_BINDTODEVICE"))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn set_user_timeout(socket: &SockRef<'_>, timeout: Duration) -> io::Result<()> {
+    socket.set_tcp_user_timeout(Some(timeout))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn set_user_timeout(_: &SockRef<'_>, _: Duration) -> io::Result<()> {
+    Err(unsupported("TCP_USER_TIMEOUT"))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn set_mark(socket: &SockRef<'_>, mark: u32) -> io::Result<()> {
+    socket.set_mark(mark)
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn set_mark(_: &SockRef<'_>, _: u32) -> io::Result<()> {
+    Err(unsupported("SO_MARK"))
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn set_tos(socket: &SockRef<'_>, tos: u32, is_ipv6: bool) -> io::Result<()> {
+    if is_ipv6 {
+        socket.set_tclass_v6(tos)
+    } else {
+        socket.set_tos(tos)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn set_tos(_: &SockRef<'_>, _: u32, _: bool) -> io::Result<()> {
+    Err(unsupported("IP_TOS"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, net::IpAddr};
+
+    use airio_core::ListenerEvent;
+    use futures::StreamExt;
+
+    use super::*;
+
+    /// 用 `dialer` 连接 `listener`，返回拨号端与监听端的连接
+    async fn connect(dialer: &Config, listener: &mut ListenStream) -> (TcpStream, TcpStream) {
+        let dial = dialer.connect(listener.local_addr().into()).unwrap();
+        let accept = async {
+            loop {
+                match listener.next().await {
+                    Some(ListenerEvent::Listened(_)) => continue,
+                    Some(ListenerEvent::Incoming { upgrade, .. }) => return upgrade.await.unwrap(),
+                    _ => panic!("expected Incoming"),
+                }
+            }
+        };
+        let (dialed, accepted) = futures::join!(dial, accept);
+        (dialed.unwrap(), accepted)
+    }
+
+    fn local_addr(stream: &TcpStream) -> SocketAddr {
+        stream.socket().local_addr().unwrap().as_socket().unwrap()
+    }
+
+    fn peer_addr(stream: &TcpStream) -> SocketAddr {
+        stream.socket().peer_addr().unwrap().as_socket().unwrap()
+    }
+
+    #[tokio::test]
+    async fn listen_reports_bound_port() {
+        let mut listener = Config::new()
+            .listen("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let bound = listener.local_addr();
+        assert_ne!(bound.port(), 0);
+        let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+            panic!("expected Listened");
+        };
+        assert_eq!(multiaddr_to_socketaddr(&addr), Some(bound));
+    }
+
+    #[tokio::test]
+    async fn listen_on_unspecified_reports_each_interface() {
+        let mut listener = Config::new()
+            .listen("/ip4/0.0.0.0/tcp/0".parse().unwrap())
+            .unwrap();
+        let port = listener.local_addr().port();
+        assert_ne!(port, 0);
+        let expected = if_addrs::get_if_addrs()
+            .unwrap()
+            .iter()
+            .map(|interface| interface.ip())
+            .filter(IpAddr::is_ipv4)
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect::<HashSet<_>>();
+        assert!(!expected.is_empty());
+        let mut reported = HashSet::new();
+        for _ in 0..expected.len() {
+            let Some(ListenerEvent::Listened(addr)) = listener.next().await else {
+                panic!("expected Listened");
+            };
+            reported.insert(multiaddr_to_socketaddr(&addr).unwrap());
+        }
+        assert_eq!(reported, expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn applies_socket_options() {
+        let config = Config::new()
+            .ttl(42)
+            .nodelay(true)
+            .keepalive(Duration::from_secs(30))
+            .keepalive_interval(Duration::from_secs(5))
+            .keepalive_retries(4)
+            .send_buffer_size(64 * 1024)
+            .recv_buffer_size(64 * 1024)
+            .linger(Duration::from_secs(1))
+            .user_timeout(Duration::from_secs(10))
+            .dscp(10);
+        let mut listener = config
+            .listen("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let (dialed, accepted) = connect(&config, &mut listener).await;
+        for stream in [&dialed, &accepted] {
+            let socket = stream.socket();
+            assert_eq!(socket.ttl().unwrap(), 42);
+            assert!(socket.nodelay().unwrap());
+            assert!(socket.keepalive().unwrap());
+            assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(30));
+            assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(5));
+            assert_eq!(socket.keepalive_retries().unwrap(), 4);
+            // Linux 返回的缓冲区大小是设置值的两倍
+            assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+            assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+            assert_eq!(socket.linger().unwrap(), Some(Duration::from_secs(1)));
+            assert_eq!(
+                socket.tcp_user_timeout().unwrap(),
+                Some(Duration::from_secs(10))
+            );
+            assert_eq!(socket.tos().unwrap(), 10 << 2);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn port_reuse_dials_from_listen_port() {
+        let config = Config::new().port_reuse(true);
+        let listener = config
+            .listen("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let mut remote = Config::new()
+            .listen("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let (dialed, accepted) = connect(&config, &mut remote).await;
+        assert_eq!(local_addr(&dialed), listener.local_addr());
+        assert_eq!(peer_addr(&accepted), listener.local_addr());
+
+        // 监听关闭后取消登记，拨号使用系统分配的端口
+        let listen_addr = listener.local_addr();
+        drop(listener);
+        let (dialed, _accepted) = connect(&config, &mut remote).await;
+        assert_ne!(local_addr(&dialed), listen_addr);
+    }
+
+    #[tokio::test]
+    async fn dials_from_explicit_local_addr() {
+        let local = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = Config::new().dial_local_addr(local);
+        let mut remote = Config::new()
+            .listen("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let (dialed, accepted) = connect(&config, &mut remote).await;
+        assert_eq!(local_addr(&dialed), local);
+        assert_eq!(peer_addr(&accepted), local);
     }
 }
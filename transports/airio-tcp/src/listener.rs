@@ -1,59 +1,565 @@
-use airio_core::ListenerEvent;
+use airio_core::{
+    Extensions, ListenerEvent,
+    transport::{Listener, ListenerHandle},
+};
 use futures::{
-    Stream,
-    future::{self, Ready},
+    FutureExt, Stream, StreamExt,
+    future::{self, BoxFuture, Either, Ready},
+    stream::FuturesUnordered,
 };
+use futures_timer::Delay;
+use if_watch::{IfEvent, tokio::IfWatcher};
+use socket2::SockRef;
 use std::{
+    collections::{HashSet, VecDeque},
     io,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::net::TcpListener;
 
-use crate::TcpStream;
+use crate::{Config, PortReuse, TcpStream, proxy_protocol};
+
+type Event = ListenerEvent<Ready<Result<TcpStream, io::Error>>, io::Error>;
+
+/// 读取 PROXY 协议头，输出连接的本地地址、对端地址与读取结果
+type Handshake = BoxFuture<'static, (SocketAddr, SocketAddr, io::Result<(TcpStream, SocketAddr)>)>;
 
 pub struct ListenStream {
+    /// 实际绑定的地址，端口为 0 时为系统分配的端口
     listener_addr: SocketAddr,
-    listener: TcpListener,
-    pending_event: Option<ListenerEvent<Ready<Result<TcpStream, io::Error>>, io::Error>>,
+    /// 开始关闭后置为 `None`，不再接受新连接
+    listener: Option<TcpListener>,
+    /// 监听通配地址时监视网络接口的地址变化
+    if_watcher: Option<IfWatcher>,
+    /// 监听通配地址时当前已报告的具体 IP
+    addresses: HashSet<IpAddr>,
+    handle: ListenerHandle,
+    pending_events: VecDeque<Event>,
+    backoff_min: Duration,
+    backoff_max: Duration,
+    /// 下一次资源耗尽时的退避时间
+    backoff: Duration,
+    /// 退避结束前不再 accept
+    backoff_timer: Option<Delay>,
+    max_pending_upgrades: Option<usize>,
+    /// 应用到 accept 得到的套接字
+    config: Config,
+    /// 开启端口复用时已登记的监听地址
+    port_reuse: Option<PortReuse>,
+    /// 正在读取 PROXY 协议头的连接
+    handshakes: FuturesUnordered<Handshake>,
+    closed: bool,
 }
 
 impl ListenStream {
-    pub fn new(listener: TcpListener, listener_addr: SocketAddr) -> Self {
-        let listened_event = ListenerEvent::Listened(listener_addr);
-        ListenStream {
+    pub(crate) fn new(listener: TcpListener, config: &Config) -> io::Result<Self> {
+        let listener_addr = listener.local_addr()?;
+        let mut stream = ListenStream {
             listener_addr,
-            listener,
-            pending_event: Some(listened_event),
+            listener: Some(listener),
+            if_watcher: None,
+            addresses: HashSet::new(),
+            handle: ListenerHandle::new(),
+            pending_events: VecDeque::new(),
+            backoff_min: config.accept_backoff_min,
+            backoff_max: config.accept_backoff_max,
+            backoff: config.accept_backoff_min,
+            backoff_timer: None,
+            max_pending_upgrades: config.max_pending_upgrades,
+            config: config.clone(),
+            port_reuse: config.port_reuse.clone(),
+            handshakes: FuturesUnordered::new(),
+            closed: false,
+        };
+        if let Some(port_reuse) = &stream.port_reuse {
+            port_reuse.register(listener_addr);
+        }
+        if !listener_addr.ip().is_unspecified() {
+            stream
+                .pending_events
+                .push_back(ListenerEvent::Listened(listener_addr.into()));
+            return Ok(stream);
+        }
+        // 通配地址：为每个具体地址产生一次 Listened，之后跟踪接口变化
+        match if_addrs::get_if_addrs() {
+            Ok(interfaces) => {
+                for ip in interfaces.iter().map(|interface| interface.ip()) {
+                    if stream.matches_family(ip) && stream.addresses.insert(ip) {
+                        let addr = SocketAddr::new(ip, listener_addr.port());
+                        stream
+                            .pending_events
+                            .push_back(ListenerEvent::Listened(addr.into()));
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to enumerate interfaces for {}: {}",
+                    listener_addr,
+                    e
+                );
+                stream
+                    .pending_events
+                    .push_back(ListenerEvent::Listened(listener_addr.into()));
+            }
+        }
+        match IfWatcher::new() {
+            Ok(watcher) => stream.if_watcher = Some(watcher),
+            Err(e) => tracing::warn!("Failed to watch interfaces for {}: {}", listener_addr, e),
+        }
+        Ok(stream)
+    }
+
+    /// 实际绑定的地址
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener_addr
+    }
+
+    /// 停止接受新连接，并取消端口复用的登记
+    fn stop_accepting(&mut self) {
+        self.listener = None;
+        self.if_watcher = None;
+        self.handshakes.clear();
+        if let Some(port_reuse) = self.port_reuse.take() {
+            port_reuse.unregister(self.listener_addr);
+        }
+    }
+
+    fn matches_family(&self, ip: IpAddr) -> bool {
+        ip.is_ipv4() == self.listener_addr.is_ipv4()
+    }
+
+    fn poll_if_watcher(&mut self, cx: &mut Context<'_>) -> Poll<Event> {
+        let Some(watcher) = self.if_watcher.as_mut() else {
+            return Poll::Pending;
+        };
+        let (is_ipv4, port) = (self.listener_addr.is_ipv4(), self.listener_addr.port());
+        while let Poll::Ready(event) = watcher.poll_if_event(cx) {
+            match event {
+                Ok(IfEvent::Up(net)) => {
+                    let ip = net.addr();
+                    if ip.is_ipv4() == is_ipv4 && self.addresses.insert(ip) {
+                        let addr = SocketAddr::new(ip, port).into();
+                        return Poll::Ready(ListenerEvent::AddressAdded(addr));
+                    }
+                }
+                Ok(IfEvent::Down(net)) => {
+                    let ip = net.addr();
+                    if self.addresses.remove(&ip) {
+                        let addr = SocketAddr::new(ip, port).into();
+                        return Poll::Ready(ListenerEvent::AddressExpired(addr));
+                    }
+                }
+                Err(e) => return Poll::Ready(ListenerEvent::Error(e)),
+            }
+        }
+        Poll::Pending
+    }
+
+    /// 退避或进行中的升级达到上限时返回 `Poll::Pending`
+    fn poll_accept_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(timer) = self.backoff_timer.as_mut() {
+            futures::ready!(timer.poll_unpin(cx));
+            self.backoff_timer = None;
+        }
+        // 升级完成时 `ListenerHandle` 会唤醒当前任务
+        if let Some(max) = self.max_pending_upgrades
+            && self.handle.in_flight() + self.handshakes.len() >= max
+        {
+            tracing::trace!(
+                "Pausing accept on {}: {} upgrades in flight",
+                self.listener_addr,
+                max
+            );
+            return Poll::Pending;
         }
+        Poll::Ready(())
     }
+
+    fn on_accept_error(&mut self, e: io::Error) -> Event {
+        match AcceptError::classify(&e) {
+            AcceptError::Transient => {
+                tracing::debug!("Transient accept error on {}: {}", self.listener_addr, e);
+                ListenerEvent::Error(e)
+            }
+            AcceptError::ResourceExhausted => {
+                tracing::warn!(
+                    "Accept on {} failed, retrying in {:?}: {}",
+                    self.listener_addr,
+                    self.backoff,
+                    e
+                );
+                self.backoff_timer = Some(Delay::new(self.backoff));
+                self.backoff = (self.backoff * 2).min(self.backoff_max);
+                ListenerEvent::Error(e)
+            }
+            AcceptError::Fatal => {
+                tracing::error!("Fatal accept error on {}: {}", self.listener_addr, e);
+                self.stop_accepting();
+                self.closed = true;
+                ListenerEvent::Closed(Err(e))
+            }
+        }
+    }
+}
+
+/// 在超时前读取 PROXY 协议头，头部中的源地址替换连接的对端地址
+fn read_proxy_header(
+    mut stream: tokio::net::TcpStream,
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    timeout: Duration,
+) -> Handshake {
+    async move {
+        let read = proxy_protocol::read_header(&mut stream);
+        let result = match future::select(Box::pin(read), Delay::new(timeout)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out reading PROXY protocol header",
+            )),
+        };
+        let result = result.map(|(header, leftover)| {
+            let remote_addr = header.source.unwrap_or(remote_addr);
+            let mut extensions = Extensions::new();
+            extensions.insert(header);
+            (
+                TcpStream::with_buffered(stream, leftover, extensions),
+                remote_addr,
+            )
+        });
+        (local_addr, remote_addr, result)
+    }
+    .boxed()
+}
+
+/// accept 错误的分类
+enum AcceptError {
+    /// 只影响单个连接，可以立即继续 accept
+    Transient,
+    /// 文件描述符或内存耗尽，退避后重试
+    ResourceExhausted,
+    /// 监听套接字不可用，结束监听
+    Fatal,
+}
+
+impl AcceptError {
+    fn classify(e: &io::Error) -> Self {
+        use io::ErrorKind::*;
+        if e.kind() == OutOfMemory || is_resource_exhausted(e) {
+            return AcceptError::ResourceExhausted;
+        }
+        match e.kind() {
+            ConnectionAborted | ConnectionReset | ConnectionRefused | Interrupted | WouldBlock
+            | TimedOut | PermissionDenied | HostUnreachable | NetworkUnreachable | NetworkDown => {
+                AcceptError::Transient
+            }
+            _ if is_protocol_error(e) => AcceptError::Transient,
+            _ => AcceptError::Fatal,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_resource_exhausted(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+    )
+}
+
+#[cfg(windows)]
+fn is_resource_exhausted(e: &io::Error) -> bool {
+    // WSAEMFILE、WSAENOBUFS
+    matches!(e.raw_os_error(), Some(10024 | 10055))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_resource_exhausted(_: &io::Error) -> bool {
+    false
+}
+
+/// accept(2) 可能返回的、属于新连接本身的协议错误
+#[cfg(unix)]
+fn is_protocol_error(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EPROTO | libc::ENOPROTOOPT))
+}
+
+#[cfg(not(unix))]
+fn is_protocol_error(_: &io::Error) -> bool {
+    false
 }
 
 impl Stream for ListenStream {
-    type Item = ListenerEvent<Ready<Result<TcpStream, io::Error>>, io::Error>;
+    type Item = Event;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Some(event) = self.pending_event.take() {
+        if let Some(event) = self.pending_events.pop_front() {
             return Poll::Ready(Some(event));
         }
-        tracing::trace!(
-            "ListenStream::poll_next: Polling for new connections on {}",
-            self.listener_addr
-        );
-        match Pin::new(&mut self.listener).poll_accept(cx) {
-            Poll::Ready(Ok((stream, remote_addr))) => {
-                return Poll::Ready(Some(ListenerEvent::Incoming {
-                    local_addr: self.listener_addr,
-                    remote_addr,
-                    upgrade: future::ok(stream.into()),
-                }));
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        if self.handle.poll_closed(cx).is_ready() {
+            self.stop_accepting();
+            self.closed = true;
+            return Poll::Ready(Some(ListenerEvent::Closed(Ok(()))));
+        }
+        if self.handle.is_closing() {
+            self.stop_accepting();
+        }
+        if let Poll::Ready(event) = self.poll_if_watcher(cx) {
+            return Poll::Ready(Some(event));
+        }
+        loop {
+            match self.handshakes.poll_next_unpin(cx) {
+                Poll::Ready(Some((local_addr, _, Ok((stream, remote_addr))))) => {
+                    return Poll::Ready(Some(ListenerEvent::Incoming {
+                        local_addr: local_addr.into(),
+                        remote_addr: remote_addr.into(),
+                        upgrade: future::ok(stream),
+                        guard: self.handle.begin_incoming(),
+                    }));
+                }
+                Poll::Ready(Some((_, remote_addr, Err(e)))) => {
+                    tracing::debug!("Invalid PROXY protocol header from {}: {}", remote_addr, e);
+                    return Poll::Ready(Some(ListenerEvent::Error(e)));
+                }
+                Poll::Ready(None) | Poll::Pending => {}
             }
-            Poll::Ready(Err(e)) => {
-                return Poll::Ready(Some(ListenerEvent::Error(e)));
+            futures::ready!(self.poll_accept_ready(cx));
+            let Some(listener) = self.listener.as_ref() else {
+                return Poll::Pending;
+            };
+            tracing::trace!(
+                "ListenStream::poll_next: Polling for new connections on {}",
+                self.listener_addr
+            );
+            match listener.poll_accept(cx) {
+                Poll::Ready(Ok((stream, remote_addr))) => {
+                    self.backoff = self.backoff_min;
+                    let socket = SockRef::from(&stream);
+                    if let Err(e) = self.config.apply(&socket, remote_addr.is_ipv6()) {
+                        tracing::debug!("Failed to apply socket options to {}: {}", remote_addr, e);
+                        return Poll::Ready(Some(ListenerEvent::Error(e)));
+                    }
+                    // 监听通配地址时使用连接实际的本地地址
+                    let local_addr = stream.local_addr().unwrap_or(self.listener_addr);
+                    if let Some(proxy) = &self.config.proxy_protocol
+                        && proxy.is_trusted(remote_addr.ip())
+                    {
+                        let handshake =
+                            read_proxy_header(stream, local_addr, remote_addr, proxy.timeout());
+                        self.handshakes.push(handshake);
+                        continue;
+                    }
+                    return Poll::Ready(Some(ListenerEvent::Incoming {
+                        local_addr: local_addr.into(),
+                        remote_addr: remote_addr.into(),
+                        upgrade: future::ok(stream.into()),
+                        guard: self.handle.begin_incoming(),
+                    }));
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(self.on_accept_error(e))),
+                Poll::Pending => return Poll::Pending,
             }
-            Poll::Pending => {}
         }
-        Poll::Pending
+    }
+}
+
+impl Listener for ListenStream {
+    fn handle(&self) -> &ListenerHandle {
+        &self.handle
+    }
+}
+
+impl Drop for ListenStream {
+    fn drop(&mut self) {
+        self.stop_accepting();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::ProxyHeader;
+
+    fn listen(config: Config) -> ListenStream {
+        config.do_listen("127.0.0.1:0".parse().unwrap()).unwrap()
+    }
+
+    /// 在 `timeout` 内取下一个事件
+    async fn next_within(stream: &mut ListenStream, timeout: Duration) -> Option<Event> {
+        match future::select(stream.next(), Delay::new(timeout)).await {
+            Either::Left((event, _)) => event,
+            Either::Right(_) => None,
+        }
+    }
+
+    async fn pair() -> (tokio::net::TcpStream, tokio::net::TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn proxy_header_replaces_remote_addr() {
+        let (mut client, server) = pair().await;
+        let (local_addr, remote_addr) = (server.local_addr().unwrap(), server.peer_addr().unwrap());
+        client
+            .write_all(b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n")
+            .await
+            .unwrap();
+        let (_, _, result) =
+            read_proxy_header(server, local_addr, remote_addr, Duration::from_secs(5)).await;
+        let (stream, remote_addr) = result.unwrap();
+        assert_eq!(remote_addr, "192.0.2.1:56324".parse().unwrap());
+        let header = stream.extensions().get::<ProxyHeader>().unwrap();
+        assert_eq!(
+            header.destination,
+            Some("198.51.100.2:443".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_header_timeout() {
+        let (mut client, server) = pair().await;
+        let (local_addr, remote_addr) = (server.local_addr().unwrap(), server.peer_addr().unwrap());
+        client.write_all(b"PROXY TCP4 192.0.2.1").await.unwrap();
+        let (_, reported, result) =
+            read_proxy_header(server, local_addr, remote_addr, Duration::from_millis(50)).await;
+        assert_eq!(reported, remote_addr);
+        let Err(e) = result else {
+            panic!("expected timeout");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn classifies_accept_errors() {
+        let classify = |e: io::Error| AcceptError::classify(&e);
+        assert!(matches!(
+            classify(io::ErrorKind::ConnectionAborted.into()),
+            AcceptError::Transient
+        ));
+        assert!(matches!(
+            classify(io::ErrorKind::OutOfMemory.into()),
+            AcceptError::ResourceExhausted
+        ));
+        assert!(matches!(
+            classify(io::ErrorKind::InvalidInput.into()),
+            AcceptError::Fatal
+        ));
+        #[cfg(unix)]
+        {
+            let os = io::Error::from_raw_os_error;
+            assert!(matches!(
+                classify(os(libc::EMFILE)),
+                AcceptError::ResourceExhausted
+            ));
+            assert!(matches!(
+                classify(os(libc::ENFILE)),
+                AcceptError::ResourceExhausted
+            ));
+            assert!(matches!(classify(os(libc::EPROTO)), AcceptError::Transient));
+            assert!(matches!(classify(os(libc::EBADF)), AcceptError::Fatal));
+        }
+    }
+
+    #[tokio::test]
+    async fn backs_off_when_resources_exhausted() {
+        let (min, max) = (Duration::from_millis(20), Duration::from_millis(50));
+        let mut stream = listen(Config::new().accept_backoff(min, max));
+        let Some(ListenerEvent::Listened(_)) = stream.next().await else {
+            panic!("expected Listened");
+        };
+
+        let exhausted = || io::Error::from(io::ErrorKind::OutOfMemory);
+        assert!(matches!(
+            stream.on_accept_error(exhausted()),
+            ListenerEvent::Error(_)
+        ));
+        assert!(stream.backoff_timer.is_some());
+        assert_eq!(stream.backoff, min * 2);
+        stream.on_accept_error(exhausted());
+        assert_eq!(stream.backoff, max);
+        stream.on_accept_error(exhausted());
+        assert_eq!(stream.backoff, max);
+
+        // 退避期间不 accept，结束后接受连接并重置退避时间
+        let _client = tokio::net::TcpStream::connect(stream.local_addr())
+            .await
+            .unwrap();
+        assert!(next_within(&mut stream, min / 2).await.is_none());
+        let Some(ListenerEvent::Incoming { .. }) = stream.next().await else {
+            panic!("expected Incoming");
+        };
+        assert_eq!(stream.backoff, min);
+    }
+
+    #[tokio::test]
+    async fn transient_accept_error_keeps_listening() {
+        let mut stream = listen(Config::new());
+        let Some(ListenerEvent::Listened(_)) = stream.next().await else {
+            panic!("expected Listened");
+        };
+        let event = stream.on_accept_error(io::ErrorKind::ConnectionAborted.into());
+        assert!(matches!(event, ListenerEvent::Error(_)));
+        assert!(stream.backoff_timer.is_none());
+
+        let _client = tokio::net::TcpStream::connect(stream.local_addr())
+            .await
+            .unwrap();
+        let Some(ListenerEvent::Incoming { .. }) = stream.next().await else {
+            panic!("expected Incoming");
+        };
+    }
+
+    #[tokio::test]
+    async fn fatal_accept_error_closes_listener() {
+        let mut stream = listen(Config::new());
+        let event = stream.on_accept_error(io::ErrorKind::InvalidInput.into());
+        assert!(matches!(event, ListenerEvent::Closed(Err(_))));
+        assert!(stream.listener.is_none());
+        // 已排队的 Listened 仍会送出，之后监听流结束
+        let Some(ListenerEvent::Listened(_)) = stream.next().await else {
+            panic!("expected Listened");
+        };
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn pauses_accept_at_max_pending_upgrades() {
+        let mut stream = listen(Config::new().max_pending_upgrades(1));
+        let Some(ListenerEvent::Listened(_)) = stream.next().await else {
+            panic!("expected Listened");
+        };
+        let addr = stream.local_addr();
+        let _first = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let _second = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let Some(ListenerEvent::Incoming { guard, .. }) = stream.next().await else {
+            panic!("expected Incoming");
+        };
+        assert_eq!(stream.handle.in_flight(), 1);
+        assert!(
+            next_within(&mut stream, Duration::from_millis(100))
+                .await
+                .is_none()
+        );
+
+        // 升级结束后恢复 accept
+        drop(guard);
+        let Some(ListenerEvent::Incoming { .. }) = stream.next().await else {
+            panic!("expected Incoming");
+        };
     }
 }
@@ -0,0 +1,44 @@
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, RwLock},
+};
+
+/// 端口复用时记录当前的监听地址，拨号时绑定到其中之一，
+/// 使出站连接的源端口与监听端口一致。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PortReuse {
+    listen_addrs: Arc<RwLock<HashSet<SocketAddr>>>,
+}
+
+impl PortReuse {
+    pub(crate) fn register(&self, addr: SocketAddr) {
+        tracing::trace!("Registering for port reuse: {}", addr);
+        self.listen_addrs
+            .write()
+            .expect("port reuse lock poisoned")
+            .insert(addr);
+    }
+
+    pub(crate) fn unregister(&self, addr: SocketAddr) {
+        tracing::trace!("Unregistering for port reuse: {}", addr);
+        self.listen_addrs
+            .write()
+            .expect("port reuse lock poisoned")
+            .remove(&addr);
+    }
+
+    /// 选择拨号 `remote` 时绑定的本地地址：地址族相同，且回环地址只用于拨号回环地址，
+    /// 通配地址可用于任何目标
+    pub(crate) fn local_dial_addr(&self, remote: IpAddr) -> Option<SocketAddr> {
+        let listen_addrs = self.listen_addrs.read().expect("port reuse lock poisoned");
+        listen_addrs
+            .iter()
+            .find(|addr| {
+                addr.is_ipv4() == remote.is_ipv4()
+                    && (addr.ip().is_unspecified()
+                        || addr.ip().is_loopback() == remote.is_loopback())
+            })
+            .copied()
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str,
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use tokio::io::AsyncReadExt;
+
+/// v2 头部的固定签名
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// v1 头部的最大长度，包括结尾的 CRLF
+const V1_MAX_LEN: usize = 107;
+/// v2 头部固定部分的长度
+const V2_HEADER_LEN: usize = 16;
+
+/// 监听器的 PROXY 协议（v1 与 v2）设置。
+///
+/// 来自可信来源的连接必须以 PROXY 协议头开始，监听器读取并解析头部后才产生 `Incoming` 事件，
+/// `remote_addr` 替换为头部中的源地址，头部本身可通过 [`TcpStream::extensions`](crate::TcpStream::extensions)
+/// 获取 [`ProxyHeader`]。来自其他来源的连接不解析头部，原样交给上层。
+#[derive(Debug, Clone)]
+pub struct ProxyProtocol {
+    trusted: Vec<IpNet>,
+    header_timeout: Duration,
+}
+
+impl Default for ProxyProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProxyProtocol {
+    pub fn new() -> Self {
+        ProxyProtocol {
+            trusted: Vec::new(),
+            header_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// 信任来自 `net` 的连接（通常是负载均衡器的地址）
+    pub fn trust(mut self, net: impl Into<IpNet>) -> Self {
+        self.trusted.push(net.into());
+        self
+    }
+
+    /// 信任所有来源，仅在监听器只能由负载均衡器访问时使用
+    pub fn trust_any(self) -> Self {
+        self.trust(IpNet::V4(Default::default()))
+            .trust(IpNet::V6(Default::default()))
+    }
+
+    /// 读取头部的超时时间
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.header_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        self.header_timeout
+    }
+
+    pub(crate) fn is_trusted(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+            addr => addr,
+        };
+        self.trusted.iter().any(|net| net.contains(&addr))
+    }
+}
+
+/// 解析得到的 PROXY 协议头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// 协议版本，1 或 2
+    pub version: u8,
+    /// 原始连接的源地址，v1 的 `UNKNOWN`、v2 的 `LOCAL` 命令或非 IP 地址族时为 `None`
+    pub source: Option<SocketAddr>,
+    /// 原始连接的目的地址
+    pub destination: Option<SocketAddr>,
+    /// v2 头部中的 TLV 字段，按出现的顺序排列
+    pub tlvs: Vec<(u8, Vec<u8>)>,
+}
+
+impl ProxyHeader {
+    /// 第一个类型为 `kind` 的 TLV 的值
+    pub fn tlv(&self, kind: u8) -> Option<&[u8]> {
+        self.tlvs
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// 从套接字读取并解析 PROXY 协议头，返回头部以及多读出的数据
+pub(crate) async fn read_header(
+    stream: &mut tokio::net::TcpStream,
+) -> io::Result<(ProxyHeader, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(V1_MAX_LEN);
+    let mut chunk = [0u8; 512];
+    loop {
+        if let Some((header, consumed)) = parse(&buf)? {
+            buf.drain(..consumed);
+            return Ok((header, buf));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// 解析完整的头部，数据不足时返回 `Ok(None)`
+fn parse(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    let prefix_len = buf.len().min(V2_SIGNATURE.len());
+    if buf[..prefix_len] == V2_SIGNATURE[..prefix_len] {
+        if buf.len() < V2_HEADER_LEN {
+            return Ok(None);
+        }
+        return parse_v2(buf);
+    }
+    let prefix_len = buf.len().min(6);
+    if buf[..prefix_len] == b"PROXY "[..prefix_len] {
+        return parse_v1(buf);
+    }
+    Err(invalid("missing PROXY protocol header"))
+}
+
+fn parse_v1(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        if buf.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+        return Ok(None);
+    };
+    if end + 2 > V1_MAX_LEN {
+        return Err(invalid("PROXY v1 header too long"));
+    }
+    let line = str::from_utf8(&buf[..end]).map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+    let mut parts = line.split(' ').skip(1);
+    let (source, destination) = match parts.next() {
+        Some("UNKNOWN") => (None, None),
+        Some(family @ ("TCP4" | "TCP6")) => {
+            let mut next = || {
+                parts
+                    .next()
+                    .ok_or_else(|| invalid("truncated PROXY v1 header"))
+            };
+            let (src_ip, dst_ip, src_port, dst_port) = (next()?, next()?, next()?, next()?);
+            if parts.next().is_some() {
+                return Err(invalid("trailing data in PROXY v1 header"));
+            }
+            let parse_ip = |ip: &str| -> io::Result<IpAddr> {
+                let ip = if family == "TCP4" {
+                    ip.parse::<Ipv4Addr>().map(IpAddr::V4)
+                } else {
+                    ip.parse::<Ipv6Addr>().map(IpAddr::V6)
+                };
+                ip.map_err(|_| invalid("invalid address in PROXY v1 header"))
+            };
+            let parse_port = |port: &str| -> io::Result<u16> {
+                port.parse()
+                    .map_err(|_| invalid("invalid port in PROXY v1 header"))
+            };
+            (
+                Some(SocketAddr::new(parse_ip(src_ip)?, parse_port(src_port)?)),
+                Some(SocketAddr::new(parse_ip(dst_ip)?, parse_port(dst_port)?)),
+            )
+        }
+        _ => return Err(invalid("unknown protocol in PROXY v1 header")),
+    };
+    let header = ProxyHeader {
+        version: 1,
+        source,
+        destination,
+        tlvs: Vec::new(),
+    };
+    Ok(Some((header, end + 2)))
+}
+
+fn parse_v2(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    let version_command = buf[12];
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let is_local = match version_command & 0x0f {
+        0x0 => true,
+        0x1 => false,
+        _ => return Err(invalid("unknown PROXY v2 command")),
+    };
+    let family = buf[13] >> 4;
+    // 监听器只处理 TCP，只接受 STREAM 与 `LOCAL` 命令常用的 UNSPEC
+    match buf[13] & 0x0f {
+        0x0 | 0x1 => {}
+        _ => return Err(invalid("unsupported PROXY v2 transport protocol")),
+    }
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    let total = V2_HEADER_LEN + len;
+    if buf.len() < total {
+        return Ok(None);
+    }
+    let payload = &buf[V2_HEADER_LEN..total];
+    let (addresses, tlvs) = match family {
+        // AF_INET
+        0x1 => split(payload, 12, "truncated PROXY v2 addresses")?,
+        // AF_INET6
+        0x2 => split(payload, 36, "truncated PROXY v2 addresses")?,
+        // AF_UNIX
+        0x3 => split(payload, 216, "truncated PROXY v2 addresses")?,
+        // AF_UNSPEC
+        0x0 => (&payload[..0], payload),
+        _ => return Err(invalid("unknown PROXY v2 address family")),
+    };
+    let (source, destination) = match (is_local, family) {
+        (false, 0x1) => {
+            let ip = |at: usize| {
+                IpAddr::from(<[u8; 4]>::try_from(&addresses[at..at + 4]).expect("length checked"))
+            };
+            let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+            (
+                Some(SocketAddr::new(ip(0), port(8))),
+                Some(SocketAddr::new(ip(4), port(10))),
+            )
+        }
+        (false, 0x2) => {
+            let ip = |at: usize| {
+                IpAddr::from(<[u8; 16]>::try_from(&addresses[at..at + 16]).expect("length checked"))
+            };
+            let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+            (
+                Some(SocketAddr::new(ip(0), port(32))),
+                Some(SocketAddr::new(ip(16), port(34))),
+            )
+        }
+        _ => (None, None),
+    };
+    let header = ProxyHeader {
+        version: 2,
+        source,
+        destination,
+        tlvs: parse_tlvs(tlvs)?,
+    };
+    Ok(Some((header, total)))
+}
+
+fn split<'a>(
+    payload: &'a [u8],
+    at: usize,
+    message: &'static str,
+) -> io::Result<(&'a [u8], &'a [u8])> {
+    if payload.len() < at {
+        return Err(invalid(message));
+    }
+    Ok(payload.split_at(at))
+}
+
+fn parse_tlvs(mut input: &[u8]) -> io::Result<Vec<(u8, Vec<u8>)>> {
+    let mut tlvs = Vec::new();
+    while !input.is_empty() {
+        if input.len() < 3 {
+            return Err(invalid("truncated PROXY v2 TLV"));
+        }
+        let kind = input[0];
+        let len = u16::from_be_bytes([input[1], input[2]]) as usize;
+        let (value, rest) = split(&input[3..], len, "truncated PROXY v2 TLV")?;
+        tlvs.push((kind, value.to_vec()));
+        input = rest;
+    }
+    Ok(tlvs)
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    fn v2(command: u8, family_transport: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(0x20 | command);
+        buf.push(family_transport);
+        buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn addr(s: &str) -> Option<SocketAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_v1() {
+        let cases = [
+            (
+                &b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\nhello"[..],
+                addr("192.0.2.1:56324"),
+                addr("198.51.100.2:443"),
+                45,
+            ),
+            (
+                b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n",
+                addr("[2001:db8::1]:56324"),
+                addr("[2001:db8::2]:443"),
+                46,
+            ),
+            (b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n", None, None, 35),
+        ];
+        for (input, source, destination, consumed) in cases {
+            let (header, len) = parse(input).unwrap().unwrap();
+            assert_eq!(header.version, 1);
+            assert_eq!((header.source, header.destination), (source, destination));
+            assert!(header.tlvs.is_empty());
+            assert_eq!(len, consumed);
+        }
+    }
+
+    #[test]
+    fn parses_v2() {
+        // PROXY，IPv4，带两个 TLV
+        let mut payload = vec![192, 0, 2, 1, 198, 51, 100, 2, 0xdc, 0x04, 0x01, 0xbb];
+        payload.extend_from_slice(&[0x02, 0x00, 0x03, b'f', b'o', b'o']);
+        payload.extend_from_slice(&[0xea, 0x00, 0x00]);
+        let mut input = v2(0x1, 0x11, &payload);
+        let total = input.len();
+        input.extend_from_slice(b"rest");
+        let (header, len) = parse(&input).unwrap().unwrap();
+        assert_eq!(len, total);
+        assert_eq!(header.version, 2);
+        assert_eq!(header.source, addr("192.0.2.1:56324"));
+        assert_eq!(header.destination, addr("198.51.100.2:443"));
+        assert_eq!(header.tlvs, [(0x02, b"foo".to_vec()), (0xea, Vec::new())]);
+        assert_eq!(header.tlv(0x02), Some(&b"foo"[..]));
+        assert_eq!(header.tlv(0x01), None);
+
+        // PROXY，IPv6
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dst: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let mut payload = src.octets().to_vec();
+        payload.extend_from_slice(&dst.octets());
+        payload.extend_from_slice(&[0xdc, 0x04, 0x01, 0xbb]);
+        let (header, _) = parse(&v2(0x1, 0x21, &payload)).unwrap().unwrap();
+        assert_eq!(header.source, addr("[2001:db8::1]:56324"));
+        assert_eq!(header.destination, addr("[2001:db8::2]:443"));
+
+        // LOCAL 忽略地址
+        let (header, _) = parse(&v2(0x0, 0x00, &[])).unwrap().unwrap();
+        assert_eq!((header.source, header.destination), (None, None));
+        let (header, _) = parse(&v2(0x0, 0x11, &[0; 12])).unwrap().unwrap();
+        assert_eq!((header.source, header.destination), (None, None));
+    }
+
+    #[test]
+    fn incomplete_input() {
+        let full_v1 = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n";
+        let full_v2 = v2(0x1, 0x11, &[0; 12]);
+        for input in [
+            &b""[..],
+            b"PRO",
+            b"PROXY ",
+            &full_v1[..full_v1.len() - 1],
+            &V2_SIGNATURE[..5],
+            &full_v2[..V2_HEADER_LEN - 1],
+            &full_v2[..full_v2.len() - 1],
+        ] {
+            assert!(parse(input).unwrap().is_none(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let long_v1 = [&b"PROXY TCP4 "[..], &[b'1'; 100], b"\r\n"].concat();
+        let cases: [Vec<u8>; 15] = [
+            // 签名错误
+            b"GET / HTTP/1.1\r\n".to_vec(),
+            b"\r\n\r\n\0\r\nQUIX\n\x21\x11\x00\x00".to_vec(),
+            // v1 过长
+            [&b"PROXY TCP4 "[..], &[b'1'; 100]].concat(),
+            long_v1,
+            // v1 内容错误
+            b"PROXY UDP4 192.0.2.1 198.51.100.2 1 2\r\n".to_vec(),
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 1\r\n".to_vec(),
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 1 2 3\r\n".to_vec(),
+            b"PROXY TCP4 2001:db8::1 198.51.100.2 1 2\r\n".to_vec(),
+            b"PROXY TCP4 192.0.2.1 198.51.100.2 1 65536\r\n".to_vec(),
+            // v2 版本、命令、地址族或传输协议错误
+            {
+                let mut input = v2(0x1, 0x11, &[0; 12]);
+                input[12] = 0x11;
+                input
+            },
+            v2(0x2, 0x11, &[0; 12]),
+            v2(0x1, 0x41, &[0; 12]),
+            v2(0x1, 0x12, &[0; 12]),
+            // 地址或 TLV 长度超出头部长度
+            v2(0x1, 0x21, &[0; 12]),
+            v2(
+                0x1,
+                0x11,
+                &[[0; 12].as_slice(), &[0x01, 0x00, 0x05, 0]].concat(),
+            ),
+        ];
+        for input in cases {
+            let e = parse(&input).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{input:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn read_header_keeps_leftover() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\n";
+        client.write_all(&header[..10]).await.unwrap();
+        let read = tokio::spawn(async move { read_header(&mut server).await });
+        tokio::task::yield_now().await;
+        client.write_all(&header[10..]).await.unwrap();
+        client.write_all(b"data").await.unwrap();
+        let (header, leftover) = read.await.unwrap().unwrap();
+        assert_eq!(header.source, addr("192.0.2.1:56324"));
+        // 可能还有部分数据留在套接字中
+        assert!(b"data".starts_with(&leftover));
+
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        client.write_all(b"PROXY TCP4").await.unwrap();
+        drop(client);
+        let e = read_header(&mut server).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,4 +1,6 @@
+use airio_core::Extensions;
 use futures::{AsyncRead, AsyncWrite};
+use socket2::SockRef;
 use std::{
     io,
     pin::Pin,
@@ -6,11 +8,46 @@ use std::{
 };
 
 #[derive(Debug)]
-pub struct TcpStream(tokio::net::TcpStream);
+pub struct TcpStream {
+    inner: tokio::net::TcpStream,
+    /// 读取 PROXY 协议头时多读出的数据，先于套接字中的数据返回
+    read_buffer: Vec<u8>,
+    read_pos: usize,
+    extensions: Extensions,
+}
+
+impl TcpStream {
+    pub(crate) fn with_buffered(
+        inner: tokio::net::TcpStream,
+        read_buffer: Vec<u8>,
+        extensions: Extensions,
+    ) -> Self {
+        TcpStream {
+            inner,
+            read_buffer,
+            read_pos: 0,
+            extensions,
+        }
+    }
+
+    /// 连接的附加信息，例如 [`ProxyHeader`](crate::ProxyHeader)
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// 底层套接字，用于读取或调整套接字选项
+    pub fn socket(&self) -> SockRef<'_> {
+        SockRef::from(&self.inner)
+    }
+}
 
 impl From<tokio::net::TcpStream> for TcpStream {
     fn from(t: tokio::net::TcpStream) -> TcpStream {
-        TcpStream(t)
+        TcpStream::with_buffered(t, Vec::new(), Extensions::new())
     }
 }
 
@@ -20,9 +57,21 @@ impl AsyncRead for TcpStream {
         cx: &mut Context,
         buf: &mut [u8],
     ) -> Poll<Result<usize, io::Error>> {
+        let this = &mut *self;
+        if this.read_pos < this.read_buffer.len() {
+            let buffered = &this.read_buffer[this.read_pos..];
+            let n = buffered.len().min(buf.len());
+            buf[..n].copy_from_slice(&buffered[..n]);
+            this.read_pos += n;
+            if this.read_pos == this.read_buffer.len() {
+                this.read_buffer = Vec::new();
+                this.read_pos = 0;
+            }
+            return Poll::Ready(Ok(n));
+        }
         let mut read_buf = tokio::io::ReadBuf::new(buf);
         futures::ready!(tokio::io::AsyncRead::poll_read(
-            Pin::new(&mut self.0),
+            Pin::new(&mut this.inner),
             cx,
             &mut read_buf
         ))?;
@@ -36,15 +85,15 @@ impl AsyncWrite for TcpStream {
         cx: &mut Context,
         buf: &[u8],
     ) -> Poll<Result<usize, io::Error>> {
-        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.inner), cx, buf)
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
-        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.inner), cx)
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
-        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.inner), cx)
     }
 
     fn poll_write_vectored(
@@ -52,6 +101,6 @@ impl AsyncWrite for TcpStream {
         cx: &mut Context<'_>,
         bufs: &[io::IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
-        tokio::io::AsyncWrite::poll_write_vectored(Pin::new(&mut self.0), cx, bufs)
+        tokio::io::AsyncWrite::poll_write_vectored(Pin::new(&mut self.inner), cx, bufs)
     }
 }
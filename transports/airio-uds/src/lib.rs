@@ -0,0 +1,286 @@
+#![cfg(unix)]
+
+mod listener;
+mod stream;
+
+use std::{
+    ffi::OsStr,
+    io,
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
+    path::{Path, PathBuf},
+};
+
+use airio_core::{Multiaddr, Transport, TransportError, multiaddr};
+use futures::{
+    FutureExt,
+    future::{BoxFuture, Ready},
+};
+use socket2::{Domain, SockAddr, Socket, Type};
+use tokio::net::UnixListener;
+
+pub use listener::ListenStream;
+pub use stream::{PeerCredentials, UnixStream};
+
+/// Unix 域套接字 Transport，使用 `/unix/<path>` 地址。
+///
+/// 路径以 `@` 开头时表示 Linux 抽象命名空间，例如 `/unix/@airio`。
+#[derive(Clone, Debug)]
+pub struct Config {
+    backlog: u32,
+    remove_stale: bool,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            backlog: 1024,
+            remove_stale: true,
+        }
+    }
+
+    pub fn listen_backlog(mut self, backlog: u32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// 监听前是否清理残留的套接字文件，默认开启。
+    ///
+    /// 只有当文件是套接字且无法连接时才会被删除。
+    pub fn remove_stale(mut self, value: bool) -> Self {
+        self.remove_stale = value;
+        self
+    }
+
+    fn do_listen(&self, path: SocketPath, addr: Multiaddr) -> io::Result<ListenStream> {
+        if let SocketPath::Pathname(path) = &path
+            && self.remove_stale
+        {
+            remove_stale_socket(path)?;
+        }
+        let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+        socket.bind(&path.to_sockaddr()?)?;
+        socket.listen(self.backlog as _)?;
+        socket.set_nonblocking(true)?;
+        let listener = UnixListener::from_std(socket.into())?;
+        let socket_file = match path {
+            SocketPath::Pathname(path) => Some(path),
+            SocketPath::Abstract(_) => None,
+        };
+        Ok(ListenStream::new(listener, addr, socket_file))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for Config {
+    type Output = UnixStream;
+    type Error = io::Error;
+    type Dialer = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+    type Listener = ListenStream;
+
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let path = multiaddr_to_path(&addr).ok_or(TransportError::Unsupported(addr))?;
+        let fut = async move {
+            let stream = tokio::net::UnixStream::connect(path.as_os_path()).await?;
+            UnixStream::new(stream)
+        }
+        .boxed();
+        Ok(fut)
+    }
+
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let path = multiaddr_to_path(&addr).ok_or(TransportError::Unsupported(addr.clone()))?;
+        self.do_listen(path, addr).map_err(TransportError::Other)
+    }
+}
+
+/// 套接字路径，文件系统路径或抽象命名空间名称
+#[derive(Clone, Debug)]
+enum SocketPath {
+    Pathname(PathBuf),
+    Abstract(Vec<u8>),
+}
+
+impl SocketPath {
+    /// 转换为系统使用的路径形式，抽象命名空间以 `\0` 开头
+    fn as_os_path(&self) -> PathBuf {
+        match self {
+            SocketPath::Pathname(path) => path.clone(),
+            SocketPath::Abstract(name) => {
+                let mut bytes = Vec::with_capacity(name.len() + 1);
+                bytes.push(0);
+                bytes.extend_from_slice(name);
+                PathBuf::from(OsStr::from_bytes(&bytes))
+            }
+        }
+    }
+
+    fn to_sockaddr(&self) -> io::Result<SockAddr> {
+        SockAddr::unix(self.as_os_path())
+    }
+}
+
+/// 将 `/unix/<path>` 形式的地址转换为 [`SocketPath`]
+fn multiaddr_to_path(addr: &Multiaddr) -> Option<SocketPath> {
+    match addr.protocols() {
+        [multiaddr::Protocol::Unix(path)] => match path.strip_prefix('@') {
+            Some(name) if cfg!(any(target_os = "linux", target_os = "android")) => {
+                Some(SocketPath::Abstract(name.as_bytes().to_vec()))
+            }
+            Some(_) => None,
+            None => Some(SocketPath::Pathname(PathBuf::from(path))),
+        },
+        _ => None,
+    }
+}
+
+/// 删除无人监听的残留套接字文件，仍在使用的套接字返回 `AddrInUse`
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another listener", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            tracing::debug!("Removing stale socket file {}", path.display());
+            std::fs::remove_file(path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use airio_core::ListenerEvent;
+    use futures::{AsyncReadExt, AsyncWriteExt, StreamExt};
+
+    use super::*;
+
+    /// 临时目录中本进程独有的套接字路径
+    fn socket_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("airio-uds-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn unix_addr(path: &str) -> Multiaddr {
+        multiaddr::Protocol::Unix(path.to_owned()).into()
+    }
+
+    fn assert_current_process(credentials: PeerCredentials) {
+        assert_eq!(credentials.pid(), Some(std::process::id() as i32));
+        // SAFETY: getuid 与 getgid 总是成功且没有副作用
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        assert_eq!(credentials.uid(), uid);
+        assert_eq!(credentials.gid(), gid);
+    }
+
+    /// 在 `addr` 上监听并建立一个连接，互相发送数据后检查对端凭据
+    async fn round_trip(addr: Multiaddr) -> ListenStream {
+        let transport = Config::new();
+        let mut listener = transport.listen(addr.clone()).unwrap();
+        let Some(ListenerEvent::Listened(listened)) = listener.next().await else {
+            panic!("expected Listened");
+        };
+        assert_eq!(listened, addr);
+
+        let mut dialed = transport.connect(addr).unwrap().await.unwrap();
+        let Some(ListenerEvent::Incoming { upgrade, .. }) = listener.next().await else {
+            panic!("expected Incoming");
+        };
+        let mut accepted = upgrade.await.unwrap();
+
+        dialed.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        accepted.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        accepted.write_all(b"pong").await.unwrap();
+        dialed.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        assert_current_process(dialed.peer_credentials());
+        assert_current_process(accepted.peer_credentials());
+        listener
+    }
+
+    #[tokio::test]
+    async fn listen_and_connect_on_path() {
+        let path = socket_path("pathname");
+        let listener = round_trip(unix_addr(path.to_str().unwrap())).await;
+        assert!(path.exists());
+        drop(listener);
+        assert!(!path.exists(), "socket file removed on drop");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[tokio::test]
+    async fn listen_and_connect_on_abstract_socket() {
+        let name = format!("@airio-uds-{}", std::process::id());
+        let _listener = round_trip(unix_addr(&name)).await;
+        assert!(!Path::new(&name[1..]).exists());
+    }
+
+    #[tokio::test]
+    async fn removes_stale_socket() {
+        let path = socket_path("stale");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = Config::new().listen(unix_addr(path.to_str().unwrap()));
+        assert!(listener.is_ok());
+    }
+
+    #[tokio::test]
+    async fn keeps_live_socket() {
+        let path = socket_path("live");
+        let live = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let Err(TransportError::Other(e)) = Config::new().listen(unix_addr(path.to_str().unwrap()))
+        else {
+            panic!("expected listen to fail");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::AddrInUse);
+        assert!(path.exists());
+        drop(live);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_socket_kept_when_disabled() {
+        let path = socket_path("disabled");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let result = Config::new()
+            .remove_stale(false)
+            .listen(unix_addr(path.to_str().unwrap()));
+        assert!(
+            matches!(result, Err(TransportError::Other(e)) if e.kind() == io::ErrorKind::AddrInUse)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_addresses() {
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/80".parse().unwrap();
+        assert!(matches!(
+            Config::new().connect(addr),
+            Err(TransportError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+use airio_core::{
+    ListenerEvent, Multiaddr,
+    multiaddr::Protocol,
+    transport::{Listener, ListenerHandle},
+};
+use futures::{
+    Stream,
+    future::{self, Ready},
+};
+use std::{
+    io,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::net::UnixListener;
+
+use crate::UnixStream;
+
+pub struct ListenStream {
+    listener_addr: Multiaddr,
+    /// 开始关闭后置为 `None`，不再接受新连接
+    listener: Option<UnixListener>,
+    handle: ListenerHandle,
+    /// 监听的套接字文件，关闭时删除；抽象命名空间为 `None`
+    socket_file: Option<PathBuf>,
+    pending_event: Option<ListenerEvent<Ready<Result<UnixStream, io::Error>>, io::Error>>,
+    closed: bool,
+}
+
+impl ListenStream {
+    pub(crate) fn new(
+        listener: UnixListener,
+        listener_addr: Multiaddr,
+        socket_file: Option<PathBuf>,
+    ) -> Self {
+        let listened_event = ListenerEvent::Listened(listener_addr.clone());
+        ListenStream {
+            listener_addr,
+            listener: Some(listener),
+            handle: ListenerHandle::new(),
+            socket_file,
+            pending_event: Some(listened_event),
+            closed: false,
+        }
+    }
+
+    /// 停止接受新连接并删除套接字文件
+    fn stop_accepting(&mut self) {
+        self.listener = None;
+        if let Some(path) = self.socket_file.take()
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            tracing::debug!("Failed to remove socket file {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl Stream for ListenStream {
+    type Item = ListenerEvent<Ready<Result<UnixStream, io::Error>>, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(event) = self.pending_event.take() {
+            return Poll::Ready(Some(event));
+        }
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        if self.handle.poll_closed(cx).is_ready() {
+            self.stop_accepting();
+            self.closed = true;
+            return Poll::Ready(Some(ListenerEvent::Closed(Ok(()))));
+        }
+        if self.handle.is_closing() {
+            self.stop_accepting();
+        }
+        let Some(listener) = self.listener.as_ref() else {
+            return Poll::Pending;
+        };
+        tracing::trace!(
+            "ListenStream::poll_next: Polling for new connections on {}",
+            self.listener_addr
+        );
+        match listener.poll_accept(cx) {
+            Poll::Ready(Ok((stream, remote_addr))) => {
+                // 客户端通常不绑定路径，此时使用监听地址作为远端地址
+                let remote_addr = remote_addr
+                    .as_pathname()
+                    .and_then(|path| path.to_str())
+                    .map(|path| Protocol::Unix(path.to_owned()).into())
+                    .unwrap_or_else(|| self.listener_addr.clone());
+                Poll::Ready(Some(ListenerEvent::Incoming {
+                    local_addr: self.listener_addr.clone(),
+                    remote_addr,
+                    upgrade: future::ready(UnixStream::new(stream)),
+                    guard: self.handle.begin_incoming(),
+                }))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Some(ListenerEvent::Error(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Listener for ListenStream {
+    fn handle(&self) -> &ListenerHandle {
+        &self.handle
+    }
+}
+
+impl Drop for ListenStream {
+    fn drop(&mut self) {
+        self.stop_accepting();
+    }
+}
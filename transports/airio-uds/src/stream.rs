@@ -0,0 +1,96 @@
+use futures::{AsyncRead, AsyncWrite};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// 对端进程的凭据，在连接建立时通过 `SO_PEERCRED`（或平台等价接口）获取
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    pid: Option<i32>,
+    uid: u32,
+    gid: u32,
+}
+
+impl PeerCredentials {
+    /// 对端进程 ID，部分平台不支持时为 `None`
+    pub fn pid(&self) -> Option<i32> {
+        self.pid
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+}
+
+#[derive(Debug)]
+pub struct UnixStream {
+    inner: tokio::net::UnixStream,
+    peer_credentials: PeerCredentials,
+}
+
+impl UnixStream {
+    pub(crate) fn new(inner: tokio::net::UnixStream) -> io::Result<Self> {
+        let cred = inner.peer_cred()?;
+        Ok(UnixStream {
+            inner,
+            peer_credentials: PeerCredentials {
+                pid: cred.pid(),
+                uid: cred.uid(),
+                gid: cred.gid(),
+            },
+        })
+    }
+
+    /// 对端进程的凭据，可在升级链中（例如 `and_then`）用于鉴权
+    pub fn peer_credentials(&self) -> PeerCredentials {
+        self.peer_credentials
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let mut read_buf = tokio::io::ReadBuf::new(buf);
+        futures::ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.inner),
+            cx,
+            &mut read_buf
+        ))?;
+        Poll::Ready(Ok(read_buf.filled().len()))
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.inner), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.inner), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.inner), cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write_vectored(Pin::new(&mut self.inner), cx, bufs)
+    }
+}
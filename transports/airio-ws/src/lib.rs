@@ -1,16 +1,25 @@
 use std::{
+    io,
     net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use airio_core::{ListenerEvent, Transport, utils::RwStreamSink};
+use airio_core::{
+    ListenerEvent, Multiaddr, Transport, TransportError,
+    multiaddr::Protocol,
+    transport::{Listener, ListenerHandle},
+    utils::RwStreamSink,
+};
 use airio_tcp::TcpStream;
 use async_tungstenite::{
     accept_async_with_config, client_async_with_config,
     tungstenite::{self, protocol::WebSocketConfig},
 };
-use futures::{FutureExt, Stream, TryFutureExt};
+use futures::{
+    FutureExt, Stream, TryFutureExt,
+    future::{self, Either},
+};
 
 use crate::framed::BytesWebSocketStream;
 pub use tungstenite::Error;
@@ -85,15 +94,23 @@ impl Transport for Config {
     type ListenerUpgrade = ListenerUpgrade;
     type Listener = ListenStream;
 
-    fn connect(&self, addr: SocketAddr) -> Result<Self::Dialer, Self::Error> {
-        let dialer = self.tcp.connect(addr)?;
-        let config = self.websocket.clone();
+    fn connect(&self, addr: Multiaddr) -> Result<Self::Dialer, TransportError<Self::Error>> {
+        let Some(WsAddress {
+            tcp_addr,
+            authority,
+            path,
+        }) = parse_ws_addr(&addr)
+        else {
+            return Err(TransportError::Unsupported(addr));
+        };
+        let dialer = self.tcp.connect(tcp_addr).map_err(|e| e.map(Error::from))?;
+        let config = self.websocket;
         let request = tungstenite::http::Uri::builder()
             .scheme("ws")
-            .authority(addr.to_string())
-            .path_and_query("/")
+            .authority(authority)
+            .path_and_query(path)
             .build()
-            .map_err(tungstenite::Error::from)?;
+            .map_err(|e| TransportError::Other(e.into()))?;
         tracing::debug!("Connecting to WebSocket at {}", request);
         Ok(dialer
             .map_err(tungstenite::Error::from)
@@ -106,11 +123,14 @@ impl Transport for Config {
             .boxed())
     }
 
-    fn listen(&self, addr: SocketAddr) -> Result<Self::Listener, Self::Error> {
-        let listener = self.tcp.listen(addr)?;
+    fn listen(&self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let Some(WsAddress { tcp_addr, .. }) = parse_ws_addr(&addr) else {
+            return Err(TransportError::Unsupported(addr));
+        };
+        let listener = self.tcp.listen(tcp_addr).map_err(|e| e.map(Error::from))?;
         tracing::debug!("Listening for WebSocket connections on {}", addr);
         Ok(ListenStream {
-            config: self.websocket.clone(),
+            config: self.websocket,
             inner: listener,
         })
     }
@@ -125,20 +145,91 @@ impl Stream for ListenStream {
     type Item = ListenerEvent<ListenerUpgrade, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let config = self.config.clone();
+        let config = self.config;
         let event = match Pin::new(&mut self.inner).poll_next(cx) {
-            Poll::Ready(Some(event)) => event
-                .map_upgrade(|u| {
-                    u.map_err(Error::from)
+            Poll::Ready(Some(event)) => match event {
+                ListenerEvent::Listened(addr) => ListenerEvent::Listened(addr.with(Protocol::Ws)),
+                ListenerEvent::AddressAdded(addr) => {
+                    ListenerEvent::AddressAdded(addr.with(Protocol::Ws))
+                }
+                ListenerEvent::AddressExpired(addr) => {
+                    ListenerEvent::AddressExpired(addr.with(Protocol::Ws))
+                }
+                ListenerEvent::Incoming {
+                    local_addr,
+                    remote_addr,
+                    upgrade,
+                    guard,
+                } => {
+                    // 握手完成前计入监听器的进行中升级，监听器关闭超时后中止握手
+                    let handshake_guard = guard.clone();
+                    let handshake = upgrade
+                        .map_err(Error::from)
                         .and_then(move |stream| accept_async_with_config(stream, Some(config)))
-                        .map_ok(BytesWebSocketStream::new)
-                        .map_ok(RwStreamSink::new)
-                        .boxed()
-                })
-                .map_err(Error::from),
+                        .map_ok(|stream| RwStreamSink::new(BytesWebSocketStream::new(stream)));
+                    let upgrade = async move {
+                        let aborted = future::poll_fn(|cx| handshake_guard.poll_aborted(cx));
+                        match future::select(Box::pin(handshake), aborted).await {
+                            Either::Left((output, _)) => output,
+                            Either::Right(_) => {
+                                Err(Error::Io(io::ErrorKind::ConnectionAborted.into()))
+                            }
+                        }
+                    };
+                    ListenerEvent::Incoming {
+                        local_addr: local_addr.with(Protocol::Ws),
+                        remote_addr: remote_addr.with(Protocol::Ws),
+                        upgrade: upgrade.boxed(),
+                        guard,
+                    }
+                }
+                ListenerEvent::Closed(result) => ListenerEvent::Closed(result.map_err(Error::from)),
+                ListenerEvent::Error(err) => ListenerEvent::Error(Error::from(err)),
+            },
             Poll::Ready(None) => return Poll::Ready(None),
             Poll::Pending => return Poll::Pending,
         };
         Poll::Ready(Some(event))
     }
 }
+
+impl Listener for ListenStream {
+    fn handle(&self) -> &ListenerHandle {
+        self.inner.handle()
+    }
+}
+
+struct WsAddress {
+    tcp_addr: Multiaddr,
+    /// 用于请求 URI 与 `Host` 头的地址，存在 `/sni` 时使用其中的主机名
+    authority: String,
+    path: String,
+}
+
+/// 解析 `<ip>/tcp/<port>[/sni/<host>]/ws[/http-path/<path>]` 形式的地址
+fn parse_ws_addr(addr: &Multiaddr) -> Option<WsAddress> {
+    let [ip, Protocol::Tcp(port), rest @ ..] = addr.protocols() else {
+        return None;
+    };
+    let ip = match ip {
+        Protocol::Ip4(ip) => (*ip).into(),
+        Protocol::Ip6(ip) => (*ip).into(),
+        _ => return None,
+    };
+    let (authority, rest) = match rest {
+        [Protocol::Sni(host), Protocol::Ws, rest @ ..] => (format!("{host}:{port}"), rest),
+        [Protocol::Ws, rest @ ..] => (SocketAddr::new(ip, *port).to_string(), rest),
+        _ => return None,
+    };
+    let path = match rest {
+        [] => "/".to_owned(),
+        [Protocol::HttpPath(path)] if path.starts_with('/') => path.clone(),
+        [Protocol::HttpPath(path)] => format!("/{path}"),
+        _ => return None,
+    };
+    Some(WsAddress {
+        tcp_addr: addr.iter().take(2).cloned().collect(),
+        authority,
+        path,
+    })
+}